@@ -0,0 +1,51 @@
+//! Times [MerkleHasher::hash_pairs] of [Keccak256Hasher] against hashing the same pairs one at a
+//! time, over a level of 2^16 pairs. Build with `RUSTFLAGS="-C target-cpu=native"` on an AVX2 machine
+//! to measure the four-lane permutation; other targets hash the pairs one by one in both cases.
+
+use std::time::{Duration, Instant};
+
+use alloy_primitives::B256;
+use simple_merkle_tree::{Keccak256Hasher, MerkleHasher};
+
+const PAIRS: usize = 1 << 16;
+const RUNS: usize = 20;
+
+/// Returns the fastest of [RUNS] runs of `hash_level`.
+fn fastest(mut hash_level: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            hash_level();
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one run")
+}
+
+fn main() {
+    let children: Vec<[B256; 2]> = (0..PAIRS as u64)
+        .map(|index| {
+            let left = B256::left_padding_from(&index.to_be_bytes());
+            [left, B256::right_padding_from(&index.to_le_bytes())]
+        })
+        .collect();
+    let mut parents = vec![B256::ZERO; PAIRS];
+
+    let batched = fastest(|| {
+        Keccak256Hasher::hash_pairs(&children, &mut parents);
+        std::hint::black_box(&parents);
+    });
+    let serial = fastest(|| {
+        for ([left, right], parent) in children.iter().zip(&mut parents) {
+            *parent = Keccak256Hasher::hash_pair(left, right);
+        }
+        std::hint::black_box(&parents);
+    });
+
+    println!("hash_pairs: {batched:?} per level of {PAIRS} pairs");
+    println!("hash_pair:  {serial:?} per level of {PAIRS} pairs");
+    println!(
+        "speedup:    {:.2}x",
+        serial.as_secs_f64() / batched.as_secs_f64()
+    );
+}
@@ -0,0 +1,91 @@
+#ifndef IMT_H
+#define IMT_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a fallible FFI call.
+typedef enum {
+  // The call succeeded
+  ImtStatus_Ok = 0,
+  // A required pointer was null
+  ImtStatus_NullPointer = 1,
+  // The tree cannot hold any more leaves
+  ImtStatus_TreeFull = 2,
+  // No leaf has been appended at the index
+  ImtStatus_IndexOutOfBounds = 3,
+  // The output buffer is too small
+  ImtStatus_BufferTooSmall = 4,
+} ImtStatus;
+
+// An opaque handle to a keccak [DynIncrementalMerkleTree].
+typedef struct ImtTree ImtTree;
+
+// Create a new, empty tree of the given `height`.
+//
+// Returns null if the height is too large. Free the tree with [imt_tree_free].
+ImtTree *imt_tree_new(uintptr_t height);
+
+// Free a tree created with [imt_tree_new]. Passing null does nothing.
+//
+// # Safety
+// `tree` must be null or a pointer returned by [imt_tree_new] that has not been freed yet.
+void imt_tree_free(ImtTree *tree);
+
+// Returns the height of `tree`, or `0` if it is null.
+//
+// # Safety
+// `tree` must be null or a live pointer returned by [imt_tree_new].
+uintptr_t imt_tree_height(const ImtTree *tree);
+
+// Returns the number of leaves appended to `tree`, or `0` if it is null.
+//
+// # Safety
+// `tree` must be null or a live pointer returned by [imt_tree_new].
+uintptr_t imt_tree_len(const ImtTree *tree);
+
+// Append the 32-byte `leaf` to `tree`.
+//
+// # Safety
+// `tree` must be null or a live pointer returned by [imt_tree_new], and `leaf` must be null or
+// point to 32 readable bytes.
+ImtStatus imt_tree_append(ImtTree *tree, const uint8_t *leaf);
+
+// Write the 32-byte root of `tree` to `root_out`.
+//
+// # Safety
+// `tree` must be null or a live pointer returned by [imt_tree_new], and `root_out` must be null or
+// point to 32 writable bytes.
+ImtStatus imt_tree_root(const ImtTree *tree, uint8_t *root_out);
+
+// Write the leaf at `index` of `tree` to `leaf_out`, and its siblings from the leaf level upwards to
+// `siblings_out`, 32 bytes each. `siblings_capacity` is the number of siblings `siblings_out` can
+// hold, which must be at least the height of the tree.
+//
+// # Safety
+// `tree` must be null or a live pointer returned by [imt_tree_new], `leaf_out` must be null or
+// point to 32 writable bytes, and `siblings_out` must be null or point to `32 * siblings_capacity`
+// writable bytes.
+ImtStatus imt_tree_prove(ImtTree *tree,
+                         uintptr_t index,
+                         uint8_t *leaf_out,
+                         uint8_t *siblings_out,
+                         uintptr_t siblings_capacity);
+
+// Returns `true` if the `height` siblings at `siblings` show that the 32-byte `leaf` sits at `index`
+// in the keccak tree committed to by the 32-byte `root`. Returns `false` if a pointer is null.
+//
+// # Safety
+// `root` and `leaf` must be null or point to 32 readable bytes, and `siblings` must be null or point
+// to `32 * height` readable bytes.
+bool imt_verify(const uint8_t *root,
+                const uint8_t *leaf,
+                uintptr_t index,
+                const uint8_t *siblings,
+                uintptr_t height);
+
+#endif  /* IMT_H */
@@ -0,0 +1,57 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+
+use crate::IncrementalMerkleTreeError;
+
+/// [AppendLog] is a durable sink for the leaves accepted by an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree), written ahead of the tree by
+/// [IncrementalMerkleTree::append_logged](crate::IncrementalMerkleTree::append_logged). Implement it
+/// over a file, a database or a message queue, and replay its entries with
+/// [IncrementalMerkleTree::recover_from_log](crate::IncrementalMerkleTree::recover_from_log) after a
+/// crash.
+pub trait AppendLog {
+    /// Durably record `leaf`, about to be appended at `index`. The leaf is only appended once this
+    /// returns `Ok`.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf is recorded.
+    /// - `Err(IncrementalMerkleTreeError::LogWriteFailed)` if it could not be recorded.
+    fn record(&mut self, index: usize, leaf: B256) -> Result<(), IncrementalMerkleTreeError>;
+}
+
+/// [MemoryLog] is an [AppendLog] keeping its entries on the heap, for tests and for logs that are
+/// persisted by other means.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryLog {
+    /// The recorded entries, as `(index, leaf)` pairs in the order they were recorded
+    entries: Vec<(usize, B256)>,
+}
+
+impl MemoryLog {
+    /// Create a new, empty [MemoryLog].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the recorded entries, as `(index, leaf)` pairs in the order they were recorded.
+    pub fn entries(&self) -> &[(usize, B256)] {
+        &self.entries
+    }
+
+    /// Returns the number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no entry has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl AppendLog for MemoryLog {
+    fn record(&mut self, index: usize, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        self.entries.push((index, leaf));
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+//! [Arbitrary] implementations and `proptest` strategies for trees and proofs, so that downstream
+//! crates can fuzz their verifiers against structurally valid inputs.
+//!
+//! Trees hold a random number of random leaves, up to their capacity. [arb_proven_leaf] pairs a tree
+//! with a valid proof of one of its leaves, while [arb_proof] and the [Arbitrary] impl of
+//! [MerkleProof] produce well-formed proofs that are almost surely invalid.
+
+use ::arbitrary::{Arbitrary, Unstructured};
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use proptest::{collection, prelude::*};
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    incremental_tree::capacity,
+    proof::MerkleProof,
+    store::{MemoryStore, TreeStore},
+    IncrementalMerkleTree,
+};
+
+impl<'a, const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default> Arbitrary<'a>
+    for IncrementalMerkleTree<HEIGHT, H, S>
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let len = u.arbitrary_len::<[u8; 32]>()?.min(capacity(HEIGHT));
+        let leaves = (0..len)
+            .map(|_| u.arbitrary().map(B256::new))
+            .collect::<::arbitrary::Result<Vec<_>>>()?;
+        Self::from_leaves(&leaves).map_err(|_| ::arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a, const HEIGHT: usize> Arbitrary<'a> for MerkleProof<HEIGHT> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let leaf = B256::new(u.arbitrary()?);
+        let index = u.int_in_range(0..=capacity(HEIGHT) - 1)?;
+        let mut siblings = [B256::ZERO; HEIGHT];
+        for sibling in &mut siblings {
+            *sibling = B256::new(u.arbitrary()?);
+        }
+        Ok(Self {
+            leaf,
+            index,
+            siblings,
+        })
+    }
+}
+
+/// Returns a strategy for a random 32-byte value.
+pub fn arb_hash() -> impl Strategy<Value = B256> {
+    any::<[u8; 32]>().prop_map(B256::new)
+}
+
+/// Returns a strategy for a keccak tree holding up to `max_leaves` random leaves, capped at its
+/// capacity.
+pub fn arb_tree<const HEIGHT: usize>(
+    max_leaves: usize,
+) -> impl Strategy<Value = IncrementalMerkleTree<HEIGHT>> {
+    arb_tree_with::<HEIGHT, Keccak256Hasher, MemoryStore>(max_leaves)
+}
+
+/// Like [arb_tree], for a tree built with the hasher `H` and the store `S`.
+pub fn arb_tree_with<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default>(
+    max_leaves: usize,
+) -> impl Strategy<Value = IncrementalMerkleTree<HEIGHT, H, S>> {
+    let max_leaves = max_leaves.min(capacity(HEIGHT));
+    collection::vec(arb_hash(), 0..=max_leaves).prop_map(|leaves| {
+        IncrementalMerkleTree::from_leaves(&leaves).expect("leaves are capped at the capacity")
+    })
+}
+
+/// Returns a strategy for a well-formed proof of height `HEIGHT` with random contents, which is valid
+/// against no tree but with negligible probability.
+pub fn arb_proof<const HEIGHT: usize>() -> impl Strategy<Value = MerkleProof<HEIGHT>> {
+    (
+        arb_hash(),
+        0..capacity(HEIGHT),
+        collection::vec(arb_hash(), HEIGHT),
+    )
+        .prop_map(|(leaf, index, siblings)| MerkleProof {
+            leaf,
+            index,
+            siblings: siblings.try_into().expect("generated HEIGHT siblings"),
+        })
+}
+
+/// Returns a strategy for a keccak tree holding between one and `max_leaves` random leaves, together
+/// with a valid proof of one of them against the tree's root.
+///
+/// # Panics
+/// If `max_leaves` is zero.
+pub fn arb_proven_leaf<const HEIGHT: usize>(
+    max_leaves: usize,
+) -> impl Strategy<Value = (IncrementalMerkleTree<HEIGHT>, MerkleProof<HEIGHT>)> {
+    assert!(max_leaves > 0, "a proven leaf needs a non-empty tree");
+    let max_leaves = max_leaves.min(capacity(HEIGHT));
+    collection::vec(arb_hash(), 1..=max_leaves)
+        .prop_flat_map(|leaves| {
+            let len = leaves.len();
+            (Just(leaves), 0..len)
+        })
+        .prop_map(|(leaves, index)| {
+            let mut tree = IncrementalMerkleTree::from_leaves(&leaves)
+                .expect("leaves are capped at the capacity");
+            let proof = tree.prove(index).expect("index is below the size");
+            (tree, proof)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    proptest! {
+        #[test]
+        fn arb_tree_stays_within_bounds(tree in arb_tree::<4>(100)) {
+            prop_assert!(tree.len() <= 15);
+            prop_assert!(tree.validate_invariants().is_ok());
+        }
+
+        #[test]
+        fn arb_proven_leaf_verifies((tree, proof) in arb_proven_leaf::<5>(20)) {
+            prop_assert!(proof.index < tree.len());
+            prop_assert_eq!(tree.leaf(proof.index), Some(proof.leaf));
+            prop_assert!(proof.verify(tree.root()));
+        }
+
+        #[test]
+        fn arb_proof_is_well_formed(proof in arb_proof::<6>()) {
+            prop_assert!(proof.index < 63);
+        }
+    }
+
+    #[test]
+    fn arbitrary_trees_and_proofs_are_well_formed() {
+        let bytes: Vec<u8> = (0..=u8::MAX).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&bytes);
+        for _ in 0..8 {
+            let tree = IncrementalMerkleTree::<3>::arbitrary(&mut u).unwrap();
+            assert!(tree.len() <= 7);
+            assert!(tree.validate_invariants().is_ok());
+            let proof = MerkleProof::<3>::arbitrary(&mut u).unwrap();
+            assert!(proof.index < 7);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use hashbrown::HashMap;
+
+use crate::store::TreeStore;
+
+/// [TreeArchive] is the `rkyv` form of an [IncrementalMerkleTree](crate::IncrementalMerkleTree),
+/// written with
+/// [IncrementalMerkleTree::to_archive_bytes](crate::IncrementalMerkleTree::to_archive_bytes).
+///
+/// Unlike the serde form, it holds the whole intermediates cache, laid out level by level from the
+/// leaves up, so a tree is reopened from the archived bytes with
+/// [IncrementalMerkleTree::from_archive_bytes](crate::IncrementalMerkleTree::from_archive_bytes)
+/// without rehashing or copying anything. The bytes can be read from a memory-mapped file.
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct TreeArchive {
+    /// The height of the tree
+    pub(crate) height: u64,
+    /// The number of leading bytes kept from every internal node hash
+    pub(crate) hash_width: u64,
+    /// Whether the tree roots with [RootMode::BaggedPeaks](crate::RootMode::BaggedPeaks)
+    pub(crate) bagged_peaks: bool,
+    /// The number of leaves that have been added to the tree
+    pub(crate) size: u64,
+    /// The index of the first leaf whose contents are stored
+    pub(crate) first_stored_leaf: u64,
+    /// The leaves of the subtrees appended after the first stored leaf, whose contents are not stored,
+    /// as the start and end of each range
+    pub(crate) unstored_subtrees: Vec<[u64; 2]>,
+    /// The active branch of the tree
+    pub(crate) active_branch: Vec<[u8; 32]>,
+    /// The index in `nodes` of the first node of every level, from the leaves up, followed by the
+    /// number of nodes
+    pub(crate) level_offsets: Vec<u64>,
+    /// The nodes covering at least one appended leaf, level by level from the leaves up. Nodes the
+    /// tree never wrote, below a [Frontier](crate::Frontier) it was restored from or inside an
+    /// unstored subtree, are zero.
+    pub(crate) nodes: Vec<[u8; 32]>,
+}
+
+/// [ArchiveStore] is a [TreeStore] reading the intermediates cache straight out of an archived
+/// [TreeArchive]. Nodes written after the tree was reopened are kept in an overlay on the heap, and
+/// the archived bytes are never modified.
+pub struct ArchiveStore<'a> {
+    /// The archived tree
+    archive: &'a ArchivedTreeArchive,
+    /// The height of the archived tree
+    height: usize,
+    /// The nodes written or removed since the tree was reopened, keyed by generalized index
+    overlay: HashMap<usize, Option<B256>>,
+}
+
+impl<'a> ArchiveStore<'a> {
+    /// Wrap a validated `archive` of a tree of the given `height`.
+    pub(crate) fn new(archive: &'a ArchivedTreeArchive, height: usize) -> Self {
+        Self {
+            archive,
+            height,
+            overlay: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of nodes written or removed since the tree was reopened.
+    pub fn overlay_len(&self) -> usize {
+        self.overlay.len()
+    }
+
+    /// Returns the archived node at generalized index `index`, if any.
+    fn archived(&self, index: usize) -> Option<B256> {
+        if index == 0 {
+            return None;
+        }
+        let depth = (usize::BITS - 1 - index.leading_zeros()) as usize;
+        let level = self.height.checked_sub(depth)?;
+        let offsets = &self.archive.level_offsets;
+        let first = offsets[level].to_native() as usize;
+        let last = offsets[level + 1].to_native() as usize;
+        let slot = first + (index - (1 << depth));
+        (slot < last).then(|| B256::new(self.archive.nodes[slot]))
+    }
+}
+
+impl TreeStore for ArchiveStore<'_> {
+    fn get(&self, index: usize) -> Option<B256> {
+        match self.overlay.get(&index) {
+            Some(node) => *node,
+            None => self.archived(index),
+        }
+    }
+
+    fn put(&mut self, index: usize, node: B256) {
+        self.overlay.insert(index, Some(node));
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.overlay.insert(index, None);
+    }
+}
@@ -0,0 +1,128 @@
+//! `imt`: compute roots and proofs of keccak incremental merkle trees from the command line.
+//!
+//! Leaves are read as newline-delimited hex from a file, or from stdin when no file is given. Blank
+//! lines are skipped.
+//!
+//! ```text
+//! imt root [--height N] [LEAVES]
+//! imt prove INDEX [--height N] [LEAVES]
+//! imt verify ROOT [PROOF]
+//! ```
+//!
+//! `prove` prints the proof as JSON, which `verify` reads back from a file or stdin. `verify` exits
+//! with status 1 if the proof is invalid.
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use alloy_primitives::B256;
+use simple_merkle_tree::{DynIncrementalMerkleTree, DynMerkleProof};
+
+/// The tree height used when `--height` is not given, that of the deposit contract.
+const DEFAULT_HEIGHT: usize = 32;
+
+const USAGE: &str = "usage:
+  imt root [--height N] [LEAVES]
+  imt prove INDEX [--height N] [LEAVES]
+  imt verify ROOT [PROOF]";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("imt: {message}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Run the command given by `args`, returning the exit code or an error message.
+fn run(mut args: Vec<String>) -> Result<ExitCode, String> {
+    let height = match args.iter().position(|arg| arg == "--height") {
+        Some(flag) => {
+            let value = args.get(flag + 1).ok_or("--height needs a value")?;
+            let height = value
+                .parse()
+                .map_err(|_| format!("invalid height {value}"))?;
+            if height >= usize::BITS as usize - 1 {
+                return Err(format!("height {height} is too large"));
+            }
+            args.drain(flag..flag + 2);
+            height
+        }
+        None => DEFAULT_HEIGHT,
+    };
+
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["root", ref input @ ..] if input.len() <= 1 => {
+            let tree = build(height, input.first().copied())?;
+            println!("{}", tree.root());
+            Ok(ExitCode::SUCCESS)
+        }
+        ["prove", index, ref input @ ..] if input.len() <= 1 => {
+            let index = index
+                .parse()
+                .map_err(|_| format!("invalid index {index}"))?;
+            let mut tree = build(height, input.first().copied())?;
+            let proof = tree
+                .prove(index)
+                .map_err(|_| format!("no leaf at index {index}"))?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&proof).map_err(|error| error.to_string())?
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        ["verify", root, ref input @ ..] if input.len() <= 1 => {
+            let root = parse_hash(root)?;
+            let proof: DynMerkleProof = serde_json::from_str(&read_input(input.first().copied())?)
+                .map_err(|error| format!("invalid proof: {error}"))?;
+            let valid = proof.verify(root);
+            println!("{valid}");
+            Ok(if valid {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Build a tree of `height` from the leaves in `path`, or on stdin if it is `None`.
+fn build(height: usize, path: Option<&str>) -> Result<DynIncrementalMerkleTree, String> {
+    let mut tree = DynIncrementalMerkleTree::new(height);
+    for (line, leaf) in read_input(path)?.lines().enumerate() {
+        let leaf = leaf.trim();
+        if leaf.is_empty() {
+            continue;
+        }
+        let leaf = parse_hash(leaf).map_err(|error| format!("line {}: {error}", line + 1))?;
+        tree.append(leaf)
+            .map_err(|error| format!("line {}: {error}", line + 1))?;
+    }
+    Ok(tree)
+}
+
+/// Read the whole file at `path`, or stdin if it is `None`.
+fn read_input(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some(path) => fs::read_to_string(path).map_err(|error| format!("{path}: {error}")),
+        None => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|error| format!("stdin: {error}"))?;
+            Ok(input)
+        }
+    }
+}
+
+/// Parse a 32-byte hash from hex, with or without the `0x` prefix.
+fn parse_hash(hex: &str) -> Result<B256, String> {
+    hex.parse()
+        .map_err(|_| format!("{hex} is not a 32-byte hex string"))
+}
@@ -0,0 +1,375 @@
+//! `borsh` and `bincode` encodings of [IncrementalMerkleTree], [MerkleProof] and [MultiProof], for
+//! protocols that need a deterministic binary form instead of the serde one.
+//!
+//! Every value is written as the fields below, in order, with each crate's own encoding of the
+//! primitive types: `borsh` writes fixed-width little-endian integers and `u32` length prefixes,
+//! while `bincode`'s standard configuration writes variable-length integers. Hashes are written as 32
+//! raw bytes. The `version` field comes first and is currently `1` for proofs and `2` for trees;
+//! decoding rejects any other version, so the layout can evolve without silently misreading older
+//! data.
+//!
+//! - [MerkleProof]: `version: u8, leaf: [u8; 32], index: u64, siblings: Vec<[u8; 32]>`
+//! - [MultiProof]: `version: u8, indices: Vec<u64>, leaves: Vec<[u8; 32]>,
+//!   siblings: Vec<[u8; 32]>, flags: Vec<bool>`
+//! - [IncrementalMerkleTree]: `version: u8, size: u64, hash_width: u8, bagged_peaks: bool,
+//!   active_branch: Vec<[u8; 32]>, base_size: u64, base_branch: Vec<[u8; 32]>,
+//!   leaves: Vec<[u8; 32]>, subtrees: Vec<(u64, u64, [u8; 32])>, history_capacity: u64,
+//!   root_history: Vec<[u8; 32]>, watched: Vec<u64>`. As in the serde form, the intermediates cache
+//!   is rebuilt from the leaves when the tree is decoded, `base_size` and `base_branch` describe the
+//!   [Frontier](crate::Frontier) a restored tree started from, and `subtrees` lists the first leaf,
+//!   height and root of every subtree appended after it whose leaves are not stored.
+
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+
+use crate::{
+    hasher::MerkleHasher,
+    incremental_tree::state::TreeState,
+    proof::{MerkleProof, MultiProof},
+    store::TreeStore,
+    IncrementalMerkleTree, RootMode,
+};
+
+/// The layout version written first by every proof encoding.
+const VERSION: u8 = 1;
+/// The layout version written first by every tree encoding, which gained `subtrees` in version `2`.
+const TREE_VERSION: u8 = 2;
+
+/// The encoded form of a [MerkleProof].
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct ProofLayout {
+    version: u8,
+    leaf: [u8; 32],
+    index: u64,
+    siblings: Vec<[u8; 32]>,
+}
+
+/// The encoded form of a [MultiProof].
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct MultiProofLayout {
+    version: u8,
+    indices: Vec<u64>,
+    leaves: Vec<[u8; 32]>,
+    siblings: Vec<[u8; 32]>,
+    flags: Vec<bool>,
+}
+
+/// The encoded form of an [IncrementalMerkleTree], see [TreeState].
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+#[cfg_attr(feature = "bincode", derive(bincode::Encode, bincode::Decode))]
+struct TreeLayout {
+    version: u8,
+    size: u64,
+    hash_width: u8,
+    bagged_peaks: bool,
+    active_branch: Vec<[u8; 32]>,
+    base_size: u64,
+    base_branch: Vec<[u8; 32]>,
+    leaves: Vec<[u8; 32]>,
+    subtrees: Vec<(u64, u64, [u8; 32])>,
+    history_capacity: u64,
+    root_history: Vec<[u8; 32]>,
+    watched: Vec<u64>,
+}
+
+impl<const HEIGHT: usize> From<&MerkleProof<HEIGHT>> for ProofLayout {
+    fn from(proof: &MerkleProof<HEIGHT>) -> Self {
+        Self {
+            version: VERSION,
+            leaf: proof.leaf.0,
+            index: proof.index as u64,
+            siblings: proof.siblings.iter().map(|sibling| sibling.0).collect(),
+        }
+    }
+}
+
+impl ProofLayout {
+    /// Returns the decoded proof, or why the layout does not describe a proof of height `HEIGHT`.
+    fn into_proof<const HEIGHT: usize>(self) -> Result<MerkleProof<HEIGHT>, &'static str> {
+        check_version(self.version, VERSION)?;
+        let siblings: Vec<B256> = hashes(self.siblings);
+        Ok(MerkleProof {
+            leaf: B256::new(self.leaf),
+            index: index(self.index)?,
+            siblings: siblings
+                .try_into()
+                .map_err(|_| "proof does not have HEIGHT siblings")?,
+        })
+    }
+}
+
+impl<const HEIGHT: usize> From<&MultiProof<HEIGHT>> for MultiProofLayout {
+    fn from(proof: &MultiProof<HEIGHT>) -> Self {
+        Self {
+            version: VERSION,
+            indices: proof.indices.iter().map(|&index| index as u64).collect(),
+            leaves: proof.leaves.iter().map(|leaf| leaf.0).collect(),
+            siblings: proof.siblings.iter().map(|sibling| sibling.0).collect(),
+            flags: proof.flags.clone(),
+        }
+    }
+}
+
+impl MultiProofLayout {
+    /// Returns the decoded proof, or why the layout does not describe a multiproof.
+    fn into_proof<const HEIGHT: usize>(self) -> Result<MultiProof<HEIGHT>, &'static str> {
+        check_version(self.version, VERSION)?;
+        Ok(MultiProof {
+            indices: self
+                .indices
+                .into_iter()
+                .map(index)
+                .collect::<Result<_, _>>()?,
+            leaves: hashes(self.leaves),
+            siblings: hashes(self.siblings),
+            flags: self.flags,
+        })
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> From<&IncrementalMerkleTree<HEIGHT, H, S>>
+    for TreeLayout
+{
+    fn from(tree: &IncrementalMerkleTree<HEIGHT, H, S>) -> Self {
+        let state = tree.to_state();
+        Self {
+            version: TREE_VERSION,
+            size: state.size as u64,
+            hash_width: state.hash_width as u8,
+            bagged_peaks: state.root_mode == RootMode::BaggedPeaks,
+            active_branch: state.active_branch.iter().map(|node| node.0).collect(),
+            base_size: state.base_size as u64,
+            base_branch: state.base_branch.iter().map(|node| node.0).collect(),
+            leaves: state.leaves.iter().map(|leaf| leaf.0).collect(),
+            subtrees: state
+                .subtrees
+                .iter()
+                .map(|&(start, height, root)| (start as u64, height as u64, root.0))
+                .collect(),
+            history_capacity: state.history_capacity as u64,
+            root_history: state.root_history.iter().map(|root| root.0).collect(),
+            watched: state.watched.iter().map(|&index| index as u64).collect(),
+        }
+    }
+}
+
+impl TreeLayout {
+    /// Returns the decoded tree, or why the layout does not describe a valid tree.
+    fn into_tree<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default>(
+        self,
+    ) -> Result<IncrementalMerkleTree<HEIGHT, H, S>, &'static str> {
+        check_version(self.version, TREE_VERSION)?;
+        IncrementalMerkleTree::from_state(TreeState {
+            size: index(self.size)?,
+            hash_width: self.hash_width.into(),
+            root_mode: if self.bagged_peaks {
+                RootMode::BaggedPeaks
+            } else {
+                RootMode::PaddedFixedHeight
+            },
+            active_branch: hashes(self.active_branch),
+            base_size: index(self.base_size)?,
+            base_branch: hashes(self.base_branch),
+            leaves: hashes(self.leaves),
+            subtrees: self
+                .subtrees
+                .into_iter()
+                .map(|(start, height, root)| Ok((index(start)?, index(height)?, B256::new(root))))
+                .collect::<Result<_, &'static str>>()?,
+            history_capacity: index(self.history_capacity)?,
+            root_history: hashes(self.root_history),
+            watched: self
+                .watched
+                .into_iter()
+                .map(index)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Reject any layout version but the `current` one.
+fn check_version(version: u8, current: u8) -> Result<(), &'static str> {
+    if version == current {
+        Ok(())
+    } else {
+        Err("unsupported layout version")
+    }
+}
+
+/// Convert an encoded index or size to a `usize`.
+fn index(value: u64) -> Result<usize, &'static str> {
+    usize::try_from(value).map_err(|_| "index does not fit in a usize")
+}
+
+/// Convert encoded hashes into any collection of [B256].
+fn hashes<C: FromIterator<B256>>(hashes: Vec<[u8; 32]>) -> C {
+    hashes.into_iter().map(B256::new).collect()
+}
+
+#[cfg(feature = "borsh")]
+mod borsh_impls {
+    use borsh::{
+        io::{Error, ErrorKind, Read, Result, Write},
+        BorshDeserialize, BorshSerialize,
+    };
+
+    use super::{MultiProofLayout, ProofLayout, TreeLayout};
+    use crate::{
+        hasher::MerkleHasher,
+        proof::{MerkleProof, MultiProof},
+        store::TreeStore,
+        IncrementalMerkleTree,
+    };
+
+    impl<const HEIGHT: usize> BorshSerialize for MerkleProof<HEIGHT> {
+        fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+            ProofLayout::from(self).serialize(writer)
+        }
+    }
+
+    impl<const HEIGHT: usize> BorshDeserialize for MerkleProof<HEIGHT> {
+        fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+            ProofLayout::deserialize_reader(reader)?
+                .into_proof()
+                .map_err(invalid_data)
+        }
+    }
+
+    impl<const HEIGHT: usize> BorshSerialize for MultiProof<HEIGHT> {
+        fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+            MultiProofLayout::from(self).serialize(writer)
+        }
+    }
+
+    impl<const HEIGHT: usize> BorshDeserialize for MultiProof<HEIGHT> {
+        fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+            MultiProofLayout::deserialize_reader(reader)?
+                .into_proof()
+                .map_err(invalid_data)
+        }
+    }
+
+    impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> BorshSerialize
+        for IncrementalMerkleTree<HEIGHT, H, S>
+    {
+        fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+            TreeLayout::from(self).serialize(writer)
+        }
+    }
+
+    impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default> BorshDeserialize
+        for IncrementalMerkleTree<HEIGHT, H, S>
+    {
+        fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+            TreeLayout::deserialize_reader(reader)?
+                .into_tree()
+                .map_err(invalid_data)
+        }
+    }
+
+    /// Wrap the reason a layout was rejected into an I/O error.
+    fn invalid_data(reason: &'static str) -> Error {
+        Error::new(ErrorKind::InvalidData, reason)
+    }
+}
+
+#[cfg(feature = "bincode")]
+mod bincode_impls {
+    use bincode::{
+        de::{BorrowDecoder, Decoder},
+        enc::Encoder,
+        error::{DecodeError, EncodeError},
+        BorrowDecode, Decode, Encode,
+    };
+
+    use super::{MultiProofLayout, ProofLayout, TreeLayout};
+    use crate::{
+        hasher::MerkleHasher,
+        proof::{MerkleProof, MultiProof},
+        store::TreeStore,
+        IncrementalMerkleTree,
+    };
+
+    impl<const HEIGHT: usize> Encode for MerkleProof<HEIGHT> {
+        fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+            ProofLayout::from(self).encode(encoder)
+        }
+    }
+
+    impl<Context, const HEIGHT: usize> Decode<Context> for MerkleProof<HEIGHT> {
+        fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+            ProofLayout::decode(decoder)?
+                .into_proof()
+                .map_err(DecodeError::Other)
+        }
+    }
+
+    impl<'de, Context, const HEIGHT: usize> BorrowDecode<'de, Context> for MerkleProof<HEIGHT> {
+        fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+            decoder: &mut D,
+        ) -> Result<Self, DecodeError> {
+            Self::decode(decoder)
+        }
+    }
+
+    impl<const HEIGHT: usize> Encode for MultiProof<HEIGHT> {
+        fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+            MultiProofLayout::from(self).encode(encoder)
+        }
+    }
+
+    impl<Context, const HEIGHT: usize> Decode<Context> for MultiProof<HEIGHT> {
+        fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+            MultiProofLayout::decode(decoder)?
+                .into_proof()
+                .map_err(DecodeError::Other)
+        }
+    }
+
+    impl<'de, Context, const HEIGHT: usize> BorrowDecode<'de, Context> for MultiProof<HEIGHT> {
+        fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+            decoder: &mut D,
+        ) -> Result<Self, DecodeError> {
+            Self::decode(decoder)
+        }
+    }
+
+    impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> Encode
+        for IncrementalMerkleTree<HEIGHT, H, S>
+    {
+        fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+            TreeLayout::from(self).encode(encoder)
+        }
+    }
+
+    impl<Context, const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default> Decode<Context>
+        for IncrementalMerkleTree<HEIGHT, H, S>
+    {
+        fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+            TreeLayout::decode(decoder)?
+                .into_tree()
+                .map_err(DecodeError::Other)
+        }
+    }
+
+    impl<'de, Context, const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default>
+        BorrowDecode<'de, Context> for IncrementalMerkleTree<HEIGHT, H, S>
+    {
+        fn borrow_decode<D: BorrowDecoder<'de, Context = Context>>(
+            decoder: &mut D,
+        ) -> Result<Self, DecodeError> {
+            Self::decode(decoder)
+        }
+    }
+}
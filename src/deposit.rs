@@ -0,0 +1,286 @@
+use alloy_primitives::{FixedBytes, B256};
+
+use crate::{
+    hasher::{MerkleHasher, Sha256Hasher},
+    leaf::LeafEncode,
+    proof::MerkleProof,
+    IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// The height of the beacon chain deposit contract's tree.
+pub const DEPOSIT_CONTRACT_TREE_DEPTH: usize = 32;
+
+/// The SSZ `DepositData` container of a deposit to the beacon chain deposit contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DepositData {
+    /// The BLS public key of the validator
+    pub pubkey: FixedBytes<48>,
+    /// The withdrawal credentials of the validator
+    pub withdrawal_credentials: B256,
+    /// The deposited amount, in gwei
+    pub amount: u64,
+    /// The BLS signature over the deposit message
+    pub signature: FixedBytes<96>,
+}
+
+impl DepositData {
+    /// Returns the SSZ `hash_tree_root` of the deposit data, which the deposit contract computes and
+    /// appends as the leaf of the deposit.
+    pub fn hash_tree_root(&self) -> B256 {
+        let mut pubkey = [0u8; 64];
+        pubkey[..48].copy_from_slice(self.pubkey.as_slice());
+        let pubkey_root = sha256(&pubkey);
+
+        let mut signature_tail = [0u8; 64];
+        signature_tail[..32].copy_from_slice(&self.signature[64..]);
+        let signature_root =
+            Sha256Hasher::hash_pair(&sha256(&self.signature[..64]), &sha256(&signature_tail));
+
+        let mut amount = [0u8; 32];
+        amount[..8].copy_from_slice(&self.amount.to_le_bytes());
+        Sha256Hasher::hash_pair(
+            &Sha256Hasher::hash_pair(&pubkey_root, &self.withdrawal_credentials),
+            &Sha256Hasher::hash_pair(&B256::from(amount), &signature_root),
+        )
+    }
+}
+
+impl LeafEncode for DepositData {
+    fn leaf_hash(&self) -> B256 {
+        self.hash_tree_root()
+    }
+}
+
+/// [DepositTree] mirrors the state of the beacon chain deposit contract: every deposit appends the
+/// `hash_tree_root` of its [DepositData] to a SHA-256 tree of height [DEPOSIT_CONTRACT_TREE_DEPTH],
+/// and [DepositTree::get_deposit_root] and [DepositTree::get_deposit_count] return exactly what the
+/// contract functions of the same name do.
+#[derive(Debug, Clone, Default)]
+pub struct DepositTree {
+    /// The tree of deposit data roots
+    tree: IncrementalMerkleTree<DEPOSIT_CONTRACT_TREE_DEPTH, Sha256Hasher>,
+}
+
+impl DepositTree {
+    /// Create a new [DepositTree] without any deposits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a deposit, as the contract's `deposit` function does once its checks pass.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the deposit was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the contract's tree is full.
+    pub fn deposit(&mut self, data: &DepositData) -> Result<(), IncrementalMerkleTreeError> {
+        self.tree.append(data)
+    }
+
+    /// Returns the deposit root, as the contract's `get_deposit_root()` does.
+    pub fn get_deposit_root(&self) -> B256 {
+        self.tree.deposit_root()
+    }
+
+    /// Returns the number of deposits as 8 little-endian bytes, as the contract's
+    /// `get_deposit_count()` does.
+    pub fn get_deposit_count(&self) -> [u8; 8] {
+        (self.tree.len() as u64).to_le_bytes()
+    }
+
+    /// Returns the number of deposits.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Returns `true` if no deposit has been made.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the underlying tree of deposit data roots.
+    pub fn tree(&self) -> &IncrementalMerkleTree<DEPOSIT_CONTRACT_TREE_DEPTH, Sha256Hasher> {
+        &self.tree
+    }
+
+    /// Generate an inclusion proof for the deposit at `index` against the padded root of the tree,
+    /// i.e. the deposit root before the count is mixed in.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the deposit data root and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if there is no deposit at
+    ///   `index`.
+    pub fn prove(
+        &mut self,
+        index: usize,
+    ) -> Result<MerkleProof<DEPOSIT_CONTRACT_TREE_DEPTH>, IncrementalMerkleTreeError> {
+        self.tree.prove(index)
+    }
+}
+
+/// Returns the SHA-256 digest of `data`.
+fn sha256(data: &[u8]) -> B256 {
+    use sha2::{Digest, Sha256};
+
+    B256::from_slice(&Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use alloy_primitives::b256;
+    use sha2::{Digest, Sha256};
+
+    /// Returns the SHA-256 digest of the concatenated `parts`.
+    fn digest(parts: &[&[u8]]) -> B256 {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        B256::from_slice(&hasher.finalize())
+    }
+
+    fn deposit(seed: u8) -> DepositData {
+        DepositData {
+            pubkey: FixedBytes::repeat_byte(seed),
+            withdrawal_credentials: B256::repeat_byte(seed.wrapping_add(1)),
+            amount: 32_000_000_000,
+            signature: FixedBytes::repeat_byte(seed.wrapping_add(2)),
+        }
+    }
+
+    /// Computes the leaf of `data` the way the contract's `deposit` function does.
+    fn contract_node(data: &DepositData) -> B256 {
+        let pubkey_root = digest(&[data.pubkey.as_slice(), &[0; 16]]);
+        let signature_root = digest(&[
+            digest(&[&data.signature[..64]]).as_slice(),
+            digest(&[&data.signature[64..], &[0; 32]]).as_slice(),
+        ]);
+        digest(&[
+            digest(&[
+                pubkey_root.as_slice(),
+                data.withdrawal_credentials.as_slice(),
+            ])
+            .as_slice(),
+            digest(&[
+                &data.amount.to_le_bytes(),
+                &[0; 24],
+                signature_root.as_slice(),
+            ])
+            .as_slice(),
+        ])
+    }
+
+    #[test]
+    fn empty_deposit_root_matches_the_contract() {
+        let tree = DepositTree::new();
+        // `get_deposit_root()` of the deposit contract before the first deposit.
+        assert_eq!(
+            tree.get_deposit_root(),
+            b256!("d70a234731285c6804c2a4f56711ddb8c82c99740f207854891028af34e27e5e")
+        );
+        assert_eq!(tree.get_deposit_count(), [0; 8]);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn deposit_data_root_matches_the_contract() {
+        for seed in [0, 1, 0x7f, 0xff] {
+            assert_eq!(
+                deposit(seed).hash_tree_root(),
+                contract_node(&deposit(seed))
+            );
+        }
+    }
+
+    /// The state of the deposit contract, updated with the loops of its `deposit` and
+    /// `get_deposit_root` functions.
+    struct Contract {
+        branch: [B256; DEPOSIT_CONTRACT_TREE_DEPTH],
+        deposit_count: u64,
+        zero_hashes: [B256; DEPOSIT_CONTRACT_TREE_DEPTH],
+    }
+
+    impl Contract {
+        /// Runs the contract's constructor.
+        fn new() -> Self {
+            let mut zero_hashes = [B256::ZERO; DEPOSIT_CONTRACT_TREE_DEPTH];
+            for height in 0..DEPOSIT_CONTRACT_TREE_DEPTH - 1 {
+                zero_hashes[height + 1] = digest(&[
+                    zero_hashes[height].as_slice(),
+                    zero_hashes[height].as_slice(),
+                ]);
+            }
+            Self {
+                branch: [B256::ZERO; DEPOSIT_CONTRACT_TREE_DEPTH],
+                deposit_count: 0,
+                zero_hashes,
+            }
+        }
+
+        /// Adds `node` to the branch as `deposit` does once its checks pass.
+        fn deposit(&mut self, mut node: B256) {
+            self.deposit_count += 1;
+            let mut size = self.deposit_count;
+            for height in 0..DEPOSIT_CONTRACT_TREE_DEPTH {
+                if size & 1 == 1 {
+                    self.branch[height] = node;
+                    return;
+                }
+                node = digest(&[self.branch[height].as_slice(), node.as_slice()]);
+                size /= 2;
+            }
+            unreachable!("the deposit contract's tree is full");
+        }
+
+        /// Returns what `get_deposit_root` does.
+        fn get_deposit_root(&self) -> B256 {
+            let mut node = B256::ZERO;
+            let mut size = self.deposit_count;
+            for height in 0..DEPOSIT_CONTRACT_TREE_DEPTH {
+                node = if size & 1 == 1 {
+                    digest(&[self.branch[height].as_slice(), node.as_slice()])
+                } else {
+                    digest(&[node.as_slice(), self.zero_hashes[height].as_slice()])
+                };
+                size /= 2;
+            }
+            digest(&[node.as_slice(), &self.deposit_count.to_le_bytes(), &[0; 24]])
+        }
+    }
+
+    #[test]
+    fn contract_zero_hashes_match_the_published_table() {
+        let contract = Contract::new();
+        // The zero hash of height 1, the SHA-256 digest of 64 zero bytes, and the root of the
+        // contract before the first deposit.
+        assert_eq!(
+            contract.zero_hashes[1],
+            b256!("f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b")
+        );
+        assert_eq!(
+            contract.get_deposit_root(),
+            b256!("d70a234731285c6804c2a4f56711ddb8c82c99740f207854891028af34e27e5e")
+        );
+    }
+
+    #[test]
+    fn deposit_root_matches_the_contract() {
+        let deposits: Vec<DepositData> = (0..=70).map(deposit).collect();
+        let mut tree = DepositTree::new();
+        let mut contract = Contract::new();
+        for (count, data) in deposits.iter().enumerate() {
+            tree.deposit(data).unwrap();
+            contract.deposit(contract_node(data));
+            assert_eq!(tree.get_deposit_count(), (count as u64 + 1).to_le_bytes());
+            assert_eq!(tree.get_deposit_root(), contract.get_deposit_root());
+        }
+        let root = tree.tree().root();
+        for (index, data) in deposits.iter().enumerate() {
+            let proof = tree.prove(index).unwrap();
+            assert_eq!(proof.leaf, contract_node(data));
+            assert!(proof.verify_with::<Sha256Hasher>(root));
+        }
+    }
+}
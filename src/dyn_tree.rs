@@ -0,0 +1,422 @@
+use alloc::{borrow::Cow, vec, vec::Vec};
+use alloy_primitives::B256;
+use core::marker::PhantomData;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    incremental_tree::{capacity, levels::Levels, zero_hashes, RootMemo},
+    proof::{verify, verify_with},
+    store::{MemoryStore, TreeStore},
+    IncrementalMerkleTreeError,
+};
+
+/// [DynIncrementalMerkleTree] is the runtime-height counterpart of
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree): the same append-only tree, hasher and
+/// [TreeStore] cache layout keyed by generalized index, with the height chosen when the tree is
+/// created instead of at compile time. Both trees append, rehash and prove through the same
+/// height-independent core, so the cache is kept up to date the same way.
+pub struct DynIncrementalMerkleTree<H = Keccak256Hasher, S = MemoryStore> {
+    /// The height of the tree
+    height: usize,
+    /// The zero hashes, one per level, shared with every tree of the same hasher
+    zero_hashes: Cow<'static, [B256]>,
+    /// The active branch of the tree, used to calculate the root hash
+    active_branch: Vec<B256>,
+    /// The number of leaves that have been added to the tree
+    size: usize,
+    /// The intermediate cache for the tree, keyed by generalized index: the leaf at `i` sits at
+    /// `2^height + i`. Nodes whose subtree holds no appended leaves are never written and stand for
+    /// the zero hash of their height.
+    intermediates: S,
+    /// The number of leading leaves whose ancestors in the intermediate cache are up to date
+    hashed_leaves: usize,
+    /// The root computed by the last call to `root` since the tree last changed
+    root_memo: RootMemo,
+    /// The hasher of the tree
+    _hasher: PhantomData<H>,
+}
+
+/// An inclusion proof for a single leaf of a [DynIncrementalMerkleTree].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DynMerkleProof {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The index of the leaf in the tree
+    pub index: usize,
+    /// The siblings on the path from the leaf up to the root, starting at the leaf level
+    pub siblings: Vec<B256>,
+}
+
+impl DynMerkleProof {
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` under `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        verify(root, self.leaf, self.index, &self.siblings)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        verify_with::<H>(root, self.leaf, self.index, &self.siblings)
+    }
+}
+
+impl<H, S> DynIncrementalMerkleTree<H, S> {
+    /// The largest supported height, the largest one whose leaves can be addressed by a `usize`
+    /// generalized index.
+    pub const MAX_HEIGHT: usize = usize::BITS as usize - 1;
+}
+
+impl<H: MerkleHasher, S: TreeStore + Default> DynIncrementalMerkleTree<H, S> {
+    /// Create a new [DynIncrementalMerkleTree] of the given `height`.
+    ///
+    /// # Panics
+    /// If `height` exceeds [Self::MAX_HEIGHT].
+    pub fn new(height: usize) -> Self {
+        Self::with_store(height, S::default())
+    }
+}
+
+impl<H: MerkleHasher, S: TreeStore> DynIncrementalMerkleTree<H, S> {
+    /// Create a new, empty [DynIncrementalMerkleTree] of the given `height` whose intermediates
+    /// cache is kept in `store`, which must not hold any nodes yet.
+    ///
+    /// # Panics
+    /// If `height` exceeds [Self::MAX_HEIGHT].
+    pub fn with_store(height: usize, store: S) -> Self {
+        assert!(
+            height <= Self::MAX_HEIGHT,
+            "tree height {height} is too large"
+        );
+        Self {
+            height,
+            zero_hashes: zero_hashes::<H>(height, 32),
+            active_branch: vec![B256::default(); height],
+            size: 0,
+            intermediates: store,
+            hashed_leaves: 0,
+            root_memo: RootMemo::default(),
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the height of the tree.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the leaf at `index`, or `None` if no leaf has been appended there.
+    pub fn leaf(&self, index: usize) -> Option<B256> {
+        (index < self.size).then(|| self.levels().node_at(0, index))
+    }
+
+    /// Returns an iterator over the leaves, in the order they were appended.
+    pub fn leaves(&self) -> impl DoubleEndedIterator<Item = B256> + ExactSizeIterator + '_ {
+        let levels = self.levels();
+        (0..self.size).map(move |index| levels.node_at(0, index))
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the maximum number of leaves the tree can hold, `2^height - 1`.
+    pub fn capacity(&self) -> usize {
+        capacity(self.height)
+    }
+
+    /// Returns the number of leaves that can still be appended before the tree is full.
+    pub fn remaining(&self) -> usize {
+        capacity(self.height) - self.size
+    }
+
+    /// Returns `true` if the tree cannot hold any more leaves.
+    pub fn is_full(&self) -> bool {
+        self.size >= capacity(self.height)
+    }
+
+    /// Compute the root hash of the tree from the active branch. The root is memoized until the next
+    /// change to the tree.
+    pub fn root(&self) -> B256 {
+        if let Some(root) = self.root_memo.get() {
+            return root;
+        }
+        let root = self.levels().padded_root(&self.active_branch);
+        self.root_memo.set(root);
+        root
+    }
+
+    /// Append a new leaf to the tree, rehashing the path above it so the cache stays up to date.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&mut self, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        let rehash = self.hashed_leaves == self.size;
+        self.push_leaf(leaf)?;
+        if rehash {
+            let index = self.size - 1;
+            self.levels_mut().0.update_path_above(0, index);
+            self.hashed_leaves = self.size;
+        }
+        Ok(())
+    }
+
+    /// Append a new leaf to the tree without rehashing the path above it, as
+    /// [IncrementalMerkleTree::append_deferred](crate::IncrementalMerkleTree::append_deferred) does.
+    /// The next proof rehashes every stale node once; [Self::prove_latest] does not need them.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append_deferred(&mut self, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        self.push_leaf(leaf).map(|_| ())
+    }
+
+    /// Append every leaf in `leaves` to the tree, in order, hashing every stale intermediate node
+    /// above them exactly once.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the tree
+    ///   is left unchanged.
+    pub fn append_batch(&mut self, leaves: &[B256]) -> Result<(), IncrementalMerkleTreeError> {
+        if leaves.len() > self.remaining() {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        if leaves.is_empty() {
+            return Ok(());
+        }
+        for (offset, leaf) in leaves.iter().enumerate() {
+            let index = self.levels().node_index(0, self.size + offset);
+            self.intermediates.put(index, *leaf);
+        }
+        self.size += leaves.len();
+        self.root_memo.clear();
+        self.recompute_intermediates();
+        let (levels, active_branch) = self.levels_mut();
+        levels.read_active_branch(active_branch);
+        Ok(())
+    }
+
+    /// Bring the intermediates cache up to date with the leaf row, hashing only the nodes above the
+    /// leaves appended since it last was.
+    pub fn recompute_intermediates(&mut self) {
+        let hashed_leaves = self.hashed_leaves;
+        self.levels_mut().0.recompute(hashed_leaves);
+        self.hashed_leaves = self.size;
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`. Stale intermediate nodes, left by
+    /// [Self::append_deferred], are rehashed first.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    pub fn prove(&mut self, index: usize) -> Result<DynMerkleProof, IncrementalMerkleTreeError> {
+        if index >= self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.size,
+            });
+        }
+        self.recompute_intermediates();
+        let levels = self.levels();
+        Ok(DynMerkleProof {
+            leaf: levels.node_at(0, index),
+            index,
+            siblings: (0..self.height)
+                .map(|height| levels.sibling(index, height))
+                .collect(),
+        })
+    }
+
+    /// Generate an inclusion proof for the most recently appended leaf in `O(height)`, without
+    /// rehashing the intermediates cache, as
+    /// [IncrementalMerkleTree::prove_latest](crate::IncrementalMerkleTree::prove_latest) does.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the latest leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended.
+    pub fn prove_latest(&self) -> Result<DynMerkleProof, IncrementalMerkleTreeError> {
+        let Some(index) = self.size.checked_sub(1) else {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: 0, len: 0 });
+        };
+        let levels = self.levels();
+        Ok(DynMerkleProof {
+            leaf: levels.node_at(0, index),
+            index,
+            siblings: (0..self.height)
+                .map(|height| levels.latest_sibling(index, height))
+                .collect(),
+        })
+    }
+
+    /// Append `leaf` to the leaf row and the frontier without rehashing its path.
+    ///
+    /// # Returns
+    /// - `Ok(height)` with the height of the new peak of the frontier.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    fn push_leaf(&mut self, leaf: B256) -> Result<usize, IncrementalMerkleTreeError> {
+        if self.is_full() {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: self.capacity(),
+            });
+        }
+        self.size += 1;
+        self.root_memo.clear();
+        let (mut levels, active_branch) = self.levels_mut();
+        levels.push_leaf(active_branch, leaf)
+    }
+
+    /// Borrow the levels of the tree for reading.
+    fn levels(&self) -> Levels<'_, H, &S> {
+        Levels::new(
+            self.height,
+            self.size,
+            32,
+            0,
+            &[],
+            &self.zero_hashes,
+            &self.intermediates,
+        )
+    }
+
+    /// Borrow the levels of the tree for writing, next to its active branch.
+    fn levels_mut(&mut self) -> (Levels<'_, H, &mut S>, &mut [B256]) {
+        let levels = Levels::new(
+            self.height,
+            self.size,
+            32,
+            0,
+            &[],
+            &self.zero_hashes,
+            &mut self.intermediates,
+        );
+        (levels, &mut self.active_branch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hasher::SortedKeccak256Hasher,
+        test_utils::{leaves, naive_proof, naive_root},
+        IncrementalMerkleTree,
+    };
+
+    #[test]
+    fn matches_the_const_height_tree() {
+        let leaves = leaves(19);
+        let mut tree = <DynIncrementalMerkleTree>::new(5);
+        let mut reference = IncrementalMerkleTree::<5>::new();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+            reference.append(*leaf).unwrap();
+            assert_eq!(tree.root(), reference.root());
+        }
+        assert_eq!(tree.root(), naive_root(&leaves, 5));
+        assert_eq!(tree.leaves().collect::<Vec<_>>(), leaves);
+        for index in 0..leaves.len() {
+            let proof = tree.prove(index).unwrap();
+            assert_eq!(proof.siblings, naive_proof(&leaves, 5, index));
+            assert_eq!(proof.siblings, reference.prove(index).unwrap().siblings);
+            assert!(proof.verify(tree.root()));
+        }
+        assert!(tree.prove(19).is_err());
+    }
+
+    #[test]
+    fn generic_hashers_match_the_const_height_tree() {
+        let leaves = leaves(6);
+        let mut tree = DynIncrementalMerkleTree::<SortedKeccak256Hasher>::new(3);
+        let mut reference = IncrementalMerkleTree::<3, SortedKeccak256Hasher>::new();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+            reference.append(*leaf).unwrap();
+        }
+        assert_eq!(tree.root(), reference.root());
+        let proof = tree.prove(4).unwrap();
+        assert!(proof.verify_with::<SortedKeccak256Hasher>(tree.root()));
+        assert_eq!(proof.siblings, reference.prove(4).unwrap().siblings);
+    }
+
+    #[test]
+    fn leaves_are_keyed_by_generalized_index() {
+        let leaves = leaves(3);
+        let mut tree = <DynIncrementalMerkleTree>::new(2);
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+        assert!(tree.is_full());
+        assert!(tree.append(leaves[0]).is_err());
+        assert_eq!(tree.intermediates.get(4), Some(leaves[0]));
+        assert_eq!(tree.intermediates.get(6), Some(leaves[2]));
+        assert_eq!(tree.intermediates.get(1), Some(tree.root()));
+    }
+
+    #[test]
+    fn deferred_and_batched_appends_match_appends() {
+        let leaves = leaves(21);
+        let mut tree = <DynIncrementalMerkleTree>::new(5);
+        let mut reference = IncrementalMerkleTree::<5>::new();
+        for chunk in leaves.chunks(4) {
+            tree.append_deferred(chunk[0]).unwrap();
+            reference.append_deferred(chunk[0]).unwrap();
+            assert_eq!(tree.root(), reference.root());
+            assert_eq!(tree.prove_latest(), tree.prove(tree.len() - 1));
+            assert!(tree.prove_latest().unwrap().verify(tree.root()));
+            tree.append_batch(&chunk[1..]).unwrap();
+            reference.append_batch(&chunk[1..]).unwrap();
+            assert_eq!(tree.root(), reference.root());
+            assert_eq!(tree.intermediates.get(1), Some(tree.root()));
+        }
+        assert_eq!(tree.root(), naive_root(&leaves, 5));
+        for index in 0..leaves.len() {
+            assert_eq!(
+                tree.prove(index).unwrap().siblings,
+                naive_proof(&leaves, 5, index)
+            );
+        }
+        assert!(tree.append_batch(&leaves[..11]).is_err());
+        assert_eq!(tree.len(), 21);
+        assert!(<DynIncrementalMerkleTree>::new(5).prove_latest().is_err());
+    }
+
+    #[test]
+    fn shortest_trees_hold_their_capacity() {
+        let leaves = leaves(2);
+        let mut empty = <DynIncrementalMerkleTree>::new(0);
+        assert_eq!(empty.capacity(), 0);
+        assert_eq!(empty.root(), B256::ZERO);
+        assert!(empty.append(leaves[0]).is_err());
+
+        let mut single = <DynIncrementalMerkleTree>::new(1);
+        single.append(leaves[0]).unwrap();
+        assert!(single.append(leaves[1]).is_err());
+        assert_eq!(single.root(), naive_root(&leaves[..1], 1));
+        assert!(single.prove(0).unwrap().verify(single.root()));
+        assert!(single.prove_latest().unwrap().verify(single.root()));
+    }
+
+    #[test]
+    fn tallest_tree_appends_and_proves() {
+        let mut tree = <DynIncrementalMerkleTree>::new(<DynIncrementalMerkleTree>::MAX_HEIGHT);
+        for leaf in leaves(2) {
+            tree.append(leaf).unwrap();
+        }
+        assert_eq!(tree.capacity(), usize::MAX >> 1);
+        let root = tree.root();
+        assert!(tree.prove(1).unwrap().verify(root));
+        assert!(tree.prove_latest().unwrap().verify(root));
+    }
+}
@@ -0,0 +1,199 @@
+//! A C interface to keccak [DynIncrementalMerkleTree]s, so services embedding the tree through cgo or
+//! ctypes compute the same roots and proofs as Rust ones. The matching header is `include/imt.h`,
+//! generated with `cbindgen --config cbindgen.toml --output include/imt.h`. Build the library with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib` (or `staticlib` for cgo).
+//!
+//! Hashes are passed as pointers to 32 bytes. Every function returning an [ImtStatus] leaves its
+//! outputs untouched unless it returns [ImtStatus::Ok].
+
+use alloc::boxed::Box;
+use alloy_primitives::B256;
+use core::slice;
+
+use crate::{dyn_tree::DynIncrementalMerkleTree, proof::verify, IncrementalMerkleTreeError};
+
+/// The outcome of a fallible FFI call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImtStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A required pointer was null
+    NullPointer = 1,
+    /// The tree cannot hold any more leaves
+    TreeFull = 2,
+    /// No leaf has been appended at the index
+    IndexOutOfBounds = 3,
+    /// The output buffer is too small
+    BufferTooSmall = 4,
+}
+
+/// An opaque handle to a keccak [DynIncrementalMerkleTree].
+pub struct ImtTree {
+    /// The wrapped tree
+    tree: DynIncrementalMerkleTree,
+}
+
+/// Create a new, empty tree of the given `height`.
+///
+/// Returns null if the height is too large. Free the tree with [imt_tree_free].
+#[no_mangle]
+pub extern "C" fn imt_tree_new(height: usize) -> *mut ImtTree {
+    if height > <DynIncrementalMerkleTree>::MAX_HEIGHT {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(ImtTree {
+        tree: DynIncrementalMerkleTree::new(height),
+    }))
+}
+
+/// Free a tree created with [imt_tree_new]. Passing null does nothing.
+///
+/// # Safety
+/// `tree` must be null or a pointer returned by [imt_tree_new] that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_free(tree: *mut ImtTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Returns the height of `tree`, or `0` if it is null.
+///
+/// # Safety
+/// `tree` must be null or a live pointer returned by [imt_tree_new].
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_height(tree: *const ImtTree) -> usize {
+    tree.as_ref().map_or(0, |tree| tree.tree.height())
+}
+
+/// Returns the number of leaves appended to `tree`, or `0` if it is null.
+///
+/// # Safety
+/// `tree` must be null or a live pointer returned by [imt_tree_new].
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_len(tree: *const ImtTree) -> usize {
+    tree.as_ref().map_or(0, |tree| tree.tree.len())
+}
+
+/// Append the 32-byte `leaf` to `tree`.
+///
+/// # Safety
+/// `tree` must be null or a live pointer returned by [imt_tree_new], and `leaf` must be null or
+/// point to 32 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_append(tree: *mut ImtTree, leaf: *const u8) -> ImtStatus {
+    let (Some(tree), Some(leaf)) = (tree.as_mut(), read_hash(leaf)) else {
+        return ImtStatus::NullPointer;
+    };
+    match tree.tree.append(leaf) {
+        Ok(()) => ImtStatus::Ok,
+        Err(error) => status(error),
+    }
+}
+
+/// Write the 32-byte root of `tree` to `root_out`.
+///
+/// # Safety
+/// `tree` must be null or a live pointer returned by [imt_tree_new], and `root_out` must be null or
+/// point to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_root(tree: *const ImtTree, root_out: *mut u8) -> ImtStatus {
+    let Some(tree) = tree.as_ref() else {
+        return ImtStatus::NullPointer;
+    };
+    if root_out.is_null() {
+        return ImtStatus::NullPointer;
+    }
+    write_hashes(root_out, &[tree.tree.root()]);
+    ImtStatus::Ok
+}
+
+/// Write the leaf at `index` of `tree` to `leaf_out`, and its siblings from the leaf level upwards to
+/// `siblings_out`, 32 bytes each. `siblings_capacity` is the number of siblings `siblings_out` can
+/// hold, which must be at least the height of the tree.
+///
+/// # Safety
+/// `tree` must be null or a live pointer returned by [imt_tree_new], `leaf_out` must be null or
+/// point to 32 writable bytes, and `siblings_out` must be null or point to `32 * siblings_capacity`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imt_tree_prove(
+    tree: *mut ImtTree,
+    index: usize,
+    leaf_out: *mut u8,
+    siblings_out: *mut u8,
+    siblings_capacity: usize,
+) -> ImtStatus {
+    let Some(tree) = tree.as_mut() else {
+        return ImtStatus::NullPointer;
+    };
+    if leaf_out.is_null() || siblings_out.is_null() {
+        return ImtStatus::NullPointer;
+    }
+    if siblings_capacity < tree.tree.height() {
+        return ImtStatus::BufferTooSmall;
+    }
+    match tree.tree.prove(index) {
+        Ok(proof) => {
+            write_hashes(leaf_out, &[proof.leaf]);
+            write_hashes(siblings_out, &proof.siblings);
+            ImtStatus::Ok
+        }
+        Err(error) => status(error),
+    }
+}
+
+/// Returns `true` if the `height` siblings at `siblings` show that the 32-byte `leaf` sits at `index`
+/// in the keccak tree committed to by the 32-byte `root`. Returns `false` if a pointer is null.
+///
+/// # Safety
+/// `root` and `leaf` must be null or point to 32 readable bytes, and `siblings` must be null or point
+/// to `32 * height` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn imt_verify(
+    root: *const u8,
+    leaf: *const u8,
+    index: usize,
+    siblings: *const u8,
+    height: usize,
+) -> bool {
+    let (Some(root), Some(leaf)) = (read_hash(root), read_hash(leaf)) else {
+        return false;
+    };
+    if siblings.is_null() {
+        return false;
+    }
+    let siblings: alloc::vec::Vec<B256> = slice::from_raw_parts(siblings, height * 32)
+        .chunks_exact(32)
+        .map(B256::from_slice)
+        .collect();
+    verify(root, leaf, index, &siblings)
+}
+
+/// Read a hash from 32 bytes at `ptr`, or `None` if it is null.
+///
+/// # Safety
+/// `ptr` must be null or point to 32 readable bytes.
+unsafe fn read_hash(ptr: *const u8) -> Option<B256> {
+    (!ptr.is_null()).then(|| B256::from_slice(slice::from_raw_parts(ptr, 32)))
+}
+
+/// Write `hashes` back to back to `out`.
+///
+/// # Safety
+/// `out` must point to `32 * hashes.len()` writable bytes.
+unsafe fn write_hashes(out: *mut u8, hashes: &[B256]) {
+    let out = slice::from_raw_parts_mut(out, hashes.len() * 32);
+    for (chunk, hash) in out.chunks_exact_mut(32).zip(hashes) {
+        chunk.copy_from_slice(hash.as_slice());
+    }
+}
+
+/// Map a tree error to the matching [ImtStatus].
+fn status(error: IncrementalMerkleTreeError) -> ImtStatus {
+    match error {
+        IncrementalMerkleTreeError::TreeFull { .. } => ImtStatus::TreeFull,
+        _ => ImtStatus::IndexOutOfBounds,
+    }
+}
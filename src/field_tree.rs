@@ -0,0 +1,194 @@
+use alloc::{vec, vec::Vec};
+use ark_ff::PrimeField;
+use core::marker::PhantomData;
+
+use crate::{incremental_tree::capacity, IncrementalMerkleTreeError};
+
+/// [FieldHasher] is the algebraic hash function combining two sibling nodes of a [FieldMerkleTree]
+/// over the prime field `F`, such as the Poseidon permutation a circuit proves.
+pub trait FieldHasher<F: PrimeField>: 'static {
+    /// Hash the `left` and `right` children of a node into the node itself. Named apart from
+    /// [MerkleHasher::hash_pair](crate::MerkleHasher::hash_pair), so hashers implementing both stay
+    /// unambiguous.
+    fn hash_elements(left: &F, right: &F) -> F;
+}
+
+/// [PoseidonHasher](crate::hasher::PoseidonHasher) hashes BN254 scalars with the same circom
+/// Poseidon permutation it applies to byte nodes, so a [FieldMerkleTree] of `Fr` and an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) of canonical `Fr` encodings share their
+/// roots.
+#[cfg(feature = "poseidon")]
+impl FieldHasher<ark_bn254::Fr> for crate::hasher::PoseidonHasher {
+    fn hash_elements(left: &ark_bn254::Fr, right: &ark_bn254::Fr) -> ark_bn254::Fr {
+        use light_poseidon::{Poseidon, PoseidonHasher as _};
+
+        Poseidon::<ark_bn254::Fr>::new_circom(2)
+            .and_then(|mut poseidon| poseidon.hash(&[*left, *right]))
+            .expect("circom poseidon parameters exist for two inputs")
+    }
+}
+
+/// An inclusion proof for a single leaf of a [FieldMerkleTree] of height `HEIGHT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMerkleProof<const HEIGHT: usize, F> {
+    /// The proven leaf
+    pub leaf: F,
+    /// The index of the leaf in the tree
+    pub index: usize,
+    /// The siblings on the path from the leaf up to the root, starting at the leaf level
+    pub siblings: [F; HEIGHT],
+}
+
+impl<const HEIGHT: usize, F: PrimeField> FieldMerkleProof<HEIGHT, F> {
+    /// Returns the root implied by the proof in a tree built with the hasher `H`.
+    pub fn root<H: FieldHasher<F>>(&self) -> F {
+        let mut position = self.index;
+        self.siblings.iter().fold(self.leaf, |node, sibling| {
+            let parent = if position & 1 == 1 {
+                H::hash_elements(sibling, &node)
+            } else {
+                H::hash_elements(&node, sibling)
+            };
+            position >>= 1;
+            parent
+        })
+    }
+
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` under `root` in a
+    /// tree built with the hasher `H`.
+    pub fn verify<H: FieldHasher<F>>(&self, root: F) -> bool {
+        (HEIGHT >= usize::BITS as usize || self.index >> HEIGHT == 0) && self.root::<H>() == root
+    }
+}
+
+/// [FieldMerkleTree] is an append-only merkle tree whose leaves and nodes are elements of the
+/// arkworks prime field `F`, combined with the algebraic [FieldHasher] `H`. It follows the padding
+/// rules of [IncrementalMerkleTree](crate::IncrementalMerkleTree): empty leaves are zero, empty
+/// subtrees hash up from them, and the tree holds up to `2^HEIGHT - 1` leaves. Circuit witness
+/// generation can therefore read its proofs and [Self::zero_hashes] directly, and agree with the
+/// off-circuit accumulator by construction.
+#[derive(Debug, Clone)]
+pub struct FieldMerkleTree<const HEIGHT: usize, F, H> {
+    /// The zero hashes, one per level including the root
+    zero_hashes: Vec<F>,
+    /// The nodes covering at least one appended leaf, by height and then by horizontal position
+    levels: Vec<Vec<F>>,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<const HEIGHT: usize, F: PrimeField, H: FieldHasher<F>> Default
+    for FieldMerkleTree<HEIGHT, F, H>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const HEIGHT: usize, F: PrimeField, H: FieldHasher<F>> FieldMerkleTree<HEIGHT, F, H> {
+    /// Create a new [FieldMerkleTree], precomputing its zero hashes.
+    pub fn new() -> Self {
+        let mut zero_hashes = vec![F::zero()];
+        for height in 1..=HEIGHT {
+            zero_hashes.push(H::hash_elements(
+                &zero_hashes[height - 1],
+                &zero_hashes[height - 1],
+            ));
+        }
+        Self {
+            zero_hashes,
+            levels: vec![Vec::new(); HEIGHT + 1],
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the zero hashes from the empty leaf up to the root of the empty tree, the padding
+    /// constants a circuit over the same tree needs.
+    pub fn zero_hashes(&self) -> &[F] {
+        &self.zero_hashes
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Returns the leaf at `index`, or `None` if no leaf has been appended there.
+    pub fn leaf(&self, index: usize) -> Option<F> {
+        self.levels[0].get(index).copied()
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> F {
+        self.levels[HEIGHT]
+            .first()
+            .copied()
+            .unwrap_or(self.zero_hashes[HEIGHT])
+    }
+
+    /// Append a new leaf to the tree and rehash the path above it.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&mut self, leaf: F) -> Result<(), IncrementalMerkleTreeError> {
+        if self.len() >= capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        self.levels[0].push(leaf);
+
+        let mut position = self.len() - 1;
+        for height in 1..=HEIGHT {
+            let first = position & !1;
+            let parent = H::hash_elements(
+                &self.node(height - 1, first),
+                &self.node(height - 1, first + 1),
+            );
+            position >>= 1;
+            match self.levels[height].get_mut(position) {
+                Some(node) => *node = parent,
+                None => self.levels[height].push(parent),
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    pub fn prove(
+        &self,
+        index: usize,
+    ) -> Result<FieldMerkleProof<HEIGHT, F>, IncrementalMerkleTreeError> {
+        let leaf = self
+            .leaf(index)
+            .ok_or(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            })?;
+        Ok(FieldMerkleProof {
+            leaf,
+            index,
+            siblings: core::array::from_fn(|height| self.node(height, (index >> height) ^ 1)),
+        })
+    }
+
+    /// Returns the node at `height` and horizontal `position`, substituting the zero hash of that
+    /// height for nodes whose subtree holds no appended leaves.
+    fn node(&self, height: usize, position: usize) -> F {
+        self.levels[height]
+            .get(position)
+            .copied()
+            .unwrap_or(self.zero_hashes[height])
+    }
+}
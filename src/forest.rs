@@ -0,0 +1,291 @@
+use alloc::collections::BTreeMap;
+use alloy_primitives::B256;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    proof::MerkleProof,
+    store::{MemoryStore, TreeStore},
+    IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// [Forest] manages many [IncrementalMerkleTree]s of the same height and hasher, such as one per
+/// channel, rollup or epoch, identified by caller-chosen ids. Every tree writes its intermediates
+/// cache to the single [TreeStore] of the forest, with the generalized indices of tree `id` offset
+/// by `id * 2^(HEIGHT + 1)`, so thousands of trees share one backend instead of one hash map each.
+/// The zero hashes are shared as well, as they are between any trees of the same hasher.
+///
+/// Ids must be below [Forest::max_trees], so that every offset node fits in a `usize`, and the height
+/// must be at most `usize::BITS - 2`, so that the forest can hold more than one tree.
+pub struct Forest<const HEIGHT: usize, H = Keccak256Hasher, S = MemoryStore> {
+    /// The store holding the nodes of every tree
+    store: S,
+    /// The state of every tree outside of its nodes, by id
+    trees: BTreeMap<usize, IncrementalMerkleTree<HEIGHT, H, Detached>>,
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default> Default
+    for Forest<HEIGHT, H, S>
+{
+    fn default() -> Self {
+        Self::with_store(S::default())
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default> Forest<HEIGHT, H, S> {
+    /// Create a new, empty [Forest].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> Forest<HEIGHT, H, S> {
+    /// Rejects, when the forest type is instantiated, heights that leave no room for tree ids: the
+    /// nodes of tree `id` are offset by `id * 2^(HEIGHT + 1)`, which must fit in a `usize` for more
+    /// than one tree.
+    const SUPPORTED_HEIGHT: () = assert!(
+        HEIGHT + 1 < usize::BITS as usize,
+        "the forest tree height must be at least two below the bit width of usize"
+    );
+
+    /// Create a new, empty [Forest] writing the nodes of its trees to `store`, which must not hold
+    /// any node yet.
+    pub fn with_store(store: S) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED_HEIGHT;
+        Self {
+            store,
+            trees: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of tree ids the forest can address, `2^(usize::BITS - HEIGHT - 1)`.
+    pub fn max_trees(&self) -> usize {
+        1 << (usize::BITS as usize - HEIGHT - 1)
+    }
+
+    /// Returns the number of trees in the forest.
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Returns `true` if the forest holds no tree.
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    /// Returns the ids of the trees in the forest, in ascending order.
+    pub fn ids(&self) -> impl DoubleEndedIterator<Item = usize> + ExactSizeIterator + '_ {
+        self.trees.keys().copied()
+    }
+
+    /// Returns `true` if the forest holds a tree with id `id`.
+    pub fn contains(&self, id: usize) -> bool {
+        self.trees.contains_key(&id)
+    }
+
+    /// Add an empty tree with id `id` to the forest.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree was added.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `id` is not below
+    ///   [Self::max_trees].
+    /// - `Err(IncrementalMerkleTreeError::ValueExists)` if the forest already holds a tree `id`.
+    pub fn create_tree(&mut self, id: usize) -> Result<(), IncrementalMerkleTreeError> {
+        if id >= self.max_trees() {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: id,
+                len: self.max_trees(),
+            });
+        }
+        if self.trees.contains_key(&id) {
+            return Err(IncrementalMerkleTreeError::ValueExists);
+        }
+        self.trees
+            .insert(id, IncrementalMerkleTree::with_store(Detached));
+        Ok(())
+    }
+
+    /// Returns the number of leaves in tree `id`, or `None` if the forest holds no such tree.
+    pub fn tree_len(&self, id: usize) -> Option<usize> {
+        self.trees.get(&id).map(IncrementalMerkleTree::len)
+    }
+
+    /// Returns the root of tree `id`, or `None` if the forest holds no such tree.
+    pub fn root(&self, id: usize) -> Option<B256> {
+        self.trees.get(&id).map(IncrementalMerkleTree::root)
+    }
+
+    /// Append `leaf` to tree `id`.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::ValueNotFound)` if the forest holds no tree `id`.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&mut self, id: usize, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        self.with_tree(id, |tree| tree.append(leaf))?
+    }
+
+    /// Append every leaf in `leaves` to tree `id`, as [IncrementalMerkleTree::append_batch] does.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::ValueNotFound)` if the forest holds no tree `id`.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the tree
+    ///   is left unchanged.
+    pub fn append_batch(
+        &mut self,
+        id: usize,
+        leaves: &[B256],
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        self.with_tree(id, |tree| tree.append_batch(leaves))?
+    }
+
+    /// Generate an inclusion proof for the leaf at `index` of tree `id`.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::ValueNotFound)` if the forest holds no tree `id`.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    pub fn prove(
+        &mut self,
+        id: usize,
+        index: usize,
+    ) -> Result<MerkleProof<HEIGHT>, IncrementalMerkleTreeError> {
+        self.with_tree(id, |tree| tree.prove(index))?
+    }
+
+    /// Remove tree `id` and all of its nodes from the forest.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree was removed.
+    /// - `Err(IncrementalMerkleTreeError::ValueNotFound)` if the forest holds no tree `id`.
+    pub fn remove_tree(&mut self, id: usize) -> Result<(), IncrementalMerkleTreeError> {
+        let gindices: alloc::vec::Vec<usize> = self.with_tree(id, |tree| {
+            tree.export_nodes()
+                .map(|(gindex, _)| gindex as usize)
+                .collect()
+        })?;
+        let offset = Self::offset(id);
+        for gindex in gindices {
+            self.store.remove(offset + gindex);
+        }
+        self.trees.remove(&id);
+        Ok(())
+    }
+
+    /// Returns the offset of the generalized indices of tree `id` in the store of the forest.
+    fn offset(id: usize) -> usize {
+        id << (HEIGHT + 1)
+    }
+
+    /// Run `f` on tree `id`, attached to the nodes it holds in the store of the forest.
+    fn with_tree<R>(
+        &mut self,
+        id: usize,
+        f: impl FnOnce(&mut IncrementalMerkleTree<HEIGHT, H, ForestView<'_, S>>) -> R,
+    ) -> Result<R, IncrementalMerkleTreeError> {
+        let detached = self
+            .trees
+            .remove(&id)
+            .ok_or(IncrementalMerkleTreeError::ValueNotFound)?;
+        let view = ForestView {
+            store: &mut self.store,
+            offset: Self::offset(id),
+        };
+        let (mut tree, _) = detached.replace_store(view);
+        let result = f(&mut tree);
+        let (detached, _) = tree.replace_store(Detached);
+        self.trees.insert(id, detached);
+        Ok(result)
+    }
+}
+
+/// The store of a tree of a [Forest] while it is not borrowed by an operation. Trees only read and
+/// write their nodes while attached to a [ForestView].
+struct Detached;
+
+impl TreeStore for Detached {
+    fn get(&self, _index: usize) -> Option<B256> {
+        unreachable!("a detached forest tree read a node")
+    }
+
+    fn put(&mut self, _index: usize, _node: B256) {
+        unreachable!("a detached forest tree wrote a node")
+    }
+
+    fn remove(&mut self, _index: usize) {
+        unreachable!("a detached forest tree removed a node")
+    }
+}
+
+/// The nodes of one tree within the store of a [Forest].
+struct ForestView<'a, S> {
+    /// The store of the forest
+    store: &'a mut S,
+    /// The key of the tree's generalized index `0`
+    offset: usize,
+}
+
+impl<S: TreeStore> TreeStore for ForestView<'_, S> {
+    fn get(&self, index: usize) -> Option<B256> {
+        self.store.get(self.offset + index)
+    }
+
+    fn put(&mut self, index: usize, node: B256) {
+        self.store.put(self.offset + index, node);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.store.remove(self.offset + index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{leaves, naive_root};
+
+    #[test]
+    fn trees_share_the_store() {
+        let leaves = leaves(10);
+        let mut forest = Forest::<4>::new();
+        forest.create_tree(0).unwrap();
+        forest.create_tree(7).unwrap();
+        forest.append_batch(0, &leaves[..6]).unwrap();
+        for leaf in &leaves[6..] {
+            forest.append(7, *leaf).unwrap();
+        }
+        assert_eq!(forest.root(0), Some(naive_root(&leaves[..6], 4)));
+        assert_eq!(forest.root(7), Some(naive_root(&leaves[6..], 4)));
+        assert!(forest
+            .prove(7, 2)
+            .unwrap()
+            .verify(naive_root(&leaves[6..], 4)));
+
+        forest.remove_tree(0).unwrap();
+        assert_eq!(forest.ids().collect::<alloc::vec::Vec<_>>(), [7]);
+        assert!(forest.append(0, leaves[0]).is_err());
+        assert_eq!(forest.root(7), Some(naive_root(&leaves[6..], 4)));
+    }
+
+    #[test]
+    fn tallest_forest_addresses_two_trees() {
+        let leaves = leaves(2);
+        let mut forest = Forest::<{ usize::BITS as usize - 2 }>::new();
+        assert_eq!(forest.max_trees(), 2);
+        forest.create_tree(0).unwrap();
+        forest.create_tree(1).unwrap();
+        assert!(matches!(
+            forest.create_tree(2),
+            Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: 2, len: 2 })
+        ));
+        forest.append(0, leaves[0]).unwrap();
+        forest.append(1, leaves[1]).unwrap();
+        let proof = forest.prove(1, 0).unwrap();
+        assert_eq!(proof.leaf, leaves[1]);
+        assert!(proof.verify(forest.root(1).unwrap()));
+        forest.remove_tree(1).unwrap();
+        assert!(forest.prove(0, 0).unwrap().verify(forest.root(0).unwrap()));
+    }
+}
@@ -0,0 +1,182 @@
+use alloy_primitives::B256;
+use core::marker::PhantomData;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    incremental_tree::capacity,
+    Frontier, IncrementalMerkleTreeError,
+};
+
+/// [FrontierTree] is the minimal form of an [IncrementalMerkleTree](crate::IncrementalMerkleTree):
+/// it only keeps the active branch, like the deposit contract, so it supports appends and roots but
+/// no proofs. It never allocates, and only takes `2 * HEIGHT * 32` bytes plus the size, which suits
+/// embedded signers and zkVM guests with tight memory budgets.
+///
+/// Its roots are the padded roots of an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) of the same height and hasher, and it
+/// converts to and from the same [Frontier].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrontierTree<const HEIGHT: usize, H = Keccak256Hasher> {
+    /// The zero hashes
+    zero_hashes: [B256; HEIGHT],
+    /// The active branch of the tree, used to calculate the root hash
+    active_branch: [B256; HEIGHT],
+    /// The number of leaves that have been added to the tree
+    size: usize,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher> Default for FrontierTree<HEIGHT, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher> FrontierTree<HEIGHT, H> {
+    /// Create a new, empty [FrontierTree]. The zero hashes are read from
+    /// [MerkleHasher::precomputed_zero_hashes] where available, and hashed otherwise.
+    pub fn new() -> Self {
+        let precomputed = H::precomputed_zero_hashes();
+        let mut zero_hashes = [B256::ZERO; HEIGHT];
+        for height in 1..HEIGHT {
+            zero_hashes[height] = match precomputed.get(height) {
+                Some(zero) => *zero,
+                None => H::hash_pair(&zero_hashes[height - 1], &zero_hashes[height - 1]),
+            };
+        }
+        Self {
+            zero_hashes,
+            active_branch: [B256::ZERO; HEIGHT],
+            size: 0,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Create a [FrontierTree] resuming from `frontier`.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` holding the frontier's leaves.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the frontier's size exceeds the
+    ///   capacity of the tree.
+    pub fn from_frontier(frontier: Frontier<HEIGHT>) -> Result<Self, IncrementalMerkleTreeError> {
+        if frontier.size > capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        Ok(Self {
+            active_branch: frontier.active_branch,
+            size: frontier.size,
+            ..Self::new()
+        })
+    }
+
+    /// Returns the [Frontier] of the tree.
+    pub fn frontier(&self) -> Frontier<HEIGHT> {
+        Frontier {
+            size: self.size,
+            active_branch: self.active_branch,
+        }
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Append a new leaf to the tree.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&mut self, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        if self.size >= capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        self.size += 1;
+
+        // Carry the new node up through the complete subtrees it finishes, and store it at the first
+        // height where the size has a set bit.
+        let mut size = self.size;
+        let mut node = leaf;
+        for height in 0..HEIGHT {
+            if size & 1 == 1 {
+                self.active_branch[height] = node;
+                return Ok(());
+            }
+            node = H::hash_pair(&self.active_branch[height], &node);
+            size >>= 1;
+        }
+        Err(IncrementalMerkleTreeError::LoopDidNotTerminate)
+    }
+
+    /// Returns the root of the tree, padded with zero hashes up to `HEIGHT`.
+    pub fn root(&self) -> B256 {
+        let mut size = self.size;
+        (0..HEIGHT).fold(B256::ZERO, |tree_root, height| {
+            let parent = if size & 1 == 1 {
+                H::hash_pair(&self.active_branch[height], &tree_root)
+            } else {
+                H::hash_pair(&tree_root, &self.zero_hashes[height])
+            };
+            size >>= 1;
+            parent
+        })
+    }
+}
+
+/// Compute the padded root of a keccak tree of height `HEIGHT` holding the leaves yielded by `iter`,
+/// in order, without materializing the tree: the leaves are folded through a [FrontierTree], so only
+/// the active branch is kept in memory.
+///
+/// # Panics
+/// If `iter` yields more leaves than the tree can hold.
+pub fn root_from_iter<const HEIGHT: usize, I: IntoIterator<Item = B256>>(iter: I) -> B256 {
+    root_from_iter_with::<HEIGHT, Keccak256Hasher, I>(iter)
+}
+
+/// Like [root_from_iter], for a tree built with the hasher `H`.
+pub fn root_from_iter_with<const HEIGHT: usize, H: MerkleHasher, I: IntoIterator<Item = B256>>(
+    iter: I,
+) -> B256 {
+    let mut tree = FrontierTree::<HEIGHT, H>::new();
+    for leaf in iter {
+        tree.append(leaf)
+            .expect("folded more leaves than the tree can hold");
+    }
+    tree.root()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hash_pair, leaves};
+
+    #[test]
+    fn tallest_frontier_tree_round_trips() {
+        let leaves = leaves(1);
+        let mut tree = FrontierTree::<64>::new();
+        tree.append(leaves[0]).unwrap();
+        let mut root = leaves[0];
+        let mut zero = B256::ZERO;
+        for _ in 0..64 {
+            root = hash_pair(root, zero);
+            zero = hash_pair(zero, zero);
+        }
+        assert_eq!(tree.root(), root);
+        assert_eq!(
+            FrontierTree::<64>::from_frontier(tree.frontier())
+                .unwrap()
+                .root(),
+            root
+        );
+    }
+}
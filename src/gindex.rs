@@ -0,0 +1,86 @@
+//! Generalized index arithmetic, as used to key the [TreeStore](crate::TreeStore) cache and by SSZ
+//! proofs: the root is `1`, and the children of node `g` are `2g` and `2g + 1`, so the node at
+//! `depth` below the root and horizontal `index` is `2^depth + index`.
+//!
+//! Depths count down from the root, while the heights taken by the trees count up from the leaves:
+//! the leaf `index` of a tree of height `HEIGHT` is `gindex(HEIGHT, index)`, and its node at `height`
+//! and `position` is `gindex(HEIGHT - height, position)`.
+
+/// Returns the generalized index of the node at `depth` below the root and horizontal `index`.
+///
+/// # Panics
+/// In debug builds, if `index` is not below `2^depth`.
+pub const fn gindex(depth: usize, index: usize) -> usize {
+    debug_assert!(index < 1 << depth, "index out of range for the depth");
+    (1 << depth) + index
+}
+
+/// Returns the depth below the root of the node at generalized index `gindex`.
+///
+/// # Panics
+/// If `gindex` is `0`, which is not a generalized index.
+pub const fn depth(gindex: usize) -> usize {
+    assert!(gindex != 0, "0 is not a generalized index");
+    (usize::BITS - 1 - gindex.leading_zeros()) as usize
+}
+
+/// Returns the horizontal index of the node at generalized index `gindex` within its depth.
+///
+/// # Panics
+/// If `gindex` is `0`, which is not a generalized index.
+pub const fn index(gindex: usize) -> usize {
+    gindex - (1 << depth(gindex))
+}
+
+/// Returns the depth and horizontal index of the node at generalized index `gindex`, the inverse of
+/// [gindex()].
+///
+/// # Panics
+/// If `gindex` is `0`, which is not a generalized index.
+pub const fn to_depth_index(gindex: usize) -> (usize, usize) {
+    (depth(gindex), index(gindex))
+}
+
+/// Returns the generalized index of the sibling of `gindex`. The sibling of the root is `0`.
+pub const fn sibling(gindex: usize) -> usize {
+    gindex ^ 1
+}
+
+/// Returns the generalized index of the parent of `gindex`. The parent of the root is `0`.
+pub const fn parent(gindex: usize) -> usize {
+    gindex >> 1
+}
+
+/// Returns the generalized index of the left child of `gindex`.
+pub const fn left_child(gindex: usize) -> usize {
+    gindex << 1
+}
+
+/// Returns the generalized index of the right child of `gindex`.
+pub const fn right_child(gindex: usize) -> usize {
+    (gindex << 1) | 1
+}
+
+/// Returns `true` if `gindex` is the root or lies on the path from `descendant` up to the root,
+/// including `descendant` itself.
+pub const fn is_ancestor(gindex: usize, descendant: usize) -> bool {
+    gindex != 0
+        && descendant != 0
+        && depth(gindex) <= depth(descendant)
+        && descendant >> (depth(descendant) - depth(gindex)) == gindex
+}
+
+/// Returns the generalized indices of the nodes on the path from `gindex` up to the root, starting
+/// at `gindex` itself and ending at the root.
+pub fn path(gindex: usize) -> impl Iterator<Item = usize> {
+    core::iter::successors((gindex != 0).then_some(gindex), |&node| {
+        (node > 1).then_some(node >> 1)
+    })
+}
+
+/// Returns the generalized indices of the siblings a proof of `gindex` holds, bottom up: the
+/// sibling of `gindex`, then the sibling of its parent, up to a child of the root. This is the order
+/// [MerkleProof::siblings](crate::MerkleProof::siblings) lists them in.
+pub fn branch(gindex: usize) -> impl Iterator<Item = usize> {
+    path(gindex).take_while(|&node| node > 1).map(sibling)
+}
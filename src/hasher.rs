@@ -0,0 +1,429 @@
+use alloy_primitives::{b256, keccak256, Keccak256, B256};
+
+mod keccak_x4;
+
+/// [MerkleHasher] is the hash function used to combine two sibling nodes into their parent.
+pub trait MerkleHasher: 'static {
+    /// Hash the `left` and `right` children of a node into the node itself.
+    fn hash_pair(left: &B256, right: &B256) -> B256;
+
+    /// Hash the `children` of a node of a tree with a branching factor other than two, such as an
+    /// [IncrementalMerkleTree](crate::IncrementalMerkleTree) with an `ARITY` of 5, into the node
+    /// itself. The default folds the children from the left with [Self::hash_pair]; hashers with a
+    /// native multi-input form override it.
+    ///
+    /// # Panics
+    /// If `children` is empty.
+    fn hash_children(children: &[B256]) -> B256 {
+        children[1..]
+            .iter()
+            .fold(children[0], |node, child| Self::hash_pair(&node, child))
+    }
+
+    /// Hash every pair of `children` into the matching entry of `parents`, as [Self::hash_pair]
+    /// does. Bulk operations such as
+    /// [IncrementalMerkleTree::append_batch](crate::IncrementalMerkleTree::append_batch) and cache
+    /// rebuilds hash whole levels through it, so hashers with a multi-lane implementation override
+    /// it. The default hashes the pairs one at a time.
+    ///
+    /// # Panics
+    /// If `children` and `parents` differ in length.
+    fn hash_pairs(children: &[[B256; 2]], parents: &mut [B256]) {
+        assert_eq!(children.len(), parents.len(), "one parent per pair");
+        for ([left, right], parent) in children.iter().zip(parents) {
+            *parent = Self::hash_pair(left, right);
+        }
+    }
+
+    /// Returns a precomputed ladder of zero hashes: the empty leaf, and then the parent of two empty
+    /// subtrees of the height below. Trees read their full-width zero hashes from it instead of
+    /// hashing them, and only compute the heights beyond its end. The default is empty.
+    fn precomputed_zero_hashes() -> &'static [B256] {
+        &[]
+    }
+}
+
+/// [DigestHasher] is a [MerkleHasher] built on a hash function of arbitrary byte strings, which also
+/// hashes leaf payloads, e.g. through a [LeafDomain](crate::LeafDomain).
+pub trait DigestHasher: MerkleHasher {
+    /// Hash the concatenation of `parts`.
+    fn hash_bytes(parts: &[&[u8]]) -> B256;
+}
+
+/// [Keccak256Hasher] hashes the concatenation `left || right` with `keccak256`, as the EVM does.
+///
+/// With the `asm-keccak` feature, `keccak256` runs the assembly permutation of `keccak-asm` on the
+/// targets it supports instead of the pure-Rust one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keccak256Hasher;
+
+impl MerkleHasher for Keccak256Hasher {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        let mut hash_buf = [0u8; 64];
+        hash_buf[..32].copy_from_slice(left.as_slice());
+        hash_buf[32..].copy_from_slice(right.as_slice());
+        keccak256(hash_buf)
+    }
+
+    fn hash_children(children: &[B256]) -> B256 {
+        let buf: alloc::vec::Vec<u8> = children.iter().flat_map(|child| child.0).collect();
+        keccak256(buf)
+    }
+
+    /// Hash four pairs at a time with a four-lane keccak permutation, and the remainder one by one,
+    /// when AVX2 is enabled at compile time to vectorize the four lanes with. Other targets hash the
+    /// pairs one by one, which is faster than running the lanes in turn.
+    fn hash_pairs(children: &[[B256; 2]], parents: &mut [B256]) {
+        assert_eq!(children.len(), parents.len(), "one parent per pair");
+        if cfg!(not(target_feature = "avx2")) {
+            for ([left, right], parent) in children.iter().zip(parents) {
+                *parent = Self::hash_pair(left, right);
+            }
+            return;
+        }
+        let mut batches = children.chunks_exact(keccak_x4::LANES);
+        let mut outputs = parents.chunks_exact_mut(keccak_x4::LANES);
+        for (batch, output) in (&mut batches).zip(&mut outputs) {
+            let batch = batch.try_into().expect("chunk of one batch");
+            output.copy_from_slice(&keccak_x4::hash_pairs(batch));
+        }
+        for ([left, right], parent) in batches.remainder().iter().zip(outputs.into_remainder()) {
+            *parent = Self::hash_pair(left, right);
+        }
+    }
+
+    fn precomputed_zero_hashes() -> &'static [B256] {
+        &KECCAK256_ZERO_HASHES
+    }
+}
+
+impl DigestHasher for Keccak256Hasher {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        let mut hasher = Keccak256::new();
+        parts.iter().for_each(|part| hasher.update(part));
+        hasher.finalize()
+    }
+}
+
+/// [KeccakBackend] is an implementation of `keccak256` to hash the nodes of a tree with, such as the
+/// accelerated keccak precompile or syscall of a zkVM guest (SP1, RISC Zero), which is far cheaper to
+/// prove than the pure-Rust permutation.
+pub trait KeccakBackend: 'static {
+    /// Returns `keccak256(input)` of a 64-byte message, the concatenation of two sibling nodes.
+    fn keccak256_64(input: &[u8; 64]) -> B256;
+
+    /// Returns `keccak256(data)` of a message of any length, used for the children of wider nodes and
+    /// for leaf payloads. The default uses the `keccak256` of [Keccak256Hasher].
+    fn keccak256(data: &[u8]) -> B256 {
+        keccak256(data)
+    }
+}
+
+/// [KeccakBackendHasher] hashes exactly like [Keccak256Hasher], with `keccak256` computed by the
+/// [KeccakBackend] `B`, so the same tree code runs inside a zkVM guest through its precompile:
+///
+/// ```ignore
+/// struct Precompile;
+///
+/// impl KeccakBackend for Precompile {
+///     fn keccak256_64(input: &[u8; 64]) -> B256 {
+///         guest_keccak256(input)
+///     }
+/// }
+///
+/// type GuestTree = IncrementalMerkleTree<32, KeccakBackendHasher<Precompile>>;
+/// ```
+///
+/// Its roots and proofs are those of [Keccak256Hasher], and it shares its precomputed zero hashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeccakBackendHasher<B>(core::marker::PhantomData<B>);
+
+impl<B: KeccakBackend> MerkleHasher for KeccakBackendHasher<B> {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        let mut hash_buf = [0u8; 64];
+        hash_buf[..32].copy_from_slice(left.as_slice());
+        hash_buf[32..].copy_from_slice(right.as_slice());
+        B::keccak256_64(&hash_buf)
+    }
+
+    fn hash_children(children: &[B256]) -> B256 {
+        let buf: alloc::vec::Vec<u8> = children.iter().flat_map(|child| child.0).collect();
+        B::keccak256(&buf)
+    }
+
+    fn precomputed_zero_hashes() -> &'static [B256] {
+        &KECCAK256_ZERO_HASHES
+    }
+}
+
+impl<B: KeccakBackend> DigestHasher for KeccakBackendHasher<B> {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        B::keccak256(&parts.concat())
+    }
+}
+
+/// [SortedKeccak256Hasher] hashes the concatenation of the two children in ascending order with
+/// `keccak256`, like OpenZeppelin's `MerkleProof` library. A node does not depend on which side each
+/// child sits on, so proofs of a tree built with it need no index and verify with
+/// [verify_sorted](crate::verify_sorted) or OpenZeppelin's `MerkleProof.verify`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortedKeccak256Hasher;
+
+impl MerkleHasher for SortedKeccak256Hasher {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        if left <= right {
+            Keccak256Hasher::hash_pair(left, right)
+        } else {
+            Keccak256Hasher::hash_pair(right, left)
+        }
+    }
+
+    fn precomputed_zero_hashes() -> &'static [B256] {
+        // both children of an empty subtree are equal, so sorting them changes nothing
+        &KECCAK256_ZERO_HASHES
+    }
+}
+
+impl DigestHasher for SortedKeccak256Hasher {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        Keccak256Hasher::hash_bytes(parts)
+    }
+}
+
+/// [Rfc6962Hasher] adds the second-preimage protection of RFC 6962 (Certificate Transparency) to the
+/// hash function of `H`: internal nodes hash `0x01 || left || right`, and leaves are expected to be
+/// hashed behind `0x00`, with [Self::hash_leaf] or [LeafDomain::RFC6962](crate::LeafDomain::RFC6962)
+/// and [IncrementalMerkleTree::append_data](crate::IncrementalMerkleTree::append_data). An internal
+/// node can then never be presented as a leaf, and its children as a proof of it.
+///
+/// Proofs of a tree built with it verify with [verify_with](crate::verify_with) and this hasher.
+/// Empty leaves are still [B256::ZERO], and the zero hashes above them are hashed with the node
+/// prefix, so the roots of trees that are not full differ from those of RFC 6962, which does not pad.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rfc6962Hasher<H = Keccak256Hasher>(core::marker::PhantomData<H>);
+
+impl<H: DigestHasher> Rfc6962Hasher<H> {
+    /// The prefix of leaf payloads
+    pub const LEAF_PREFIX: u8 = 0x00;
+    /// The prefix of internal nodes
+    pub const NODE_PREFIX: u8 = 0x01;
+
+    /// Returns the leaf of `data`, `H(0x00 || data)`.
+    pub fn hash_leaf(data: &[u8]) -> B256 {
+        H::hash_bytes(&[&[Self::LEAF_PREFIX], data])
+    }
+}
+
+impl<H: DigestHasher> MerkleHasher for Rfc6962Hasher<H> {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        H::hash_bytes(&[&[Self::NODE_PREFIX], left.as_slice(), right.as_slice()])
+    }
+}
+
+impl<H: DigestHasher> DigestHasher for Rfc6962Hasher<H> {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        H::hash_bytes(parts)
+    }
+}
+
+/// [Sha256Hasher] hashes the concatenation `left || right` with SHA-256, as the beacon chain deposit
+/// contract and SSZ merkleization do.
+///
+/// The SHA extensions of x86 CPUs are detected and used at runtime. The `asm-sha256` feature adds
+/// those of aarch64 CPUs, and an assembly implementation for the CPUs without them.
+#[cfg(feature = "sha256")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+#[cfg(feature = "sha256")]
+impl MerkleHasher for Sha256Hasher {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(left);
+        hasher.update(right);
+        B256::from_slice(&hasher.finalize())
+    }
+
+    fn hash_children(children: &[B256]) -> B256 {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        children.iter().for_each(|child| hasher.update(child));
+        B256::from_slice(&hasher.finalize())
+    }
+}
+
+#[cfg(feature = "sha256")]
+impl DigestHasher for Sha256Hasher {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        parts.iter().for_each(|part| hasher.update(part));
+        B256::from_slice(&hasher.finalize())
+    }
+}
+
+/// [Blake3Hasher] hashes the concatenation `left || right` with BLAKE3, for trees that are never
+/// verified by the EVM, such as log or backup integrity trees, and want the fastest host-side hashing.
+/// With the `std` feature, BLAKE3 picks the fastest SIMD implementation the CPU supports at runtime.
+#[cfg(feature = "blake3")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Blake3Hasher;
+
+#[cfg(feature = "blake3")]
+impl MerkleHasher for Blake3Hasher {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(left.as_slice());
+        hasher.update(right.as_slice());
+        B256::from(*hasher.finalize().as_bytes())
+    }
+
+    fn hash_children(children: &[B256]) -> B256 {
+        Self::hash_bytes(
+            &children
+                .iter()
+                .map(|child| child.as_slice())
+                .collect::<alloc::vec::Vec<_>>(),
+        )
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl DigestHasher for Blake3Hasher {
+    fn hash_bytes(parts: &[&[u8]]) -> B256 {
+        let mut hasher = blake3::Hasher::new();
+        parts.iter().for_each(|part| {
+            hasher.update(part);
+        });
+        B256::from(*hasher.finalize().as_bytes())
+    }
+}
+
+/// [PoseidonHasher] hashes two children with the circom-compatible Poseidon permutation over the
+/// BN254 scalar field, matching the incremental trees of Semaphore and circomlib.
+///
+/// Nodes are read as big-endian integers and reduced modulo the field order, so the tree is only
+/// compatible with circuits when every leaf is already a canonical field element.
+#[cfg(feature = "poseidon")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoseidonHasher;
+
+#[cfg(feature = "poseidon")]
+impl MerkleHasher for PoseidonHasher {
+    fn hash_pair(left: &B256, right: &B256) -> B256 {
+        Self::hash_children(&[*left, *right])
+    }
+
+    /// Hash up to twelve children with the circom Poseidon permutation of that width, matching
+    /// circomlib's incremental quin tree for five children.
+    ///
+    /// # Panics
+    /// If there are no children or more than twelve.
+    fn hash_children(children: &[B256]) -> B256 {
+        use ark_bn254::Fr;
+        use ark_ff::{BigInteger, PrimeField};
+        use light_poseidon::{Poseidon, PoseidonHasher as _};
+
+        let inputs: alloc::vec::Vec<Fr> = children
+            .iter()
+            .map(|child| Fr::from_be_bytes_mod_order(child.as_slice()))
+            .collect();
+        let hash = Poseidon::<Fr>::new_circom(inputs.len())
+            .and_then(|mut poseidon| poseidon.hash(&inputs))
+            .expect("circom poseidon parameters exist for up to twelve inputs");
+        B256::from_slice(&hash.into_bigint().to_bytes_be())
+    }
+}
+
+/// The zero hashes of [Keccak256Hasher] for heights `0..64`, so that creating a keccak tree hashes
+/// nothing. Entry `i` is the root of an empty subtree of height `i`, the keccak hash of two copies of
+/// entry `i - 1`. The table is checked into the source rather than generated at build time; a test
+/// checks every entry against that hash chain.
+pub const KECCAK256_ZERO_HASHES: [B256; 64] = [
+    b256!("0000000000000000000000000000000000000000000000000000000000000000"),
+    b256!("ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"),
+    b256!("b4c11951957c6f8f642c4af61cd6b24640fec6dc7fc607ee8206a99e92410d30"),
+    b256!("21ddb9a356815c3fac1026b6dec5df3124afbadb485c9ba5a3e3398a04b7ba85"),
+    b256!("e58769b32a1beaf1ea27375a44095a0d1fb664ce2dd358e7fcbfb78c26a19344"),
+    b256!("0eb01ebfc9ed27500cd4dfc979272d1f0913cc9f66540d7e8005811109e1cf2d"),
+    b256!("887c22bd8750d34016ac3c66b5ff102dacdd73f6b014e710b51e8022af9a1968"),
+    b256!("ffd70157e48063fc33c97a050f7f640233bf646cc98d9524c6b92bcf3ab56f83"),
+    b256!("9867cc5f7f196b93bae1e27e6320742445d290f2263827498b54fec539f756af"),
+    b256!("cefad4e508c098b9a7e1d8feb19955fb02ba9675585078710969d3440f5054e0"),
+    b256!("f9dc3e7fe016e050eff260334f18a5d4fe391d82092319f5964f2e2eb7c1c3a5"),
+    b256!("f8b13a49e282f609c317a833fb8d976d11517c571d1221a265d25af778ecf892"),
+    b256!("3490c6ceeb450aecdc82e28293031d10c7d73bf85e57bf041a97360aa2c5d99c"),
+    b256!("c1df82d9c4b87413eae2ef048f94b4d3554cea73d92b0f7af96e0271c691e2bb"),
+    b256!("5c67add7c6caf302256adedf7ab114da0acfe870d449a3a489f781d659e8becc"),
+    b256!("da7bce9f4e8618b6bd2f4132ce798cdc7a60e7e1460a7299e3c6342a579626d2"),
+    b256!("2733e50f526ec2fa19a22b31e8ed50f23cd1fdf94c9154ed3a7609a2f1ff981f"),
+    b256!("e1d3b5c807b281e4683cc6d6315cf95b9ade8641defcb32372f1c126e398ef7a"),
+    b256!("5a2dce0a8a7f68bb74560f8f71837c2c2ebbcbf7fffb42ae1896f13f7c7479a0"),
+    b256!("b46a28b6f55540f89444f63de0378e3d121be09e06cc9ded1c20e65876d36aa0"),
+    b256!("c65e9645644786b620e2dd2ad648ddfcbf4a7e5b1a3a4ecfe7f64667a3f0b7e2"),
+    b256!("f4418588ed35a2458cffeb39b93d26f18d2ab13bdce6aee58e7b99359ec2dfd9"),
+    b256!("5a9c16dc00d6ef18b7933a6f8dc65ccb55667138776f7dea101070dc8796e377"),
+    b256!("4df84f40ae0c8229d0d6069e5c8f39a7c299677a09d367fc7b05e3bc380ee652"),
+    b256!("cdc72595f74c7b1043d0e1ffbab734648c838dfb0527d971b602bc216c9619ef"),
+    b256!("0abf5ac974a1ed57f4050aa510dd9c74f508277b39d7973bb2dfccc5eeb0618d"),
+    b256!("b8cd74046ff337f0a7bf2c8e03e10f642c1886798d71806ab1e888d9e5ee87d0"),
+    b256!("838c5655cb21c6cb83313b5a631175dff4963772cce9108188b34ac87c81c41e"),
+    b256!("662ee4dd2dd7b2bc707961b1e646c4047669dcb6584f0d8d770daf5d7e7deb2e"),
+    b256!("388ab20e2573d171a88108e79d820e98f26c0b84aa8b2f4aa4968dbb818ea322"),
+    b256!("93237c50ba75ee485f4c22adf2f741400bdf8d6a9cc7df7ecae576221665d735"),
+    b256!("8448818bb4ae4562849e949e17ac16e0be16688e156b5cf15e098c627c0056a9"),
+    b256!("27ae5ba08d7291c96c8cbddcc148bf48a6d68c7974b94356f53754ef6171d757"),
+    b256!("bf558bebd2ceec7f3c5dce04a4782f88c2c6036ae78ee206d0bc5289d20461a2"),
+    b256!("e21908c2968c0699040a6fd866a577a99a9d2ec88745c815fd4a472c789244da"),
+    b256!("ae824d72ddc272aab68a8c3022e36f10454437c1886f3ff9927b64f232df414f"),
+    b256!("27e429a4bef3083bc31a671d046ea5c1f5b8c3094d72868d9dfdc12c7334ac5f"),
+    b256!("743cc5c365a9a6a15c1f240ac25880c7a9d1de290696cb766074a1d83d927816"),
+    b256!("4adcf616c3bfabf63999a01966c998b7bb572774035a63ead49da73b5987f347"),
+    b256!("75786645d0c5dd7c04a2f8a75dcae085213652f5bce3ea8b9b9bedd1cab3c5e9"),
+    b256!("b88b152c9b8a7b79637d35911848b0c41e7cc7cca2ab4fe9a15f9c38bb4bb939"),
+    b256!("0c4e2d8ce834ffd7a6cd85d7113d4521abb857774845c4291e6f6d010d97e318"),
+    b256!("5bc799d83e3bb31501b3da786680df30fbc18eb41cbce611e8c0e9c72f69571c"),
+    b256!("a10d3ef857d04d9c03ead7c6317d797a090fa1271ad9c7addfbcb412e9643d4f"),
+    b256!("b33b1809c42623f474055fa9400a2027a7a885c8dfa4efe20666b4ee27d7529c"),
+    b256!("134d7f28d53f175f6bf4b62faa2110d5b76f0f770c15e628181c1fcc18f970a9"),
+    b256!("c34d24b2fc8c50ca9c07a7156ef4e5ff4bdf002eda0b11c1d359d0b59a546807"),
+    b256!("04dbb9db631457879b27e0dfdbe50158fd9cf9b4cf77605c4ac4c95bd65fc9f6"),
+    b256!("f9295a686647cb999090819cda700820c282c613cedcd218540bbc6f37b01c65"),
+    b256!("67c4a1ea624f092a3a5cca2d6f0f0db231972fce627f0ecca0dee60f17551c5f"),
+    b256!("8fdaeb5ab560b2ceb781cdb339361a0fbee1b9dffad59115138c8d6a70dda9cc"),
+    b256!("c1bf0bbdd7fee15764845db875f6432559ff8dbc9055324431bc34e5b93d15da"),
+    b256!("307317849eccd90c0c7b98870b9317c15a5959dcfb84c76dcc908c4fe6ba9212"),
+    b256!("6339bf06e458f6646df5e83ba7c3d35bc263b3222c8e9040068847749ca8e8f9"),
+    b256!("5045e4342aeb521eb3a5587ec268ed3aa6faf32b62b0bc41a9d549521f406fc3"),
+    b256!("08601d83cdd34b5f7b8df63e7b9a16519d35473d0b89c317beed3d3d9424b253"),
+    b256!("84e35c5d92171376cae5c86300822d729cd3a8479583bef09527027dba5f1126"),
+    b256!("3c5cbbeb3834b7a5c1cba9aa5fee0c95ec3f17a33ec3d8047fff799187f5ae20"),
+    b256!("40bbe913c226c34c9fbe4389dd728984257a816892b3cae3e43191dd291f0eb5"),
+    b256!("14af5385bcbb1e4738bbae8106046e6e2fca42875aa5c000c582587742bcc748"),
+    b256!("72f29656803c2f4be177b1b8dd2a5137892b080b022100fde4e96d93ef8c96ff"),
+    b256!("d06f27061c734d7825b46865d00aa900e5cc3a3672080e527171e1171aa5038a"),
+    b256!("28203985b5f2d87709171678169739f957d2745f4bfa5cc91e2b4bd9bf483b40"),
+    b256!("0a162946e56158bac0673e6dd3bdfdc1e4a0e7744a120fdb640050c8d7abe1c6"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keccak_zero_hashes_are_the_hash_chain() {
+        assert_eq!(KECCAK256_ZERO_HASHES[0], B256::ZERO);
+        for height in 1..64 {
+            let below = KECCAK256_ZERO_HASHES[height - 1];
+            assert_eq!(
+                KECCAK256_ZERO_HASHES[height],
+                alloy_primitives::keccak256([below.as_slice(), below.as_slice()].concat()),
+                "zero hash at height {height}"
+            );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! A four-lane `keccak256` of 64-byte messages, for hashing the sibling pairs of a whole tree level
+//! at once. Every step of the permutation applies the same operation to the matching word of four
+//! independent states, which the compiler lowers to SIMD instructions where the target has them.
+//! With 256-bit vectors, e.g. AVX2 through `-C target-cpu=native`, a level hashes two to three times
+//! as fast as one message at a time. Without them the lanes run one after the other, which is slower
+//! than the scalar permutation, so [Keccak256Hasher](super::Keccak256Hasher) only uses it on AVX2
+//! targets. `cargo bench --bench hash_pairs` compares the two.
+
+use alloy_primitives::B256;
+
+/// The number of messages hashed at once
+pub(super) const LANES: usize = 4;
+
+/// One word of the keccak state, for each lane
+type Word = [u64; LANES];
+
+/// The round constants of `keccak-f[1600]`
+const RC: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// The rotation offsets of the rho step, in the order the pi step visits the words
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// The order in which the pi step moves the words
+const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Returns `keccak256(left || right)` of every pair of `children`, as
+/// [Keccak256Hasher::hash_pair](super::Keccak256Hasher::hash_pair) does.
+pub(super) fn hash_pairs(children: &[[B256; 2]; LANES]) -> [B256; LANES] {
+    let mut state = [[0u64; LANES]; 25];
+    for (lane, [left, right]) in children.iter().enumerate() {
+        for (word, bytes) in left
+            .chunks_exact(8)
+            .chain(right.chunks_exact(8))
+            .enumerate()
+        {
+            state[word][lane] = u64::from_le_bytes(bytes.try_into().expect("8-byte chunk"));
+        }
+        // The message fills 64 of the 136 bytes of the rate, followed by the keccak padding.
+        state[8][lane] = 0x01;
+        state[16][lane] = 0x80 << 56;
+    }
+    keccak_f(&mut state);
+
+    core::array::from_fn(|lane| {
+        let mut hash = B256::ZERO;
+        for (word, bytes) in hash.chunks_exact_mut(8).enumerate() {
+            bytes.copy_from_slice(&state[word][lane].to_le_bytes());
+        }
+        hash
+    })
+}
+
+/// Apply `keccak-f[1600]` to every lane of `state`.
+fn keccak_f(state: &mut [Word; 25]) {
+    for round_constant in RC {
+        // Theta
+        let mut columns = [[0u64; LANES]; 5];
+        for x in 0..5 {
+            for y in 0..5 {
+                columns[x] = xor(columns[x], state[5 * y + x]);
+            }
+        }
+        for x in 0..5 {
+            let mix = xor(columns[(x + 4) % 5], rotate(columns[(x + 1) % 5], 1));
+            for y in 0..5 {
+                state[5 * y + x] = xor(state[5 * y + x], mix);
+            }
+        }
+
+        // Rho and pi
+        let mut last = state[1];
+        for (&position, &offset) in PI.iter().zip(&RHO) {
+            let next = state[position];
+            state[position] = rotate(last, offset);
+            last = next;
+        }
+
+        // Chi
+        for y in 0..5 {
+            let row: [Word; 5] = core::array::from_fn(|x| state[5 * y + x]);
+            for x in 0..5 {
+                state[5 * y + x] = core::array::from_fn(|lane| {
+                    row[x][lane] ^ (!row[(x + 1) % 5][lane] & row[(x + 2) % 5][lane])
+                });
+            }
+        }
+
+        // Iota
+        for word in &mut state[0] {
+            *word ^= round_constant;
+        }
+    }
+}
+
+/// Returns the lane-wise xor of `a` and `b`.
+#[inline(always)]
+fn xor(a: Word, b: Word) -> Word {
+    core::array::from_fn(|lane| a[lane] ^ b[lane])
+}
+
+/// Returns every lane of `word` rotated left by `offset` bits.
+#[inline(always)]
+fn rotate(word: Word, offset: u32) -> Word {
+    word.map(|lane| lane.rotate_left(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloy_primitives::keccak256;
+    use proptest::prelude::*;
+
+    fn pair() -> impl Strategy<Value = [B256; 2]> {
+        any::<[[u8; 32]; 2]>().prop_map(|[left, right]| [left.into(), right.into()])
+    }
+
+    proptest! {
+        #[test]
+        fn lanes_match_keccak256(children in proptest::array::uniform4(pair())) {
+            let hashes = hash_pairs(&children);
+            for ([left, right], hash) in children.iter().zip(hashes) {
+                prop_assert_eq!(hash, keccak256([left.as_slice(), right.as_slice()].concat()));
+            }
+        }
+    }
+
+    #[test]
+    fn lanes_match_keccak256_at_the_edges() {
+        for children in [
+            [[B256::ZERO; 2]; LANES],
+            [[B256::repeat_byte(0xff); 2]; LANES],
+            [
+                [B256::ZERO, B256::repeat_byte(0xff)],
+                [B256::repeat_byte(0xff), B256::ZERO],
+                [B256::with_last_byte(1), B256::ZERO],
+                [B256::ZERO, B256::with_last_byte(0x80)],
+            ],
+        ] {
+            for ([left, right], hash) in children.iter().zip(hash_pairs(&children)) {
+                assert_eq!(
+                    hash,
+                    keccak256([left.as_slice(), right.as_slice()].concat())
+                );
+            }
+        }
+    }
+}
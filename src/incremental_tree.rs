@@ -1,127 +1,2941 @@
-use alloc::{vec, vec::Vec};
-use alloy_primitives::{keccak256, B256};
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::String,
+    vec::Vec,
+};
+use alloy_primitives::B256;
+use core::{
+    fmt::{self, Write},
+    marker::PhantomData,
+    ops::Range,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
 
-#[derive(Debug)]
+use crate::{
+    append_log::AppendLog,
+    gindex::{gindex, parent, to_depth_index},
+    hasher::{DigestHasher, Keccak256Hasher, MerkleHasher},
+    leaf::{LeafDomain, LeafEncode},
+    observer::AppendObserver,
+    proof::{ArityMerkleProof, ConsistencyProof, MerkleProof, MultiProof, RangeProof},
+    store::{MemoryStore, TreeStore},
+};
+
+mod batch;
+pub(crate) mod levels;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(any(feature = "serde", feature = "borsh", feature = "bincode"))]
+pub(crate) mod state;
+#[cfg(test)]
+mod tests;
+
+pub use batch::BatchGuard;
+use levels::Levels;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IncrementalMerkleTreeError {
     ///  When tree is full and cannot add more leaves
-    TreeFull,
+    TreeFull {
+        /// The maximum number of leaves the tree can hold
+        capacity: usize,
+    },
     /// Not terminate at most height
     LoopDidNotTerminate,
     /// Index out of bound.
-    IndexOutOfBounds,
+    IndexOutOfBounds {
+        /// The offending index
+        index: usize,
+        /// The number of leaves the index had to be below
+        len: usize,
+    },
+    /// The tree's internal state violates one of its invariants
+    CorruptedState,
+    /// The leaf was appended before the tree was restored from a [Frontier], or as part of a subtree,
+    /// and is not stored
+    LeafUnavailable,
+    /// The value is already in the [IndexedMerkleTree](crate::IndexedMerkleTree), or the tree id
+    /// is already in the [Forest](crate::Forest)
+    ValueExists,
+    /// The value is not in the [IndexedMerkleTree](crate::IndexedMerkleTree), or the tree id is
+    /// not in the [Forest](crate::Forest)
+    ValueNotFound,
+    /// The [AppendLog](crate::AppendLog) could not durably record the leaf
+    LogWriteFailed,
+    /// The encoded data is malformed, or does not describe a value of the expected shape
+    InvalidEncoding,
+    /// The subtree does not start at a multiple of its own number of leaves, or the tree stores
+    /// leaves its root would hide
+    InvalidSubtree,
+    /// The namespace is below the namespace of the last leaf of the
+    /// [NamespacedMerkleTree](crate::NamespacedMerkleTree)
+    NamespaceOutOfOrder,
+}
+
+impl fmt::Display for IncrementalMerkleTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TreeFull { capacity } => {
+                write!(f, "the tree is full, it holds at most {capacity} leaves")
+            }
+            Self::LoopDidNotTerminate => f.write_str("the tree update did not terminate"),
+            Self::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} is out of bounds for {len} leaves")
+            }
+            Self::CorruptedState => f.write_str("the tree state violates one of its invariants"),
+            Self::LeafUnavailable => f.write_str("the leaf is not stored by the tree"),
+            Self::ValueExists => f.write_str("the value is already in the tree"),
+            Self::ValueNotFound => f.write_str("the value is not in the tree"),
+            Self::LogWriteFailed => f.write_str("the append log could not record the leaf"),
+            Self::InvalidEncoding => f.write_str("the encoded data is malformed"),
+            Self::InvalidSubtree => f.write_str("the subtree cannot be appended at the tree size"),
+            Self::NamespaceOutOfOrder => {
+                f.write_str("the namespace is below the namespace of the last leaf")
+            }
+        }
+    }
+}
+
+impl core::error::Error for IncrementalMerkleTreeError {}
+
+/// Selects how [IncrementalMerkleTree::root] commits to a tree whose size is not a power of two.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RootMode {
+    /// Pad the tree with zero hashes up to `HEIGHT`, as the deposit contract does.
+    #[default]
+    PaddedFixedHeight,
+    /// Bag the peaks of the complete subtrees directly, as a merkle mountain range does. See
+    /// [bag_peaks].
+    BaggedPeaks,
+}
+
+/// A compact snapshot of an [IncrementalMerkleTree]: the number of appended leaves and the active
+/// branch, which is all an on-chain incremental merkle tree stores. See
+/// [IncrementalMerkleTree::frontier] and [IncrementalMerkleTree::from_frontier].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frontier<const HEIGHT: usize> {
+    /// The number of leaves that have been added to the tree
+    pub size: usize,
+    /// The active branch of the tree. Only the entries at the set bits of `size` are meaningful.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub active_branch: [B256; HEIGHT],
 }
 
-/// [IncrementalMerketTree] is an append-only merkle tree of 
-/// generic height, using `keccak256` as the hash function
+/// A point an [IncrementalMerkleTree] can be rolled back to after further appends. See
+/// [IncrementalMerkleTree::checkpoint] and [IncrementalMerkleTree::rollback].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint<const HEIGHT: usize> {
+    /// The state of the tree when the checkpoint was taken
+    frontier: Frontier<HEIGHT>,
+}
+
+impl<const HEIGHT: usize> Checkpoint<HEIGHT> {
+    /// Returns the number of leaves the tree held when the checkpoint was taken.
+    pub fn size(&self) -> usize {
+        self.frontier.size
+    }
+}
+
+/// How a tree relates to another, as returned by [IncrementalMerkleTree::diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeDiff {
+    /// Both trees hold the same leaves.
+    Equal,
+    /// The other tree holds the same leaves, followed by the leaves at the indices in the range.
+    Appended(Range<usize>),
+    /// The other tree holds the leaves before the range only.
+    Truncated(Range<usize>),
+    /// The trees differ at the leaf at `first_leaf`, and hold the same leaves before it. If that
+    /// region is no longer stored because one of the trees was restored from a [Frontier], it is the
+    /// first leaf of the smallest subtree the trees are known to differ in.
+    Diverged {
+        /// The first leaf at which the trees differ
+        first_leaf: usize,
+    },
+}
+
+/// A node visited by [IncrementalMerkleTree::to_dot] and [IncrementalMerkleTree::to_json_nodes].
+struct DumpNode {
+    /// The generalized index of the node
+    gindex: usize,
+    /// The height of the node above the leaves
+    height: usize,
+    /// The hash of the node, or [B256::ZERO] if it is unavailable
+    hash: B256,
+    /// What the node stands for
+    kind: DumpKind,
+}
 
-pub struct IncrementalMerkleTree<const HEIGHT: usize>{
-    /// The zero hashes
-    zero_hashes: [B256; HEIGHT],
-    /// The active branch of the tree, used to calculate the root hash 
-    active_branch: [B256; HEIGHT], 
+/// What a [DumpNode] stands for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DumpKind {
+    /// A node covering appended leaves
+    Node,
+    /// A collapsed subtree holding no appended leaf
+    Zero,
+    /// A collapsed subtree that is not stored
+    Unavailable,
+}
+
+/// [IncrementalMerketTree] is an append-only merkle tree of
+/// generic height, using `keccak256` as the hash function by default. Any other [MerkleHasher] can be
+/// selected with the `H` parameter, and any other [TreeStore] for the intermediates cache with the `S`
+/// parameter.
+///
+/// `HEIGHT` must be below the bit width of `usize`, at most 63 on 64-bit targets, so that every node
+/// has a generalized index; taller trees fail to compile. [FrontierTree](crate::FrontierTree) has no
+/// such limit.
+///
+/// With the `tracing` feature, appends, cache rebuilds and proofs are instrumented with `tracing`
+/// spans, and their events carry `monotonic_counter.leaves_appended`, `monotonic_counter.cache_rebuilds`
+/// and `monotonic_counter.hashes` fields, which metrics layers such as `tracing-opentelemetry` export
+/// as counters.
+///
+/// `ARITY` selects the number of children of every internal node, two by default. Trees of any arity,
+/// such as circomlib's incremental quin tree for an arity of 5, hold up to `ARITY^HEIGHT - 1` leaves,
+/// combine children with [MerkleHasher::hash_children], and share the constructors, appends, batches,
+/// hash width, root history, stores and serialization of binary trees. Their leaves are proven with
+/// [Self::prove_arity]; the other proofs, frontiers, rollbacks, pruning and the remaining extensions
+/// are only implemented for binary trees. Arities below two, and shapes whose generalized indices do
+/// not fit in a `usize`, fail to compile.
+pub struct IncrementalMerkleTree<
+    const HEIGHT: usize,
+    H = Keccak256Hasher,
+    S = MemoryStore,
+    const ARITY: usize = 2,
+> {
+    /// The zero hashes of the heights below the root, shared with every other tree of the same hasher
+    /// and hash width when the `std` feature is enabled
+    zero_hashes: Cow<'static, [B256]>,
+    /// The active branch of the tree, used to calculate the root hash. Entry `i` is the last complete
+    /// node at height `i` while the parent of that node is incomplete.
+    active_branch: [B256; HEIGHT],
     /// The number of leaves that have been added to the tree
     size: usize,
-    /// The intermediate cache for the tree, indexed by `generalized_index + 1`. The intermediates are
-    /// only valid if `cache_valid` is true.
-    intermediates: Vec<B256>,
-    /// Signals whether the intermediate cache is valid. Cache Validation is global, and all levels above 
-    /// the leaves will be recomputed during proof generation if it is invalid.
-    cache_valid: bool,
+    /// The index of the first leaf whose contents are stored. Leaves below it were appended before the
+    /// tree was restored from a [Frontier], or as part of a subtree, and are only represented by the
+    /// peaks of that frontier or the subtree roots.
+    first_stored_leaf: usize,
+    /// The leaves of the subtrees appended with [Self::append_subtree] after the first stored leaf, in
+    /// ascending order. Their contents are not stored either, and each subtree is only represented by
+    /// its root.
+    unstored_subtrees: Vec<Range<usize>>,
+    /// The number of leading bytes kept from every internal node hash; the remaining bytes are zeroed.
+    /// `32` keeps the full hasher output.
+    hash_width: usize,
+    /// The intermediate cache for the tree, keyed by generalized index. Only the nodes above the first
+    /// `hashed_leaves` leaves are guaranteed to be up to date. Nodes whose subtree holds no appended
+    /// leaves are never written and stand for the zero hash of their height, so the cache only grows
+    /// with the number of appended leaves and tall trees stay cheap to create.
+    intermediates: S,
+    /// The number of leading leaves whose ancestors in the intermediate cache are up to date. While
+    /// it equals `size`, `append` keeps it there by rehashing only the path above each new leaf.
+    /// Otherwise proof generation rehashes only the nodes above the leaves from `hashed_leaves` on.
+    hashed_leaves: usize,
+    /// The roots recorded after the most recent appends, oldest first. The last entry is the root at
+    /// the current `size`.
+    root_history: VecDeque<B256>,
+    /// The maximum number of roots retained in `root_history`. `0` disables recording.
+    history_capacity: usize,
+    /// How `root` folds the active branch into a single hash.
+    root_mode: RootMode,
+    /// The current siblings of every watched leaf, keyed by leaf index and kept up to date on every
+    /// append. See [Self::watch].
+    witnesses: BTreeMap<usize, [B256; HEIGHT]>,
+    /// The root computed by the last call to `root` since the tree last changed
+    root_memo: RootMemo,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
 }
 
-impl<const HEIGHT: usize> Default for IncrementalMerkleTree<HEIGHT> {
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize> Default
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<const HEIGHT: usize> IncrementalMerkleTree<HEIGHT> {
+impl<const HEIGHT: usize, H, S: Clone, const ARITY: usize> Clone
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn clone(&self) -> Self {
+        Self {
+            zero_hashes: self.zero_hashes.clone(),
+            active_branch: self.active_branch,
+            size: self.size,
+            first_stored_leaf: self.first_stored_leaf,
+            unstored_subtrees: self.unstored_subtrees.clone(),
+            hash_width: self.hash_width,
+            intermediates: self.intermediates.clone(),
+            hashed_leaves: self.hashed_leaves,
+            root_history: self.root_history.clone(),
+            history_capacity: self.history_capacity,
+            root_mode: self.root_mode,
+            witnesses: self.witnesses.clone(),
+            root_memo: self.root_memo.clone(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
+/// The memoized root of an [IncrementalMerkleTree] or a
+/// [DynIncrementalMerkleTree](crate::DynIncrementalMerkleTree). `root` fills it through a shared
+/// reference, so the root is kept in atomic words to keep the tree `Sync`: concurrent readers of an
+/// unchanged tree all write the same root, and publish it by setting `valid` last. Every change to
+/// the tree goes through a mutable reference and clears it.
+#[derive(Default)]
+pub(crate) struct RootMemo {
+    /// Whether `words` hold the current root
+    valid: AtomicBool,
+    /// The bytes of the root, four at a time
+    words: [AtomicU32; 8],
+}
+
+impl RootMemo {
+    /// Returns the memoized root, if any.
+    pub(crate) fn get(&self) -> Option<B256> {
+        if !self.valid.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut root = B256::ZERO;
+        for (bytes, word) in root.chunks_exact_mut(4).zip(&self.words) {
+            bytes.copy_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+        }
+        Some(root)
+    }
+
+    /// Memoize `root`, which must be the root of the tree in its current state.
+    pub(crate) fn set(&self, root: B256) {
+        for (bytes, word) in root.chunks_exact(4).zip(&self.words) {
+            let bytes = bytes.try_into().expect("chunks of four bytes");
+            word.store(u32::from_le_bytes(bytes), Ordering::Relaxed);
+        }
+        self.valid.store(true, Ordering::Release);
+    }
+
+    /// Forget the memoized root.
+    pub(crate) fn clear(&mut self) {
+        *self.valid.get_mut() = false;
+    }
+}
+
+impl Clone for RootMemo {
+    fn clone(&self) -> Self {
+        let memo = Self::default();
+        if let Some(root) = self.get() {
+            memo.set(root);
+        }
+        memo
+    }
+}
+
+/// Two trees are equal when they hold the same number of leaves and the same active branch, and hence
+/// commit to the same root. Trees of a higher arity keep further complete nodes in the cache, and are
+/// equal when they hold the same number of leaves under the same root. The intermediates cache, the
+/// root history and the watched leaves are not compared.
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> PartialEq
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn eq(&self, other: &Self) -> bool {
+        if ARITY != 2 {
+            return self.size == other.size && self.root() == other.root();
+        }
+        self.size == other.size
+            && (0..HEIGHT)
+                .filter(|height| (self.size >> height) & 1 == 1)
+                .all(|height| self.active_branch[height] == other.active_branch[height])
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> Eq
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+}
+
+/// Only the state that defines the tree is printed, never the intermediates cache: the size,
+/// capacity and root of the tree and its active branch, with every hash shortened to its first and
+/// last two bytes.
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> fmt::Debug
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncrementalMerkleTree")
+            .field("size", &self.size)
+            .field("capacity", &self.capacity())
+            .field("root", &ShortHex(self.root()))
+            .field(
+                "active_branch",
+                &self.active_branch.map(ShortHex).as_slice(),
+            )
+            .field("hash_width", &self.hash_width)
+            .field("root_mode", &self.root_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats the height, size and root of the tree, such as `tree of height 32 holding 3 of
+/// 4294967295 leaves with root 0x…`. The alternate flag shortens the root like `{:#}` does for a
+/// [B256].
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> fmt::Display
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tree of height {HEIGHT} holding {} of {} leaves with root ",
+            self.size,
+            self.capacity()
+        )?;
+        fmt::Display::fmt(&self.root(), f)
+    }
+}
+
+/// Debug-formats a hash as its first and last two bytes in hex.
+struct ShortHex(B256);
+
+impl fmt::Debug for ShortHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    TryFrom<&[B256]> for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    type Error = IncrementalMerkleTreeError;
+
+    fn try_from(leaves: &[B256]) -> Result<Self, Self::Error> {
+        Self::from_leaves(leaves)
+    }
+}
+
+impl<'a, const HEIGHT: usize, H, S: TreeStore, const ARITY: usize> IntoIterator
+    for &'a IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    type Item = B256;
+    type IntoIter = Leaves<'a, HEIGHT, H, S, ARITY>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Leaves {
+            tree: self,
+            range: self.first_stored_leaf..self.size,
+        }
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> Extend<B256>
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Append every leaf yielded by `iter` as a single [IncrementalMerkleTree::append_batch].
+    ///
+    /// # Panics
+    /// If the leaves do not all fit in the tree.
+    fn extend<I: IntoIterator<Item = B256>>(&mut self, iter: I) {
+        let leaves: Vec<B256> = iter.into_iter().collect();
+        self.append_batch(&leaves)
+            .expect("extended an incremental merkle tree past its capacity");
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    TryFrom<Vec<B256>> for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    type Error = IncrementalMerkleTreeError;
+
+    fn try_from(leaves: Vec<B256>) -> Result<Self, Self::Error> {
+        Self::from_leaves(&leaves)
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    FromIterator<B256> for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Create a tree containing every leaf yielded by `iter`, in order, as
+    /// [IncrementalMerkleTree::from_leaves] does. Use [TryFrom] on the collected leaves to handle
+    /// an overflow instead.
+    ///
+    /// # Panics
+    /// If the leaves do not all fit in the tree.
+    fn from_iter<I: IntoIterator<Item = B256>>(iter: I) -> Self {
+        let leaves: Vec<B256> = iter.into_iter().collect();
+        Self::from_leaves(&leaves).expect("collected more leaves than the tree can hold")
+    }
+}
+
+/// An iterator over the leaves of an [IncrementalMerkleTree], in the order they were appended. Leaves
+/// appended before the tree was restored from a [Frontier] or as part of a subtree are not stored,
+/// and are skipped.
+pub struct Leaves<
+    'a,
+    const HEIGHT: usize,
+    H = Keccak256Hasher,
+    S = MemoryStore,
+    const ARITY: usize = 2,
+> {
+    /// The tree whose leaf row is being read
+    tree: &'a IncrementalMerkleTree<HEIGHT, H, S, ARITY>,
+    /// The leaf indices that have not been yielded yet
+    range: Range<usize>,
+}
+
+impl<const HEIGHT: usize, H, S: TreeStore, const ARITY: usize> Iterator
+    for Leaves<'_, HEIGHT, H, S, ARITY>
+{
+    type Item = B256;
+
+    fn next(&mut self) -> Option<B256> {
+        while let Some(subtree) = self.tree.unstored_subtree(self.range.start) {
+            self.range.start = subtree.end;
+        }
+        self.range.next().map(|index| self.tree.stored_leaf(index))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<B256> {
+        if !self.tree.unstored_subtrees.is_empty() {
+            for _ in 0..n {
+                self.next()?;
+            }
+            return self.next();
+        }
+        self.range.nth(n).map(|index| self.tree.stored_leaf(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.range.len() - self.tree.subtree_leaves_in(&self.range);
+        (len, Some(len))
+    }
+}
+
+impl<const HEIGHT: usize, H, S: TreeStore, const ARITY: usize> DoubleEndedIterator
+    for Leaves<'_, HEIGHT, H, S, ARITY>
+{
+    fn next_back(&mut self) -> Option<B256> {
+        while let Some(subtree) = self
+            .range
+            .end
+            .checked_sub(1)
+            .and_then(|index| self.tree.unstored_subtree(index))
+        {
+            self.range.end = subtree.start;
+        }
+        self.range
+            .next_back()
+            .map(|index| self.tree.stored_leaf(index))
+    }
+}
+
+impl<const HEIGHT: usize, H, S: TreeStore, const ARITY: usize> ExactSizeIterator
+    for Leaves<'_, HEIGHT, H, S, ARITY>
+{
+}
+
+impl<const HEIGHT: usize, H, S: TreeStore, const ARITY: usize>
+    IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Returns the generalized index of the node at `height` and horizontal `position`. The root is
+    /// `1`, and the children of node `i` are `ARITY * (i - 1) + 2` to `ARITY * i + 1`, which are `2i`
+    /// and `2i + 1` in a binary tree.
+    fn node_index(height: usize, position: usize) -> usize {
+        levels::node_index::<ARITY>(HEIGHT, height, position)
+    }
+
+    /// Borrow the levels of the tree for reading.
+    fn levels(&self) -> Levels<'_, H, &S, ARITY> {
+        Levels::new(
+            HEIGHT,
+            self.size,
+            self.hash_width,
+            self.first_stored_leaf,
+            &self.unstored_subtrees,
+            &self.zero_hashes,
+            &self.intermediates,
+        )
+    }
+
+    /// Returns the stored leaf at `index`, which must have been appended.
+    fn stored_leaf(&self, index: usize) -> B256 {
+        self.levels().node_at(0, index)
+    }
+
+    /// Returns `true` if the subtree at `height` and `position` only covers leaves whose contents are
+    /// not stored, because they were appended before the first stored leaf or as part of a subtree.
+    fn is_unstored(&self, height: usize, position: usize) -> bool {
+        self.levels().is_unstored(height, position)
+    }
+
+    /// Returns `true` if the frontier of the tree at `size` leaves is stored, so the tree can be read
+    /// as it was then: `size` is neither below the first stored leaf nor inside an unstored subtree.
+    fn is_stored_size(&self, size: usize) -> bool {
+        let subtree = self
+            .unstored_subtrees
+            .partition_point(|subtree| subtree.end <= size);
+        size >= self.first_stored_leaf
+            && self
+                .unstored_subtrees
+                .get(subtree)
+                .is_none_or(|subtree| subtree.start >= size)
+    }
+
+    /// Returns the leaves of the unstored subtree appended after the first stored leaf that holds the
+    /// leaf at `index`, if any.
+    fn unstored_subtree(&self, index: usize) -> Option<Range<usize>> {
+        let subtree = self
+            .unstored_subtrees
+            .partition_point(|subtree| subtree.end <= index);
+        self.unstored_subtrees
+            .get(subtree)
+            .filter(|subtree| subtree.start <= index)
+            .cloned()
+    }
+
+    /// Returns the number of leaves in `range` that were appended as part of an unstored subtree
+    /// after the first stored leaf.
+    fn subtree_leaves_in(&self, range: &Range<usize>) -> usize {
+        self.unstored_subtrees
+            .iter()
+            .map(|subtree| {
+                let start = subtree.start.max(range.start);
+                subtree.end.min(range.end).saturating_sub(start)
+            })
+            .sum()
+    }
+
+    /// Move the tree onto `store`, returning the tree and its previous store. The nodes are not
+    /// copied, so `store` must already hold the nodes the tree wrote.
+    pub(crate) fn replace_store<T: TreeStore>(
+        self,
+        store: T,
+    ) -> (IncrementalMerkleTree<HEIGHT, H, T, ARITY>, S) {
+        let tree = IncrementalMerkleTree {
+            zero_hashes: self.zero_hashes,
+            active_branch: self.active_branch,
+            size: self.size,
+            first_stored_leaf: self.first_stored_leaf,
+            unstored_subtrees: self.unstored_subtrees,
+            hash_width: self.hash_width,
+            intermediates: store,
+            hashed_leaves: self.hashed_leaves,
+            root_history: self.root_history,
+            history_capacity: self.history_capacity,
+            root_mode: self.root_mode,
+            witnesses: self.witnesses,
+            root_memo: self.root_memo,
+            _hasher: PhantomData,
+        };
+        (tree, self.intermediates)
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
     /// Create a new [IncrementalMerkleTree] with a height of `height`. This function precompute the zero hashes
     /// for the tree
     pub fn new() -> Self {
-        let mut zero_hashes = [B256::default(); HEIGHT];
-        let mut hash_buf = [0u8; 64];
-        (1..HEIGHT).for_each(|height| {
-            /// copy the first32 bytes of data from `zero_hashes[height-1]` into the first 32 bytes of hash_buf
-            /// it is concatinating and generating parent node
-            hash_buf[..32].copy_from_slice(zero_hashes[height-1].as_slice());
-            /// copy the entire content of `zero_hashes[height-1]` into the second half of 
-            /// `hash_buf`, starting from the index 32
-            hash_buf[32..].copy_from_slice(zero_hashes[height-1].as_slice());
-            /// it  calculates a new hash using `keccak256` and assinge to zero_hashes[height]
-            zero_hashes[height] = keccak256(hash_buf);
-        });
-        // assigned the default value for each element of vector
-        // convert the HEIGHT as u32-bit integer
-        // `(1 << (HEIGHT as u32 +1)` converts teh variable `HEIGHT` to an unsigned 32-bit integer
-        // and subtract the 1 from this result gives the final size of the vector
-        let intermediates = vec![B256::default(); (1 << (HEIGHT as u32 +1)) - 1];
+        Self::with_hash_width(32)
+    }
+
+    /// Create a new [IncrementalMerkleTree] whose internal node hashes are truncated to their first
+    /// `bytes` bytes, with the remaining bytes of each [B256] set to zero.
+    ///
+    /// # Security
+    /// Truncating to `n` bytes leaves only `8n / 2` bits of collision resistance: a 16-byte width
+    /// offers 64 bits, which is within reach of a well-funded attacker. Only use a reduced width
+    /// when the commitment does not need to resist adversarially chosen leaves.
+    ///
+    /// # Panics
+    /// If `bytes` is zero or greater than 32.
+    pub fn with_hash_width(bytes: usize) -> Self {
+        Self::from_store(S::default(), bytes)
+    }
+
+    /// Create a new [IncrementalMerkleTree] containing `leaves`, in order. The whole intermediates
+    /// cache is built bottom-up in a single pass, hashing every node once, and the active branch is
+    /// read back from it, so the tree is ready to prove any leaf.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` with every leaf appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if `leaves` does not fit in the tree.
+    pub fn from_leaves(leaves: &[B256]) -> Result<Self, IncrementalMerkleTreeError> {
+        if leaves.len() > Self::CAPACITY {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: Self::CAPACITY,
+            });
+        }
+
+        let mut tree = Self::new();
+        tree.append_batch(leaves)?;
+        Ok(tree)
+    }
+
+    /// Create a new [IncrementalMerkleTree] containing `leaves`, in order. Equivalent to
+    /// [Self::from_leaves].
+    pub fn from_slice(leaves: &[B256]) -> Result<Self, IncrementalMerkleTreeError> {
+        Self::from_leaves(leaves)
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default>
+    IncrementalMerkleTree<HEIGHT, H, S>
+{
+    /// Restore a tree from a [Frontier], without replaying the leaves it was built from. The restored
+    /// tree has the same root and accepts further appends exactly like the original, but only the
+    /// leaves appended after the restore can be proven.
+    ///
+    /// The frontier is assumed to come from a tree with the default hash width of 32 bytes.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` resuming from `frontier`.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if `frontier.size` exceeds the capacity
+    ///   of the tree.
+    pub fn from_frontier(frontier: Frontier<HEIGHT>) -> Result<Self, IncrementalMerkleTreeError> {
+        let mut tree = Self::new();
+        tree.restore_frontier(frontier)?;
+        Ok(tree)
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize>
+    IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Create a new, empty [IncrementalMerkleTree] whose intermediates cache is kept in `store`, which
+    /// must not hold any nodes yet.
+    pub fn with_store(store: S) -> Self {
+        Self::from_store(store, 32)
+    }
+
+    /// Rejects, when the tree type is instantiated, heights whose leaves cannot be addressed: the leaf
+    /// row starts at generalized index `2^HEIGHT`, which must fit in a `usize`.
+    const SUPPORTED_HEIGHT: () = assert!(
+        HEIGHT < usize::BITS as usize,
+        "the tree height must be below the bit width of usize"
+    );
+
+    /// Rejects, when the tree type is instantiated, arities below two, and shapes whose last leaf has
+    /// a generalized index beyond `usize::MAX`.
+    const SUPPORTED_ARITY: () = assert!(
+        ARITY >= 2 && node_indices_fit(HEIGHT, ARITY),
+        "the tree arity must be at least two, and every node must have a generalized index"
+    );
+
+    /// The maximum number of leaves the tree can hold
+    const CAPACITY: usize = capacity_with_arity(HEIGHT, ARITY);
+
+    /// Create a new, empty tree with its cache in `store` and the given hash width.
+    ///
+    /// # Panics
+    /// If `bytes` is zero or greater than 32.
+    fn from_store(store: S, bytes: usize) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED_HEIGHT;
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED_ARITY;
+        assert!(
+            (1..=32).contains(&bytes),
+            "hash width must be between 1 and 32 bytes"
+        );
+        let zero_hashes = if ARITY == 2 {
+            zero_hashes::<H>(HEIGHT, bytes)
+        } else {
+            Cow::Owned(compute_arity_zero_hashes::<H>(HEIGHT, ARITY, bytes))
+        };
         Self {
-            zero_hashes, 
+            zero_hashes,
             active_branch: [B256::default(); HEIGHT],
-            size: 0, 
-            intermediates,
-            cache_valid:false,
+            size: 0,
+            first_stored_leaf: 0,
+            unstored_subtrees: Vec::new(),
+            hash_width: bytes,
+            intermediates: store,
+            hashed_leaves: 0,
+            root_history: VecDeque::new(),
+            history_capacity: 0,
+            root_mode: RootMode::default(),
+            witnesses: BTreeMap::new(),
+            root_memo: RootMemo::default(),
+            _hasher: PhantomData,
         }
     }
-    /// Compute the root hash of the tree from the active branch.
+
+    /// Returns the leaf at `index`, or `None` if no leaf has been appended there or it was appended
+    /// before the tree was restored from a [Frontier] or as part of a subtree.
+    pub fn leaf(&self, index: usize) -> Option<B256> {
+        (index < self.size && !self.is_unstored(0, index)).then(|| self.stored_leaf(index))
+    }
+
+    /// Returns an iterator over the stored leaves, in the order they were appended.
+    pub fn leaves(&self) -> Leaves<'_, HEIGHT, H, S, ARITY> {
+        self.into_iter()
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the height of the tree, `HEIGHT`.
+    pub fn height(&self) -> usize {
+        HEIGHT
+    }
+
+    /// Returns the maximum number of leaves the tree can hold, `ARITY^HEIGHT - 1`, which is
+    /// `2^HEIGHT - 1` for a binary tree.
+    pub fn capacity(&self) -> usize {
+        Self::CAPACITY
+    }
+
+    /// Returns the number of leaves that can still be appended before the tree is full.
+    pub fn remaining(&self) -> usize {
+        Self::CAPACITY - self.size
+    }
+
+    /// Returns `true` if the tree cannot hold any more leaves, so the next
+    /// [append](Self::append) would fail with [IncrementalMerkleTreeError::TreeFull].
+    pub fn is_full(&self) -> bool {
+        self.size >= Self::CAPACITY
+    }
+
+    /// Returns the number of leading bytes kept from every internal node hash.
+    pub fn hash_width(&self) -> usize {
+        self.hash_width
+    }
+
+    /// Compute the root hash of the tree from the active branch, according to the tree's [RootMode].
+    /// The root is memoized until the next change to the tree, so repeated calls in between cost no
+    /// hashing.
     ///
     /// # Returns
     /// - The root hash of the tree.
-    
     pub fn root(&self) -> B256 {
-    // Initialize variables for size and hash buffer
-    let mut size = self.size;
-    let mut hash_buf = [0u8; 64];
-    
-    // Iterate over the tree height and fold the results
-    (0..HEIGHT).fold(B256::default(), |tree_root, height| {
-        // Check if the current size is odd
-        if size & 1 == 1 {
-            // Copy active branch and tree root into hash buffer
-            hash_buf[..32].copy_from_slice(self.active_branch[height].as_slice());
-            hash_buf[32..].copy_from_slice(tree_root.as_slice());
+        debug_assert!(
+            self.size <= Self::CAPACITY,
+            "size exceeds the tree capacity"
+        );
+
+        if let Some(root) = self.root_memo.get() {
+            return root;
+        }
+        let root = match self.root_mode {
+            RootMode::PaddedFixedHeight => self.padded_root(),
+            RootMode::BaggedPeaks => {
+                bag_peaks_with_width::<H>(&self.active_peaks(), self.hash_width)
+            }
+        };
+        self.root_memo.set(root);
+        root
+    }
+
+    /// Returns the root of the tree as a `0x`-prefixed hex string.
+    pub fn display_root(&self) -> String {
+        format!("{}", self.root())
+    }
+
+    /// Returns the peaks of the tree from the active branch, from the tallest to the shortest.
+    fn active_peaks(&self) -> Vec<B256> {
+        (0..HEIGHT)
+            .rev()
+            .filter(|height| (self.size >> height) & 1 == 1)
+            .map(|height| self.active_branch[height])
+            .collect()
+    }
+
+    /// Fold the active branch with the zero hashes into the root of the tree padded to `HEIGHT`.
+    fn padded_root(&self) -> B256 {
+        self.levels().padded_root(&self.active_branch)
+    }
+
+    /// Bring every level of the intermediates cache above the leaves up to date with the leaf row. Only
+    /// the nodes covering a leaf whose path is stale are hashed, each exactly once, so this costs
+    /// `O(stale leaves + HEIGHT)` hashes and nothing if the cache is already up to date. In a binary
+    /// tree, the stale nodes of a level are hashed in one [MerkleHasher::hash_pairs] call.
+    pub fn recompute_intermediates(&mut self) {
+        if self.hashed_leaves == self.size {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "recompute_intermediates",
+            stale_leaves = self.size - self.hashed_leaves
+        )
+        .entered();
+        let hashed_leaves = self.hashed_leaves;
+        let hashes = self.levels_mut().0.recompute(hashed_leaves);
+        self.hashed_leaves = self.size;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            monotonic_counter.cache_rebuilds = 1u64,
+            monotonic_counter.hashes = hashes as u64,
+            "rebuilt the intermediates cache"
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = hashes;
+    }
+
+    /// Returns the retained root of the tree at the time it held `size` leaves, or `None` if that
+    /// root is not in the root history.
+    pub fn retained_root(&self, size: usize) -> Option<B256> {
+        let age = self.size.checked_sub(size)?;
+        let len = self.root_history.len();
+        (age < len).then(|| self.root_history[len - 1 - age])
+    }
+
+    /// Retain the last `capacity` roots of the tree, starting with the current one, so that proofs
+    /// against any of them can be accepted with [Self::is_known_root].
+    pub fn with_root_history(mut self, capacity: usize) -> Self {
+        self.reserve_history(capacity);
+        if self.root_history.is_empty() {
+            self.record_root();
+        }
+        self
+    }
+
+    /// Returns `true` if `root` is one of the roots retained in the root history.
+    pub fn is_known_root(&self, root: &B256) -> bool {
+        self.root_history.contains(root)
+    }
+
+    /// Returns the roots retained in the root history, from the oldest to the current one.
+    pub fn recent_roots(&self) -> impl DoubleEndedIterator<Item = B256> + ExactSizeIterator + '_ {
+        self.root_history.iter().copied()
+    }
+
+    /// Set the number of roots retained in the root history to `capacity`, dropping the oldest roots
+    /// if more than `capacity` are currently held. A capacity of `0` disables recording.
+    pub fn reserve_history(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        if self.root_history.len() > capacity {
+            self.root_history
+                .drain(..self.root_history.len() - capacity);
+            self.root_history.shrink_to(capacity);
         } else {
-            // Copy tree root and zero hashes into hash buffer
-            hash_buf[..32].copy_from_slice(tree_root.as_slice());
-            hash_buf[32..].copy_from_slice(self.zero_hashes[height].as_slice());
-        }
-        
-        // Right shift the size by 1
-        size >>= 1;
-        
-        // Calculate keccak256 hash of the buffer
-        keccak256(hash_buf)
-    })
-}
+            self.root_history
+                .reserve_exact(capacity - self.root_history.len());
+        }
+    }
 
-    pub fn append(&mut self, leaf: B256) -> Result<(), IncrementalMerkelTreeError> {
-        self.size += 1; 
-        let mut size = self.size; 
+    /// Drop every retained root and free the memory held by the root history. The tree itself and the
+    /// history capacity are unaffected, so recording resumes with the next append.
+    pub fn clear_history(&mut self) {
+        self.root_history.clear();
+        self.root_history.shrink_to_fit();
+    }
 
-        if size > ( 1 << HEIGHT ) -1 {
-            return ERR(IncrementalMerkelTreeError::TreeFull);
+    /// Append a new leaf to the tree. Any [LeafEncode] value is accepted, and appended as its leaf.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(index = self.size))
+    )]
+    pub fn append(&mut self, leaf: impl LeafEncode) -> Result<(), IncrementalMerkleTreeError> {
+        // Rehash the path above the new leaf so the cache stays up to date without a rebuild.
+        let rehash = self.hashed_leaves == self.size;
+        let height = self.push_leaf(leaf.leaf_hash())?;
+        if rehash {
+            self.update_path(self.size - 1);
+            self.hashed_leaves = self.size;
         }
-        let mut intermediate = leaf;
-        let mut hash_buf = [0u8; 64];
-        for height in 0..HEIGHT {
-            if size & 1 == 1 {
-                // Set the branch value at the current height to the intermediate hash and return.
-                self.active_branch[height] = intermediate;
+        self.record_root();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            monotonic_counter.leaves_appended = 1u64,
+            monotonic_counter.hashes = (height + if rehash { HEIGHT } else { 0 }) as u64,
+            "appended a leaf"
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = height;
+        Ok(())
+    }
 
-                // Add the leaf to the intermediates and invalidate the global cache.
-                self.intermediates[(1 << HEIGHT) + self.size - 2] = leaf;
-                self.cache_valid = false;
+    /// Append `leaf` to the leaf row and the frontier, writing the leaf and every complete subtree it
+    /// closes to the cache, without rehashing the nodes it shares with earlier leaves.
+    ///
+    /// # Returns
+    /// - `Ok(height)` with the height of the new peak of the frontier.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    fn push_leaf(&mut self, leaf: B256) -> Result<usize, IncrementalMerkleTreeError> {
+        // Check the capacity before touching any state, so a rejected leaf leaves the size, the
+        // active branch and the cache exactly as they were.
+        if self.size >= Self::CAPACITY {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: Self::CAPACITY,
+            });
+        }
+        self.update_witnesses(leaf);
+        self.size += 1;
+        self.root_memo.clear();
+        let (mut levels, active_branch) = self.levels_mut();
+        levels.push_leaf(active_branch, leaf)
+    }
 
-                return Ok(());
+    /// Append every leaf in `leaves` to the tree, in order.
+    ///
+    /// The leaves are written to the leaf row first, and then every stale intermediate node above them
+    /// is hashed exactly once, level by level, before the active branch is read back from the cache.
+    /// This avoids rehashing the shared upper levels once per leaf.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the tree
+    ///   is left unchanged.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(count = leaves.len()))
+    )]
+    pub fn append_batch(&mut self, leaves: &[B256]) -> Result<(), IncrementalMerkleTreeError> {
+        if leaves.len() > Self::CAPACITY - self.size {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: Self::CAPACITY,
+            });
+        }
+        if leaves.is_empty() {
+            return Ok(());
+        }
+
+        let old_size = self.size;
+        for (offset, leaf) in leaves.iter().enumerate() {
+            self.intermediates
+                .put(Self::node_index(0, old_size + offset), *leaf);
+        }
+        self.size += leaves.len();
+        self.root_memo.clear();
+
+        // Rehash every node whose subtree gained a leaf, from the leaves upwards.
+        self.recompute_intermediates();
+
+        self.read_active_branch();
+
+        self.refresh_witnesses();
+        self.record_roots_since(old_size);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            monotonic_counter.leaves_appended = leaves.len() as u64,
+            "appended a batch of leaves"
+        );
+        Ok(())
+    }
+
+    /// Read the active branch back from the intermediates cache, in which every node of it is a
+    /// complete subtree.
+    fn read_active_branch(&mut self) {
+        let (levels, active_branch) = self.levels_mut();
+        levels.read_active_branch(active_branch);
+    }
+
+    /// Append a complete subtree of a binary tree as [IncrementalMerkleTree::append_subtree] does.
+    fn push_subtree(
+        &mut self,
+        height: usize,
+        root: B256,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        if height >= HEIGHT || 1 << height > capacity(HEIGHT) - self.size {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        if self.size & ((1 << height) - 1) != 0 {
+            return Err(IncrementalMerkleTreeError::InvalidSubtree);
+        }
+        // Rehash stale nodes while their leaves are still stored.
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let position = self.size >> height;
+        self.intermediates
+            .put((1 << (HEIGHT - height)) + position, root);
+        if self.size == self.first_stored_leaf {
+            self.first_stored_leaf += 1 << height;
+        } else {
+            self.unstored_subtrees
+                .push(self.size..self.size + (1 << height));
+        }
+        self.size += 1 << height;
+        self.root_memo.clear();
+        self.hashed_leaves = self.size;
+
+        // Rehash the path above the subtree node, whose left siblings are the peaks of the frontier.
+        self.update_path_above(height, position);
+        self.read_active_branch();
+
+        self.refresh_witnesses();
+        self.record_root();
+        Ok(())
+    }
+
+    /// Generate an [ArityMerkleProof] for the leaf at `index`, holding the `ARITY - 1` siblings of the
+    /// node on its path at every level. Binary trees are also proven, with a fixed number of siblings,
+    /// by [IncrementalMerkleTree::prove]. Any stale nodes in the intermediates cache are rehashed
+    /// first.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the leaf at `index` is not stored.
+    pub fn prove_arity(
+        &mut self,
+        index: usize,
+    ) -> Result<ArityMerkleProof<HEIGHT, ARITY>, IncrementalMerkleTreeError> {
+        if index >= self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.size,
+            });
+        }
+        if self.is_unstored(0, index) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        self.recompute_intermediates();
+
+        let mut position = index;
+        let siblings = (0..HEIGHT)
+            .map(|height| {
+                let first = position - position % ARITY;
+                let siblings = (first..first + ARITY)
+                    .filter(|&sibling| sibling != position)
+                    .map(|sibling| self.node_at(height, sibling))
+                    .collect();
+                position /= ARITY;
+                siblings
+            })
+            .collect();
+        Ok(ArityMerkleProof {
+            leaf: self.stored_leaf(index),
+            index,
+            siblings,
+        })
+    }
+
+    /// Returns the siblings on the path from the leaf at `index` of a binary tree up to the root, read
+    /// from the intermediates cache.
+    pub(crate) fn siblings(
+        &self,
+        index: usize,
+    ) -> Result<[B256; HEIGHT], IncrementalMerkleTreeError> {
+        if index >= self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.size,
+            });
+        }
+        if self.is_unstored(0, index) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        let levels = self.levels();
+        Ok(core::array::from_fn(|height| levels.sibling(index, height)))
+    }
+
+    /// Resume an empty binary tree from `frontier`. Every peak of the frontier is written to the cache,
+    /// which is all that later appends and proofs of later leaves read from the subtrees before it.
+    fn restore_frontier(
+        &mut self,
+        frontier: Frontier<HEIGHT>,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        debug_assert!(self.size == 0, "restored a frontier into a non-empty tree");
+        if frontier.size > capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        self.size = frontier.size;
+        self.root_memo.clear();
+        self.first_stored_leaf = frontier.size;
+        self.hashed_leaves = frontier.size;
+        self.active_branch = frontier.active_branch;
+        for height in 0..HEIGHT {
+            if (self.size >> height) & 1 == 1 {
+                self.intermediates.put(
+                    (1 << (HEIGHT - height)) + (self.size >> height) - 1,
+                    self.active_branch[height],
+                );
             }
+        }
+        self.record_root();
+        Ok(())
+    }
 
-            hash_buf[..32].copy_from_slice(self.active_branch[height].as_slice());
-            hash_buf[32..].copy_from_slice(intermediate.as_slice());
-            intermediate = keccak256(hash_buf);
-            size >>= 1;
+    /// Push the current root onto the root history, evicting the oldest root once the history is at
+    /// capacity.
+    fn record_root(&mut self) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.root_history.len() == self.history_capacity {
+            self.root_history.pop_front();
+        }
+        let root = self.root();
+        self.root_history.push_back(root);
+    }
+
+    /// Push the roots at every size after `old_size`, up to the current size, onto the root history.
+    /// Only the roots that fit in the history are computed.
+    fn record_roots_since(&mut self, old_size: usize) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        let first = (old_size + 1).max((self.size + 1).saturating_sub(self.history_capacity));
+        for size in first..=self.size {
+            if self.root_history.len() == self.history_capacity {
+                self.root_history.pop_front();
+            }
+            let root = self.root_at_size(size);
+            self.root_history.push_back(root);
         }
+    }
 
-        Err(IncrementalMerkleTreeError::LoopDidNotTerminate)
+    /// Borrow the levels of the tree for writing, next to its active branch.
+    fn levels_mut(&mut self) -> (Levels<'_, H, &mut S, ARITY>, &mut [B256; HEIGHT]) {
+        let levels = Levels::new(
+            HEIGHT,
+            self.size,
+            self.hash_width,
+            self.first_stored_leaf,
+            &self.unstored_subtrees,
+            &self.zero_hashes,
+            &mut self.intermediates,
+        );
+        (levels, &mut self.active_branch)
+    }
+
+    /// Hash two sibling nodes into their parent at the tree's hash width.
+    fn hash(&self, left: &B256, right: &B256) -> B256 {
+        self.levels().hash(left, right)
     }
-}
\ No newline at end of file
+
+    /// Compute the root the tree had when it held `size` leaves, folding the complete subtrees of
+    /// that earlier frontier out of the intermediates cache.
+    fn root_at_size(&self, size: usize) -> B256 {
+        if ARITY == 2 && self.root_mode == RootMode::BaggedPeaks {
+            let peaks: Vec<B256> = (0..HEIGHT)
+                .rev()
+                .filter(|height| (size >> height) & 1 == 1)
+                .map(|height| self.node_at(height, (size >> height) - 1))
+                .collect();
+            return bag_peaks_with_width::<H>(&peaks, self.hash_width);
+        }
+        self.levels().root_at_size(size)
+    }
+
+    /// Returns the cached node at `height` and horizontal `position`, substituting the zero hash of
+    /// that height for nodes whose subtree holds no appended leaves.
+    fn node_at(&self, height: usize, position: usize) -> B256 {
+        self.levels().node_at(height, position)
+    }
+
+    /// Returns the node at `height` and horizontal `position` as it was when the tree held `size`
+    /// leaves.
+    fn node_at_size(&self, height: usize, position: usize, size: usize) -> B256 {
+        self.levels().node_at_size(height, position, size)
+    }
+
+    /// Update the siblings of every watched leaf for `leaf`, which is about to be appended at
+    /// `size`. The new leaf only changes the sibling at the height where its path joins the path of
+    /// a watched leaf, which is the node of its own path at that height. Its path is folded from the
+    /// active branch before the append, which holds the complete left siblings along it.
+    fn update_witnesses(&mut self, leaf: B256) {
+        let Some(&first) = self.witnesses.keys().next() else {
+            return;
+        };
+        let index = self.size;
+        let top = (usize::BITS - (first ^ index).leading_zeros()) as usize - 1;
+        let mut path = Vec::with_capacity(top + 1);
+        path.push(leaf);
+        for height in 0..top {
+            let node = path[height];
+            path.push(if (index >> height) & 1 == 1 {
+                self.hash(&self.active_branch[height], &node)
+            } else {
+                self.hash(&node, &self.zero_hashes[height])
+            });
+        }
+        for (&watched, siblings) in &mut self.witnesses {
+            let height = (usize::BITS - (watched ^ index).leading_zeros()) as usize - 1;
+            siblings[height] = path[height];
+        }
+    }
+
+    /// Read the siblings of every watched leaf back from the intermediates cache, which must be up to
+    /// date.
+    fn refresh_witnesses(&mut self) {
+        debug_assert!(
+            self.hashed_leaves == self.size,
+            "witnesses refreshed from a stale cache"
+        );
+        let mut witnesses = core::mem::take(&mut self.witnesses);
+        for (&index, siblings) in &mut witnesses {
+            *siblings = core::array::from_fn(|height| self.node_at(height, (index >> height) ^ 1));
+        }
+        self.witnesses = witnesses;
+    }
+
+    /// Rehash every intermediate node on the path from the leaf at `index` up to the root, reading
+    /// the siblings from the cache.
+    fn update_path(&mut self, index: usize) {
+        debug_assert!(
+            index < self.size,
+            "path update for a leaf that was never appended"
+        );
+        self.update_path_above(0, index);
+    }
+
+    /// Rehash every intermediate node above the node at `height` and `position` up to the root,
+    /// reading the siblings from the cache.
+    fn update_path_above(&mut self, height: usize, position: usize) {
+        self.levels_mut().0.update_path_above(height, position);
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> IncrementalMerkleTree<HEIGHT, H, S> {
+    /// Restore a tree from a [Frontier] as [Self::from_frontier] does, with its intermediates cache
+    /// kept in `store`, which must not hold any nodes yet.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` resuming from `frontier`.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if `frontier.size` exceeds the capacity
+    ///   of the tree.
+    pub fn from_frontier_with_store(
+        frontier: Frontier<HEIGHT>,
+        store: S,
+    ) -> Result<Self, IncrementalMerkleTreeError> {
+        let mut tree = Self::with_store(store);
+        tree.restore_frontier(frontier)?;
+        Ok(tree)
+    }
+
+    /// Returns a [Frontier] snapshot of the tree, from which [Self::from_frontier] can resume it.
+    pub fn frontier(&self) -> Frontier<HEIGHT> {
+        Frontier {
+            size: self.size,
+            active_branch: self.active_branch,
+        }
+    }
+
+    /// Take a [Checkpoint] of the current state, which [Self::rollback] can return to.
+    pub fn checkpoint(&self) -> Checkpoint<HEIGHT> {
+        Checkpoint {
+            frontier: self.frontier(),
+        }
+    }
+
+    /// Undo every append made since `checkpoint` was taken. The size, the active branch and the
+    /// intermediates cache are restored together, and the roots recorded since the checkpoint are
+    /// dropped from the root history. Roots the history evicted in the meantime are not restored.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree was rolled back.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if the tree holds fewer leaves
+    ///   than it did at the checkpoint.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the checkpoint predates the
+    ///   [Frontier] the tree was restored from, or falls inside a subtree appended with
+    ///   [Self::append_subtree].
+    /// - `Err(IncrementalMerkleTreeError::CorruptedState)` if the checkpoint was not taken from this
+    ///   tree; the tree is left unchanged.
+    pub fn rollback(
+        &mut self,
+        checkpoint: Checkpoint<HEIGHT>,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        let size = checkpoint.frontier.size;
+        if size > self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: size,
+                len: self.size,
+            });
+        }
+        if !self.is_stored_size(size) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+        let matches = (0..HEIGHT)
+            .filter(|height| (size >> height) & 1 == 1)
+            .all(|height| {
+                checkpoint.frontier.active_branch[height]
+                    == self.node_at(height, (size >> height) - 1)
+            });
+        if !matches {
+            return Err(IncrementalMerkleTreeError::CorruptedState);
+        }
+
+        self.rewind_to(size);
+        Ok(())
+    }
+
+    /// Begin an all-or-nothing batch of appends. The leaves appended through the returned
+    /// [BatchGuard] are kept by [BatchGuard::commit], and discarded by [BatchGuard::abort] or when the
+    /// guard is dropped, e.g. when a speculatively built block is abandoned.
+    pub fn begin_batch(&mut self) -> BatchGuard<'_, HEIGHT, H, S> {
+        BatchGuard::new(self)
+    }
+
+    /// Remove the most recently appended leaf, restoring the frontier, the intermediates cache and
+    /// the root history to what they were before it was appended. The previous peaks are read back
+    /// from the cache, so reverting a reorged deposit does not require rebuilding the tree.
+    ///
+    /// # Returns
+    /// - `Ok(leaf)` with the removed leaf.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the latest leaf is not stored, because
+    ///   it was appended with [Self::append_root_only] or before the tree was restored from a
+    ///   [Frontier]; the tree is left unchanged.
+    pub fn pop(&mut self) -> Result<B256, IncrementalMerkleTreeError> {
+        let Some(index) = self.size.checked_sub(1) else {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: 0, len: 0 });
+        };
+        if self.is_unstored(0, index) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+        let leaf = self.stored_leaf(index);
+        self.rewind_to(index);
+        Ok(leaf)
+    }
+
+    /// Shrink the tree to its first `size` leaves, which must all be stored and hashed. The peaks of
+    /// the active branch are read back from the cache.
+    fn rewind_to(&mut self, size: usize) {
+        debug_assert!(
+            self.is_stored_size(size) && size <= self.hashed_leaves,
+            "rewound to a size that is not stored and hashed"
+        );
+        // Drop the nodes that only cover rolled back leaves, then rehash the nodes that straddle the
+        // new size, which covered rolled back leaves too.
+        let old_size = self.size;
+        for height in 0..=HEIGHT {
+            let first = (size + (1 << height) - 1) >> height;
+            let last = (old_size + (1 << height) - 1) >> height;
+            for position in first..last {
+                self.intermediates
+                    .remove((1 << (HEIGHT - height)) + position);
+            }
+        }
+        self.size = size;
+        self.hashed_leaves = size;
+        self.root_memo.clear();
+        self.unstored_subtrees.retain(|subtree| subtree.end <= size);
+        for height in 1..=HEIGHT {
+            let position = size >> height;
+            if position << height < size {
+                let left = self.node_at(height - 1, position << 1);
+                let right = self.node_at(height - 1, (position << 1) + 1);
+                // The left child may only be implied by the padding root that the parent replaces.
+                self.intermediates
+                    .put((1 << (HEIGHT - height + 1)) + (position << 1), left);
+                let parent = self.hash(&left, &right);
+                self.intermediates
+                    .put((1 << (HEIGHT - height)) + position, parent);
+            }
+        }
+        self.read_active_branch();
+        self.witnesses.retain(|&index, _| index < size);
+        self.refresh_witnesses();
+
+        let dropped = (old_size - size).min(self.root_history.len());
+        self.root_history
+            .truncate(self.root_history.len() - dropped);
+    }
+
+    /// Discard the leaves before `boundary` and the intermediate nodes that only cover them, so a
+    /// long-lived tree only keeps the nodes of its retention window. The peaks of the first
+    /// `boundary` leaves are kept, which is all that appends and proofs of the leaves from `boundary`
+    /// on read from the discarded region. Call it with the first leaf of the oldest epoch to keep.
+    ///
+    /// The discarded leaves behave as if the tree had been restored from a [Frontier] of that size:
+    /// they, and the states before them, can no longer be proven, read or rolled back to, and the
+    /// leaves among them that were watched are no longer watched. Pruning before a boundary that is
+    /// already pruned does nothing.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaves before `boundary` are discarded.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `boundary` exceeds the
+    ///   number of leaves.
+    pub fn prune_before(&mut self, boundary: usize) -> Result<(), IncrementalMerkleTreeError> {
+        if boundary > self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: boundary,
+                len: self.size,
+            });
+        }
+        if boundary <= self.first_stored_leaf {
+            return Ok(());
+        }
+        // The leaves of an unstored subtree after the boundary are discarded along with it.
+        let boundary = self
+            .unstored_subtree(boundary)
+            .map_or(boundary, |subtree| subtree.end);
+        // Rehash stale nodes while their leaves are still stored.
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        // Below the previous boundary only its peaks are left, so the scan of every height starts at
+        // the last of them.
+        for height in 0..=HEIGHT {
+            let first = (self.first_stored_leaf >> height).saturating_sub(1);
+            let end = boundary >> height;
+            for position in first..end {
+                let is_peak = position + 1 == end && end & 1 == 1;
+                if !is_peak {
+                    self.intermediates
+                        .remove((1 << (HEIGHT - height)) + position);
+                }
+            }
+        }
+        self.first_stored_leaf = boundary;
+        self.unstored_subtrees
+            .retain(|subtree| subtree.start >= boundary);
+        self.witnesses.retain(|&index, _| index >= boundary);
+        Ok(())
+    }
+
+    /// Returns the active branch of the tree.
+    ///
+    /// Entry `i` holds the root of the complete subtree of `2^i` leaves that is waiting for a right
+    /// sibling at height `i`, i.e. the peak at that level. It is only meaningful when bit `i` of the
+    /// number of appended leaves is set; other entries hold stale values from earlier appends.
+    pub fn active_branch(&self) -> &[B256; HEIGHT] {
+        &self.active_branch
+    }
+
+    /// Returns the peaks of the tree: the roots of its complete subtrees, from the tallest (leftmost)
+    /// to the shortest (rightmost). There is one peak per set bit of the number of appended leaves.
+    pub fn peaks(&self) -> Vec<B256> {
+        self.active_peaks()
+    }
+
+    /// Returns the [RootMode] used by [Self::root].
+    pub fn root_mode(&self) -> RootMode {
+        self.root_mode
+    }
+
+    /// Select the [RootMode] used by [Self::root]. This only changes how the root is folded; the
+    /// leaves, the active branch and the intermediates are unaffected.
+    pub fn set_root_mode(&mut self, root_mode: RootMode) {
+        self.root_mode = root_mode;
+        self.root_memo.clear();
+    }
+
+    /// Returns the range of generalized indices whose cached intermediates can currently be trusted,
+    /// or `None` while the nodes above some of the leaves are stale, e.g. after
+    /// [Self::append_deferred]. Nodes in the range whose subtree holds no appended leaves read back
+    /// as the zero hash of their height. The range ends after the node of the last leaf the tree can
+    /// hold.
+    ///
+    /// Once leaves are unavailable, because the tree was pruned, restored from a [Frontier] or
+    /// appended as part of a subtree, the range starts after the last of them on the leaf row: the
+    /// nodes above them mix available and unavailable subtrees, and are read through [Self::node].
+    pub fn cache_coverage(&self) -> Option<Range<usize>> {
+        let end = usize::MAX >> (usize::BITS as usize - 1 - HEIGHT);
+        let first = self
+            .unstored_subtrees
+            .last()
+            .map_or(self.first_stored_leaf, |subtree| subtree.end);
+        if self.hashed_leaves < self.size {
+            None
+        } else if first == 0 {
+            Some(1..end)
+        } else {
+            Some((1 << HEIGHT) + first..end)
+        }
+    }
+
+    /// Check the invariants that `debug_assert!`s guard in debug builds, without panicking.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree is internally consistent.
+    /// - `Err(IncrementalMerkleTreeError::CorruptedState)` if the size exceeds the capacity, the hash
+    ///   width is out of range, the zero hashes are not canonical, the root history holds more roots
+    ///   than its capacity, or an up to date cache disagrees with the active branch.
+    pub fn validate_invariants(&self) -> Result<(), IncrementalMerkleTreeError> {
+        let consistent = self.size <= capacity(HEIGHT)
+            && self.first_stored_leaf <= self.hashed_leaves
+            && self.hashed_leaves <= self.size
+            && valid_unstored_subtrees(&self.unstored_subtrees, self.first_stored_leaf, self.size)
+            && (1..=32).contains(&self.hash_width)
+            && self.zero_hashes_are_canonical()
+            && self.root_history.len() <= self.history_capacity
+            && (self.hashed_leaves < self.size
+                || (0..HEIGHT)
+                    .filter(|height| (self.size >> height) & 1 == 1)
+                    .all(|height| {
+                        self.active_branch[height]
+                            == self.node_at(height, (self.size >> height) - 1)
+                    }));
+
+        if consistent {
+            Ok(())
+        } else {
+            Err(IncrementalMerkleTreeError::CorruptedState)
+        }
+    }
+
+    /// Returns `true` if every stored zero hash is the parent of two copies of the zero hash one level
+    /// below it, starting from `zero_hashes[0]`. A mismatch indicates a corrupted or tampered tree.
+    pub fn zero_hashes_are_canonical(&self) -> bool {
+        (1..HEIGHT).all(|height| {
+            self.zero_hashes[height]
+                == self.hash(&self.zero_hashes[height - 1], &self.zero_hashes[height - 1])
+        })
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`. Any stale nodes in the intermediates cache
+    /// are rehashed first.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the leaf at `index` was appended before
+    ///   the tree was restored from a [Frontier].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn prove(
+        &mut self,
+        index: usize,
+    ) -> Result<MerkleProof<HEIGHT>, IncrementalMerkleTreeError> {
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+        let siblings = self.siblings(index)?;
+        Ok(MerkleProof {
+            leaf: self.stored_leaf(index),
+            index,
+            siblings,
+        })
+    }
+
+    /// Generate an inclusion proof for the most recently appended leaf in `O(HEIGHT)`, without
+    /// rehashing the intermediates cache. Every left sibling on the path of the latest leaf is a
+    /// complete subtree, which every append writes to the cache, even [Self::append_deferred], and
+    /// every right sibling is a zero hash. This suits deposit-style flows, where each depositor only
+    /// needs the proof of the leaf they just appended.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the latest leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the latest leaf is not stored, because
+    ///   it was appended with [Self::append_root_only] or before the tree was restored from a
+    ///   [Frontier].
+    pub fn prove_latest(&self) -> Result<MerkleProof<HEIGHT>, IncrementalMerkleTreeError> {
+        let Some(index) = self.size.checked_sub(1) else {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: 0, len: 0 });
+        };
+        if self.is_unstored(0, index) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        Ok(MerkleProof {
+            leaf: self.stored_leaf(index),
+            index,
+            siblings: {
+                let levels = self.levels();
+                core::array::from_fn(|height| levels.latest_sibling(index, height))
+            },
+        })
+    }
+
+    /// Watch the leaf at `index`: its siblings are stored and updated on every later append, so
+    /// [Self::current_proof] returns its proof without touching the intermediates cache. Watching a
+    /// leaf that is already watched does nothing.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf is watched.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the leaf at `index` was appended before
+    ///   the tree was restored from a [Frontier].
+    pub fn watch(&mut self, index: usize) -> Result<(), IncrementalMerkleTreeError> {
+        if self.witnesses.contains_key(&index) {
+            return Ok(());
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+        let siblings = self.siblings(index)?;
+        self.witnesses.insert(index, siblings);
+        Ok(())
+    }
+
+    /// Stop watching the leaf at `index`. Returns `true` if it was watched.
+    pub fn unwatch(&mut self, index: usize) -> bool {
+        self.witnesses.remove(&index).is_some()
+    }
+
+    /// Returns the indices of the watched leaves, in ascending order.
+    pub fn watched(&self) -> impl DoubleEndedIterator<Item = usize> + ExactSizeIterator + '_ {
+        self.witnesses.keys().copied()
+    }
+
+    /// Returns the proof of the watched leaf at `index` against the current padded root, or `None`
+    /// if the leaf is not watched. No node is hashed.
+    pub fn current_proof(&self, index: usize) -> Option<MerkleProof<HEIGHT>> {
+        self.witnesses.get(&index).map(|siblings| MerkleProof {
+            leaf: self.stored_leaf(index),
+            index,
+            siblings: *siblings,
+        })
+    }
+
+    /// Generate a single [MultiProof] for every leaf in `indices`. Duplicate indices are proven once,
+    /// and the proof lists the leaves in ascending index order.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the proven leaves, the siblings needed to rebuild the root and the
+    ///   descriptor bits.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   one of the indices.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if one of the leaves was appended before
+    ///   the tree was restored from a [Frontier].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn prove_multi(
+        &mut self,
+        indices: &[usize],
+    ) -> Result<MultiProof<HEIGHT>, IncrementalMerkleTreeError> {
+        if let Some(&index) = indices.iter().find(|&&index| index >= self.size) {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.size,
+            });
+        }
+        if indices.iter().any(|&index| self.is_unstored(0, index)) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        let leaves = indices
+            .iter()
+            .map(|&index| self.stored_leaf(index))
+            .collect();
+
+        // Walk the proven positions up the tree, recording for every merge step whether the sibling
+        // is derived from another proven position or has to be supplied.
+        let mut siblings = Vec::new();
+        let mut flags = Vec::new();
+        let mut positions = indices.clone();
+        for height in 0..HEIGHT {
+            let mut parents = Vec::with_capacity(positions.len());
+            let mut i = 0;
+            while i < positions.len() {
+                let position = positions[i];
+                let paired = position & 1 == 0 && positions.get(i + 1) == Some(&(position + 1));
+                flags.push(paired);
+                if paired {
+                    i += 1;
+                } else {
+                    siblings.push(self.node_at(height, position ^ 1));
+                }
+                parents.push(position >> 1);
+                i += 1;
+            }
+            positions = parents;
+        }
+
+        Ok(MultiProof {
+            indices,
+            leaves,
+            siblings,
+            flags,
+        })
+    }
+
+    /// Generate a [RangeProof] for the contiguous leaves at `range`, which carries at most two
+    /// siblings per level instead of one path per leaf.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the proven leaves and the nodes bordering them.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `range` is empty or reaches
+    ///   past the last appended leaf.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if one of the leaves was appended before
+    ///   the tree was restored from a [Frontier].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn prove_range(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<RangeProof<HEIGHT>, IncrementalMerkleTreeError> {
+        if range.is_empty() || range.end > self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: range.end.max(range.start),
+                len: self.size,
+            });
+        }
+        if range.start < self.first_stored_leaf || self.subtree_leaves_in(&range) > 0 {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let leaves = range.clone().map(|index| self.stored_leaf(index)).collect();
+        let mut siblings = Vec::new();
+        let (mut start, mut end) = (range.start, range.end);
+        for height in 0..HEIGHT {
+            if start & 1 == 1 {
+                start -= 1;
+                siblings.push(self.node_at(height, start));
+            }
+            if end & 1 == 1 {
+                siblings.push(self.node_at(height, end));
+                end += 1;
+            }
+            start >>= 1;
+            end >>= 1;
+        }
+
+        Ok(RangeProof {
+            start: range.start,
+            leaves,
+            siblings,
+        })
+    }
+
+    /// Compare the tree with `other`. If one of them holds a prefix of the other's leaves, the diff is
+    /// the range of leaves only the longer one holds. Otherwise the first differing leaf is located
+    /// by descending from the largest complete subtrees both trees share, comparing cached nodes and
+    /// only entering the first subtree that differs, so syncing a replica needs `O(HEIGHT)`
+    /// comparisons rather than a scan of the leaves. Nodes that are stale in either cache are compared
+    /// through their children.
+    pub fn diff(&self, other: &Self) -> TreeDiff {
+        let shared = self.size.min(other.size);
+        let mut first = 0;
+        for height in (0..HEIGHT).rev() {
+            if (shared >> height) & 1 == 1 {
+                if let Some(first_leaf) = self.first_difference(other, height, first >> height) {
+                    return TreeDiff::Diverged { first_leaf };
+                }
+                first += 1 << height;
+            }
+        }
+        match self.size.cmp(&other.size) {
+            core::cmp::Ordering::Equal => TreeDiff::Equal,
+            core::cmp::Ordering::Less => TreeDiff::Appended(self.size..other.size),
+            core::cmp::Ordering::Greater => TreeDiff::Truncated(other.size..self.size),
+        }
+    }
+
+    /// Returns the node at generalized index `gindex`, the numbering of [export_nodes](Self::export_nodes)
+    /// and the [gindex](crate::gindex) module: `1` is the padded root, and the leaf `index` is
+    /// `2^HEIGHT + index`. Nodes covering no appended leaf are zero hashes, and any stale nodes in
+    /// the intermediates cache are rehashed first.
+    ///
+    /// # Returns
+    /// - `Ok(node)` with the hash of the node.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `gindex` is `0` or below the
+    ///   leaf level.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the node only covers leaves appended
+    ///   before the tree was restored from a [Frontier], and is not one of its peaks.
+    pub fn node(&mut self, gindex: u64) -> Result<B256, IncrementalMerkleTreeError> {
+        let index = usize::try_from(gindex).unwrap_or(usize::MAX);
+        if index == 0 || index > capacity(HEIGHT + 1) {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: capacity(HEIGHT + 1).saturating_add(1),
+            });
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let (depth, position) = to_depth_index(index);
+        let height = HEIGHT - depth;
+        if height == HEIGHT {
+            return Ok(self.padded_root());
+        }
+        if self.is_unstored(height, position) {
+            return self
+                .intermediates
+                .get(index)
+                .ok_or(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        Ok(self.node_at_size(height, position, self.size))
+    }
+
+    /// Returns the root of the subtree at `depth` below the root and horizontal `index`, i.e. the
+    /// node at generalized index `2^depth + index`. See [Self::node].
+    ///
+    /// # Returns
+    /// - `Ok(node)` with the root of the subtree.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `depth` exceeds `HEIGHT` or
+    ///   `index` is not below `2^depth`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the subtree only covers leaves
+    ///   appended before the tree was restored from a [Frontier], and is not one of its peaks.
+    pub fn subtree_root(
+        &mut self,
+        depth: usize,
+        index: usize,
+    ) -> Result<B256, IncrementalMerkleTreeError> {
+        if depth > HEIGHT {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: depth,
+                len: HEIGHT + 1,
+            });
+        }
+        if index > capacity(depth) {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: capacity(depth).saturating_add(1),
+            });
+        }
+        self.node(gindex(depth, index) as u64)
+    }
+
+    /// Returns every populated node of the tree with its generalized index, in ascending generalized
+    /// index order: the root first, then level by level down to the leaves. Only nodes covering at
+    /// least one appended leaf are yielded, so a proof service backed by its own key-value store can
+    /// bulk-load the node set once and substitute the zero hashes for missing nodes. Any stale nodes
+    /// in the intermediates cache are rehashed first.
+    ///
+    /// Nodes that are not stored because the tree was restored from a [Frontier] are skipped, apart
+    /// from the peaks of that frontier.
+    pub fn export_nodes(&mut self) -> impl Iterator<Item = (u64, B256)> + '_ {
+        self.recompute_intermediates();
+        let tree = &*self;
+        let levels = if tree.size == 0 { 0 } else { HEIGHT + 1 };
+        (0..levels).rev().flat_map(move |height| {
+            (0..=(tree.size - 1) >> height).filter_map(move |position| {
+                let gindex = (1 << (HEIGHT - height)) + position;
+                let node = if tree.is_unstored(height, position) {
+                    tree.intermediates.get(gindex)?
+                } else {
+                    // The nodes above the last leaf are not stored right after a restore.
+                    tree.node_at_size(height, position, tree.size)
+                };
+                Some((gindex as u64, node))
+            })
+        })
+    }
+
+    /// Export the populated part of the tree as a Graphviz `digraph`, for inspecting where the tree
+    /// departs from another implementation. Every node is labelled with its generalized index and hex
+    /// hash; subtrees holding no appended leaf are collapsed into a single dashed zero node, and
+    /// subtrees that are not stored because the tree was restored from a [Frontier] into a dotted
+    /// node. Any stale nodes in the intermediates cache are rehashed first.
+    ///
+    /// The graph holds about two nodes per appended leaf, so only render small trees.
+    pub fn to_dot(&mut self) -> String {
+        self.recompute_intermediates();
+        let mut dot =
+            String::from("digraph merkle {\n    node [shape=box, fontname=\"monospace\"];\n");
+        self.visit_nodes(&mut |node| {
+            let (label, style) = match node.kind {
+                DumpKind::Node => (format!("{}", node.hash), "solid"),
+                DumpKind::Zero => (format!("zero subtree of height {}", node.height), "dashed"),
+                DumpKind::Unavailable => (String::from("not stored"), "dotted"),
+            };
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{}\\n{}\", style={}];",
+                node.gindex, node.gindex, label, style
+            );
+            if node.gindex > 1 {
+                let _ = writeln!(dot, "    n{} -> n{};", parent(node.gindex), node.gindex);
+            }
+        });
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Export the populated part of the tree as a JSON array of nodes, top down, each an object with
+    /// its `gindex`, `height`, hex `hash` and `kind`: `"node"`, `"zero"` for a collapsed subtree
+    /// holding no appended leaf, or `"unavailable"`, with a `null` hash, for a subtree that is not
+    /// stored because the tree was restored from a [Frontier]. Any stale nodes in the intermediates
+    /// cache are rehashed first.
+    pub fn to_json_nodes(&mut self) -> String {
+        self.recompute_intermediates();
+        let mut json = String::from("[");
+        self.visit_nodes(&mut |node| {
+            if json.len() > 1 {
+                json.push(',');
+            }
+            let (hash, kind) = match node.kind {
+                DumpKind::Node => (format!("\"{}\"", node.hash), "node"),
+                DumpKind::Zero => (format!("\"{}\"", node.hash), "zero"),
+                DumpKind::Unavailable => (String::from("null"), "unavailable"),
+            };
+            let _ = write!(
+                json,
+                "{{\"gindex\":{},\"height\":{},\"hash\":{},\"kind\":\"{}\"}}",
+                node.gindex, node.height, hash, kind
+            );
+        });
+        json.push(']');
+        json
+    }
+
+    /// Generate a [ConsistencyProof] that the tree at `new_size` leaves is an append-only extension of
+    /// the tree at `old_size` leaves. Any stale nodes in the intermediates cache are rehashed first.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the shortest complete subtree of the old tree and the siblings above it.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `old_size` exceeds
+    ///   `new_size` or `new_size` exceeds the number of appended leaves.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if `old_size` is smaller than the size at
+    ///   which the tree was restored from a [Frontier].
+    pub fn consistency_proof(
+        &mut self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof<HEIGHT>, IncrementalMerkleTreeError> {
+        if old_size > new_size || new_size > self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: old_size.max(new_size),
+                len: new_size.min(self.size),
+            });
+        }
+        if (old_size != 0 && !self.is_stored_size(old_size)) || !self.is_stored_size(new_size) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if old_size == 0 {
+            return Ok(ConsistencyProof {
+                old_size,
+                new_size,
+                peak: B256::ZERO,
+                siblings: Vec::new(),
+            });
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let base = old_size.trailing_zeros() as usize;
+        let mut position = (old_size >> base) - 1;
+        let peak = self.node_at(base, position);
+        let siblings = (base..HEIGHT)
+            .map(|height| {
+                // Left siblings are complete subtrees of the old tree; right siblings are read as
+                // they were when the tree held `new_size` leaves.
+                let sibling = if position & 1 == 1 {
+                    self.node_at(height, position - 1)
+                } else {
+                    self.node_at_size(height, position + 1, new_size)
+                };
+                position >>= 1;
+                sibling
+            })
+            .collect();
+
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            peak,
+            siblings,
+        })
+    }
+
+    /// Returns an iterator yielding the proof of every stored leaf, in order, one at a time. Only a
+    /// single proof is held at once, so exporting every proof of a large tree needs memory for the
+    /// cache alone.
+    pub fn proof_iter(
+        &mut self,
+    ) -> impl Iterator<Item = Result<MerkleProof<HEIGHT>, IncrementalMerkleTreeError>> + '_ {
+        let subtrees = self.unstored_subtrees.clone();
+        (self.first_stored_leaf..self.size)
+            .filter(move |index| !subtrees.iter().any(|subtree| subtree.contains(index)))
+            .map(move |index| self.prove(index))
+    }
+
+    /// Returns `true` if `root` was the root of the tree at any point in its history, including the
+    /// empty tree and the current state. For a tree restored from a [Frontier], the history starts
+    /// at the restored state.
+    ///
+    /// Every earlier root is rebuilt from the intermediates cache, so this costs `O(size * HEIGHT)`
+    /// hashes. Roots at sizes whose nodes are still stale in the cache, other than the current one,
+    /// are not considered.
+    pub fn was_root(&self, root: B256) -> bool {
+        self.root() == root
+            || (self.first_stored_leaf..=self.hashed_leaves)
+                .any(|size| self.is_stored_size(size) && self.root_at_size(size) == root)
+    }
+
+    /// Compute the root of the tree at the time it held `size` leaves, according to the tree's
+    /// [RootMode], from the complete subtrees of that earlier frontier in the intermediates cache.
+    ///
+    /// # Returns
+    /// - `Ok(root)` with the historical root.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if `size` exceeds the number of
+    ///   appended leaves, or the cache above the leaves before `size` is stale and
+    ///   [Self::recompute_intermediates] has to run first.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if `size` is smaller than the size at
+    ///   which the tree was restored from a [Frontier].
+    pub fn root_at(&self, size: usize) -> Result<B256, IncrementalMerkleTreeError> {
+        if size == self.size {
+            return Ok(self.root());
+        }
+        if size > self.hashed_leaves {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index: size,
+                len: self.hashed_leaves,
+            });
+        }
+        if !self.is_stored_size(size) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        Ok(self.root_at_size(size))
+    }
+
+    /// Append a new leaf like [Self::append], but leave the nodes above it that also cover earlier
+    /// leaves stale in the intermediates cache instead of rehashing them. Only the leaf and the
+    /// complete subtrees it closes are written, which costs the carry hashes of
+    /// [FrontierTree](crate::FrontierTree) rather than `HEIGHT` hashes per leaf. The next proof, or
+    /// [Self::recompute_intermediates], rehashes every stale node once, level by level, so producers
+    /// appending many leaves one at a time between proofs hash about as much as with
+    /// [Self::append_batch]. [Self::prove_latest] does not need the stale nodes.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append_deferred(
+        &mut self,
+        leaf: impl LeafEncode,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        let height = self.push_leaf(leaf.leaf_hash())?;
+        self.record_root();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            monotonic_counter.leaves_appended = 1u64,
+            monotonic_counter.hashes = height as u64,
+            "appended a leaf without rehashing its path"
+        );
+        #[cfg(not(feature = "tracing"))]
+        let _ = height;
+        Ok(())
+    }
+
+    /// Append a new leaf while only maintaining the frontier, for instances that compute roots but
+    /// never prove the leaves appended this way. The leaf and the path above it are not written to
+    /// the intermediates cache; only the new peak of the frontier is, replacing the peaks it absorbs,
+    /// so a long run of these appends costs the carry hashes of [FrontierTree](crate::FrontierTree)
+    /// and keeps the cache at `HEIGHT` nodes.
+    ///
+    /// The leaf and every leaf before it are afterwards treated like those appended before the tree
+    /// was restored from a [Frontier]: they can no longer be read or proven, while later leaves
+    /// appended with [Self::append] can. Watched leaves keep their proofs.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append_root_only(
+        &mut self,
+        leaf: impl LeafEncode,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        let leaf = leaf.leaf_hash();
+        if self.size >= capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        // Rehash stale nodes while their leaves are still stored.
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+        self.update_witnesses(leaf);
+        let old_size = self.size;
+        self.size += 1;
+        self.root_memo.clear();
+
+        let mut size = self.size;
+        let mut node = leaf;
+        for height in 0..HEIGHT {
+            if size & 1 == 1 {
+                self.active_branch[height] = node;
+                self.intermediates
+                    .put((1 << (HEIGHT - height)) + (self.size >> height) - 1, node);
+                self.first_stored_leaf = self.size;
+                self.unstored_subtrees.clear();
+                self.hashed_leaves = self.size;
+                self.record_root();
+                #[cfg(feature = "tracing")]
+                tracing::trace!(
+                    monotonic_counter.leaves_appended = 1u64,
+                    monotonic_counter.hashes = height as u64,
+                    "appended a leaf to the frontier"
+                );
+                return Ok(());
+            }
+
+            // The peak at this height is absorbed into the new node. Drop it from the cache, unless
+            // it covers stored leaves, which watched leaves may still read.
+            let position = (old_size >> height) - 1;
+            if self.is_unstored(height, position) {
+                self.intermediates
+                    .remove((1 << (HEIGHT - height)) + position);
+            }
+            node = self.hash(&self.active_branch[height], &node);
+            size >>= 1;
+        }
+
+        Err(IncrementalMerkleTreeError::LoopDidNotTerminate)
+    }
+
+    /// Record `leaf` to `log`, and append it to the tree once it is durably recorded.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was recorded and appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more
+    ///   leaves; nothing is recorded.
+    /// - `Err(IncrementalMerkleTreeError::LogWriteFailed)` if the log could not record the leaf; the
+    ///   tree is left unchanged.
+    pub fn append_logged(
+        &mut self,
+        leaf: B256,
+        log: &mut impl AppendLog,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        if self.size >= capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        log.record(self.size, leaf)?;
+        self.append(leaf)
+    }
+
+    /// Append `leaf` to the tree, and notify `observer` of it with the new root.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves;
+    ///   the observer is not notified.
+    pub fn append_observed(
+        &mut self,
+        leaf: impl LeafEncode,
+        observer: &mut impl AppendObserver,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        let leaf = leaf.leaf_hash();
+        self.append(leaf)?;
+        observer.on_append(self.size - 1, leaf, self.root());
+        Ok(())
+    }
+
+    /// Append every leaf in `leaves` as [Self::append_batch] does, and then notify `observer` of
+    /// each of them in order, with the root of the tree ending at that leaf. The intermediate roots
+    /// are folded from the rebuilt cache, at `O(HEIGHT)` hashes each.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the tree
+    ///   is left unchanged and the observer is not notified.
+    pub fn append_batch_observed(
+        &mut self,
+        leaves: &[B256],
+        observer: &mut impl AppendObserver,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        let old_size = self.size;
+        self.append_batch(leaves)?;
+        for (index, leaf) in (old_size..).zip(leaves) {
+            observer.on_append(index, *leaf, self.root_at_size(index + 1));
+        }
+        Ok(())
+    }
+
+    /// Replay the `(index, leaf)` entries of an [AppendLog] on top of the tree, e.g. an empty tree or
+    /// one restored from a [Frontier] or [Checkpoint] taken before the crash. Entries below the
+    /// current size are already in the tree and are skipped.
+    ///
+    /// # Returns
+    /// - `Ok(count)` with the number of replayed leaves.
+    /// - `Err(IncrementalMerkleTreeError::CorruptedState)` if an entry is missing from the log; the
+    ///   tree is left unchanged.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the replayed leaves do not all fit;
+    ///   the tree is left unchanged.
+    pub fn recover_from_log(
+        &mut self,
+        entries: impl IntoIterator<Item = (usize, B256)>,
+    ) -> Result<usize, IncrementalMerkleTreeError> {
+        let mut leaves = Vec::new();
+        for (index, leaf) in entries {
+            if index < self.size {
+                continue;
+            }
+            if index != self.size + leaves.len() {
+                return Err(IncrementalMerkleTreeError::CorruptedState);
+            }
+            leaves.push(leaf);
+        }
+        self.append_batch(&leaves)?;
+        Ok(leaves.len())
+    }
+
+    /// Append a complete subtree of `2^height` leaves, given only its `root` as computed by a tree of
+    /// the same hasher and hash width. The subtree node is written to the cache and the frontier is
+    /// updated with a single ladder of `HEIGHT - height` hashes, so batchers that already hold the
+    /// root of a batch never rehash its leaves.
+    ///
+    /// The leaves of the subtree are not stored, like those appended before the tree was restored
+    /// from a [Frontier]: they cannot be read or proven, and the tree cannot be rolled back to a size
+    /// inside the subtree. The leaves before and after it are stored and proven as usual. The root
+    /// history records the root after the subtree only.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the subtree was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the subtree does not fit; the tree is
+    ///   left unchanged.
+    /// - `Err(IncrementalMerkleTreeError::InvalidSubtree)` if the size of the tree is not a multiple
+    ///   of `2^height`; the tree is left unchanged.
+    pub fn append_subtree(
+        &mut self,
+        height: usize,
+        root: B256,
+    ) -> Result<(), IncrementalMerkleTreeError> {
+        self.push_subtree(height, root)
+    }
+
+    /// Append zero leaves until the tree holds `size` leaves. Rather than hashing every padding
+    /// leaf, the padding is split into the largest aligned complete subtrees, whose roots are zero
+    /// hashes. Only those roots and the paths above them are written, `O(HEIGHT^2)` hashes and cache
+    /// writes in total; the nodes below the roots are not written and read as zero hashes. The
+    /// padding leaves can be read and proven like any other leaf. Does nothing if the tree already
+    /// holds at least `size` leaves.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree holds at least `size` leaves.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if `size` exceeds the capacity of the
+    ///   tree; the tree is left unchanged.
+    pub fn pad_to(&mut self, size: usize) -> Result<(), IncrementalMerkleTreeError> {
+        if size > capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+        if size <= self.size {
+            return Ok(());
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        let old_size = self.size;
+        while self.size < size {
+            // The largest complete subtree starting at the current size that fits the padding.
+            let mut height = self.size.trailing_zeros().min(HEIGHT as u32) as usize;
+            while self.size + (1 << height) > size {
+                height -= 1;
+            }
+            let position = self.size >> height;
+            self.intermediates.put(
+                (1 << (HEIGHT - height)) + position,
+                self.zero_hashes[height],
+            );
+            self.size += 1 << height;
+            self.hashed_leaves = self.size;
+            self.root_memo.clear();
+            self.update_path_above(height, position);
+        }
+        self.read_active_branch();
+
+        self.refresh_witnesses();
+        self.record_roots_since(old_size);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            monotonic_counter.leaves_appended = (self.size - old_size) as u64,
+            "padded the tree with zero leaves"
+        );
+        Ok(())
+    }
+
+    /// Append zero leaves until the number of leaves is a power of two, as [Self::pad_to] does. An
+    /// empty tree is left empty.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the number of leaves is zero or a power of two.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the next power of two exceeds the
+    ///   capacity of the tree; the tree is left unchanged.
+    pub fn pad_to_next_power_of_two(&mut self) -> Result<(), IncrementalMerkleTreeError> {
+        if self.size == 0 {
+            return Ok(());
+        }
+        match self.size.checked_next_power_of_two() {
+            Some(size) => self.pad_to(size),
+            None => Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            }),
+        }
+    }
+
+    /// Replace the leaf at `index` with `leaf`, rehashing the path above it and the peak it belongs to.
+    /// The current root in the root history is replaced as well. Roots rebuilt from the cache, such as
+    /// those of [Self::root_at] and [Self::was_root], reflect the new leaf from now on.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was replaced.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the leaf at `index` was appended before
+    ///   the tree was restored from a [Frontier].
+    pub fn set_leaf(&mut self, index: usize, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        if index >= self.size {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.size,
+            });
+        }
+        if self.is_unstored(0, index) {
+            return Err(IncrementalMerkleTreeError::LeafUnavailable);
+        }
+        if self.hashed_leaves < self.size {
+            self.recompute_intermediates();
+        }
+
+        // The siblings on the path may only be implied by a padding root that the path replaces.
+        for height in 0..HEIGHT {
+            let sibling = (index >> height) ^ 1;
+            let gindex = (1 << (HEIGHT - height)) + sibling;
+            if sibling << height < self.size && self.intermediates.get(gindex).is_none() {
+                let node = self.node_at(height, sibling);
+                self.intermediates.put(gindex, node);
+            }
+        }
+        self.intermediates.put((1 << HEIGHT) + index, leaf);
+        self.update_path(index);
+        self.root_memo.clear();
+        self.read_active_branch();
+        self.refresh_witnesses();
+
+        if self.root_history.pop_back().is_some() {
+            let root = self.root();
+            self.root_history.push_back(root);
+        }
+        Ok(())
+    }
+
+    /// Returns the first leaf in the complete subtree at `height` and `position` at which the tree
+    /// differs from `other`, or `None` if the subtree is the same in both.
+    fn first_difference(&self, other: &Self, height: usize, position: usize) -> Option<usize> {
+        match (
+            self.cached_node(height, position),
+            other.cached_node(height, position),
+        ) {
+            (Some(left), Some(right)) if left == right => return None,
+            _ if height == 0 => return Some(position),
+            _ => {}
+        }
+        self.first_difference(other, height - 1, position << 1)
+            .or_else(|| self.first_difference(other, height - 1, (position << 1) + 1))
+    }
+
+    /// Visit the populated nodes of the tree top down, depth first, collapsing the subtrees that hold
+    /// no appended leaf or are not stored into a single node. The cache must be up to date.
+    fn visit_nodes(&self, visit: &mut impl FnMut(DumpNode)) {
+        visit(DumpNode {
+            gindex: 1,
+            height: HEIGHT,
+            hash: self.padded_root(),
+            kind: if self.size == 0 {
+                DumpKind::Zero
+            } else {
+                DumpKind::Node
+            },
+        });
+        if self.size > 0 {
+            self.visit_subtree(HEIGHT - 1, 0, visit);
+            self.visit_subtree(HEIGHT - 1, 1, visit);
+        }
+    }
+
+    /// Visit the node at `height` and `position` and the populated nodes below it.
+    fn visit_subtree(&self, height: usize, position: usize, visit: &mut impl FnMut(DumpNode)) {
+        let gindex = (1 << (HEIGHT - height)) + position;
+        let (hash, kind) = if position << height >= self.size {
+            (self.zero_hashes[height], DumpKind::Zero)
+        } else if self.is_unstored(height, position) {
+            // Only the peaks of a restored frontier and the roots of appended subtrees are stored.
+            match self.intermediates.get(gindex) {
+                Some(hash) => (hash, DumpKind::Node),
+                None => (B256::ZERO, DumpKind::Unavailable),
+            }
+        } else {
+            // The nodes above the last leaf are not stored right after a restore, so rehash them.
+            (
+                self.node_at_size(height, position, self.size),
+                DumpKind::Node,
+            )
+        };
+        visit(DumpNode {
+            gindex,
+            height,
+            hash,
+            kind,
+        });
+        if height > 0 && !self.is_unstored(height, position) && kind == DumpKind::Node {
+            self.visit_subtree(height - 1, position << 1, visit);
+            self.visit_subtree(height - 1, (position << 1) + 1, visit);
+        }
+    }
+
+    /// Returns the node of a complete subtree at `height` and `position` if it is up to date in the
+    /// cache.
+    fn cached_node(&self, height: usize, position: usize) -> Option<B256> {
+        let fresh = if height == 0 {
+            position < self.size
+        } else {
+            (position + 1) << height <= self.hashed_leaves
+        };
+        fresh
+            .then(|| {
+                self.intermediates
+                    .get((1 << (HEIGHT - height)) + position)
+                    .or_else(|| self.levels().padding_node(height, position))
+            })
+            .flatten()
+    }
+}
+
+/// Returns the number of leaves a tree of `height` can hold, `2^height - 1`, saturating at
+/// `usize::MAX` for heights whose capacity does not fit in a `usize`.
+pub(crate) const fn capacity(height: usize) -> usize {
+    capacity_with_arity(height, 2)
+}
+
+/// Returns the number of leaves a tree of `height` and `arity` can hold, `arity^height - 1`,
+/// saturating at `usize::MAX` for shapes whose capacity does not fit in a `usize`.
+const fn capacity_with_arity(height: usize, arity: usize) -> usize {
+    match arity.checked_pow(height as u32) {
+        Some(leaves) => leaves - 1,
+        None => usize::MAX,
+    }
+}
+
+/// Returns `true` if every node of a tree of `height` and `arity` has a generalized index that fits
+/// in a `usize`. The largest is that of the last leaf, `(arity^height - 1) / (arity - 1) + arity^height`.
+const fn node_indices_fit(height: usize, arity: usize) -> bool {
+    match arity.checked_pow(height as u32) {
+        Some(leaves) => ((leaves - 1) / (arity - 1)).checked_add(leaves).is_some(),
+        None => false,
+    }
+}
+
+/// Returns `true` if `subtrees` are in ascending order, each holding an aligned power of two of the
+/// leaves from `first_stored_leaf` up to `size`, as [IncrementalMerkleTree::append_subtree] appends
+/// them.
+fn valid_unstored_subtrees(
+    subtrees: &[Range<usize>],
+    first_stored_leaf: usize,
+    size: usize,
+) -> bool {
+    // A subtree appended right at the first stored leaf moves it instead.
+    let mut end = first_stored_leaf + 1;
+    subtrees.iter().all(|subtree| {
+        let len = subtree.len();
+        let valid = subtree.start >= end
+            && subtree.end <= size
+            && len.is_power_of_two()
+            && subtree.start % len == 0;
+        end = subtree.end;
+        valid
+    })
+}
+
+/// Returns `true` if every leaf in `leaves` is either below `first_stored_leaf` or inside one of the
+/// ascending `unstored_subtrees`, so its contents are not stored.
+fn only_unstored(
+    first_stored_leaf: usize,
+    unstored_subtrees: &[Range<usize>],
+    leaves: Range<usize>,
+) -> bool {
+    let subtree = unstored_subtrees.partition_point(|subtree| subtree.end <= leaves.start);
+    leaves.end <= first_stored_leaf
+        || unstored_subtrees
+            .get(subtree)
+            .is_some_and(|subtree| subtree.start <= leaves.start && leaves.end <= subtree.end)
+}
+
+/// Keep only the first `width` bytes of `hash`, zeroing the rest.
+pub(crate) fn truncate(mut hash: B256, width: usize) -> B256 {
+    hash[width..].fill(0);
+    hash
+}
+
+/// Returns the first `height` zero hashes of `H` at the given hash width: the empty leaf, and then the
+/// parent of two empty subtrees of the height below.
+///
+/// Full-width ladders covered by [MerkleHasher::precomputed_zero_hashes] are borrowed from it. With
+/// the `std` feature, every other ladder is computed once per hasher and width and then shared by all
+/// trees through a lazily initialized table, so creating a tree hashes nothing.
+pub(crate) fn zero_hashes<H: MerkleHasher>(height: usize, width: usize) -> Cow<'static, [B256]> {
+    let precomputed = H::precomputed_zero_hashes();
+    if width == 32 && precomputed.len() >= height {
+        return Cow::Borrowed(&precomputed[..height]);
+    }
+    #[cfg(feature = "std")]
+    {
+        use core::any::TypeId;
+        use hashbrown::HashMap;
+        use std::sync::{Mutex, OnceLock, PoisonError};
+
+        type Ladders = HashMap<(TypeId, usize), &'static [B256]>;
+        /// The longest ladder computed so far, by hasher and hash width
+        static LADDERS: OnceLock<Mutex<Ladders>> = OnceLock::new();
+
+        let mut ladders = LADDERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let ladder = ladders.entry((TypeId::of::<H>(), width)).or_default();
+        if ladder.len() < height {
+            // Ladders are never freed, so compute one covering every addressable height at once.
+            *ladder = compute_zero_hashes::<H>(height.max(usize::BITS as usize), width).leak();
+        }
+        Cow::Borrowed(&ladder[..height])
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        Cow::Owned(compute_zero_hashes::<H>(height, width))
+    }
+}
+
+/// Compute the first `height` zero hashes of `H` at the given hash width, starting from its
+/// precomputed ones when they are at full width.
+fn compute_zero_hashes<H: MerkleHasher>(height: usize, width: usize) -> Vec<B256> {
+    let mut zero_hashes = Vec::with_capacity(height);
+    if width == 32 {
+        let precomputed = H::precomputed_zero_hashes();
+        zero_hashes.extend_from_slice(&precomputed[..precomputed.len().min(height)]);
+    }
+    if height > 0 && zero_hashes.is_empty() {
+        zero_hashes.push(B256::ZERO);
+    }
+    for height in zero_hashes.len()..height {
+        // the empty subtree of `height` is the parent of two empty subtrees of `height - 1`
+        let zero = zero_hashes[height - 1];
+        zero_hashes.push(truncate(H::hash_pair(&zero, &zero), width));
+    }
+    zero_hashes
+}
+
+/// Compute the first `height` zero hashes of `H` for a tree of `arity` at the given hash width: the
+/// empty leaf, and then the parent of `arity` empty subtrees of the height below.
+fn compute_arity_zero_hashes<H: MerkleHasher>(
+    height: usize,
+    arity: usize,
+    width: usize,
+) -> Vec<B256> {
+    let mut zero_hashes = Vec::with_capacity(height);
+    if height > 0 {
+        zero_hashes.push(B256::ZERO);
+    }
+    for height in 1..height {
+        let children = alloc::vec![zero_hashes[height - 1]; arity];
+        zero_hashes.push(truncate(H::hash_children(&children), width));
+    }
+    zero_hashes
+}
+
+/// Bag a list of peaks, ordered from the tallest to the shortest, into a single root: the peaks are
+/// folded from the right, hashing each peak with the bag of the peaks to its right.
+///
+/// # Returns
+/// - The bagged root, the only peak if there is one, or [B256::ZERO] if there are none.
+pub fn bag_peaks(peaks: &[B256]) -> B256 {
+    bag_peaks_with_width::<Keccak256Hasher>(peaks, 32)
+}
+
+pub(crate) fn bag_peaks_with_width<H: MerkleHasher>(peaks: &[B256], width: usize) -> B256 {
+    match peaks.split_last() {
+        Some((last, rest)) => rest
+            .iter()
+            .rev()
+            .fold(*last, |bag, peak| truncate(H::hash_pair(peak, &bag), width)),
+        None => B256::ZERO,
+    }
+}
+
+impl<const HEIGHT: usize, H: DigestHasher, S: TreeStore> IncrementalMerkleTree<HEIGHT, H, S> {
+    /// Hash `data` into a leaf of `domain` with the hash function of the tree, and append it.
+    ///
+    /// # Returns
+    /// - `Ok(leaf)` with the appended leaf.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append_data(
+        &mut self,
+        domain: LeafDomain<'_>,
+        data: &[u8],
+    ) -> Result<B256, IncrementalMerkleTreeError> {
+        let leaf = domain.hash_with::<H>(data);
+        self.append(leaf)?;
+        Ok(leaf)
+    }
+}
+
+#[cfg(feature = "sha256")]
+impl<const HEIGHT: usize, S: TreeStore>
+    IncrementalMerkleTree<HEIGHT, crate::hasher::Sha256Hasher, S>
+{
+    /// Compute the root exactly as the beacon chain deposit contract's `get_deposit_root()` does: the
+    /// padded root of the tree, whatever its [RootMode], with the number of deposits mixed in (see
+    /// [crate::ssz::mix_in_length]).
+    ///
+    /// # Returns
+    /// - The deposit root of the tree.
+    pub fn deposit_root(&self) -> B256 {
+        crate::ssz::mix_in_length(self.padded_root(), self.size)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default>
+    IncrementalMerkleTree<HEIGHT, H, S>
+{
+    /// Create a new [IncrementalMerkleTree] containing `leaves`, in order, hashing every level of the
+    /// intermediates cache in parallel on the rayon thread pool. The result is identical to
+    /// [Self::from_leaves].
+    ///
+    /// # Returns
+    /// - `Ok(tree)` with every leaf appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if `leaves` does not fit in the tree.
+    pub fn from_leaves_parallel(leaves: &[B256]) -> Result<Self, IncrementalMerkleTreeError> {
+        use rayon::prelude::*;
+
+        if leaves.len() > capacity(HEIGHT) {
+            return Err(IncrementalMerkleTreeError::TreeFull {
+                capacity: capacity(HEIGHT),
+            });
+        }
+
+        let mut tree = Self::new();
+        if leaves.is_empty() {
+            return Ok(tree);
+        }
+        tree.size = leaves.len();
+        for (index, leaf) in leaves.iter().enumerate() {
+            tree.intermediates.put((1 << HEIGHT) + index, *leaf);
+        }
+
+        // Hash each level from the one below it, padding an odd level with the zero hash.
+        let width = tree.hash_width;
+        let mut level = leaves.to_vec();
+        for height in 1..=HEIGHT {
+            let zero = tree.zero_hashes[height - 1];
+            level = level
+                .par_chunks(2)
+                .map(|pair| truncate(H::hash_pair(&pair[0], pair.get(1).unwrap_or(&zero)), width))
+                .collect();
+            for (position, node) in level.iter().enumerate() {
+                tree.intermediates
+                    .put((1 << (HEIGHT - height)) + position, *node);
+            }
+        }
+        tree.hashed_leaves = tree.size;
+
+        for height in 0..HEIGHT {
+            if (tree.size >> height) & 1 == 1 {
+                tree.active_branch[height] = tree.node_at(height, (tree.size >> height) - 1);
+            }
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> IncrementalMerkleTree<HEIGHT, H, S> {
+    /// Returns the [TreeArchive](crate::TreeArchive) of the tree, holding its whole intermediates
+    /// cache. Any stale nodes are rehashed first. The root history and the watched leaves are not
+    /// archived.
+    pub fn to_archive(&mut self) -> crate::TreeArchive {
+        self.recompute_intermediates();
+        let mut level_offsets = Vec::with_capacity(HEIGHT + 2);
+        let mut nodes = Vec::new();
+        for level in 0..=HEIGHT {
+            level_offsets.push(nodes.len() as u64);
+            let count = archived_level_len(self.size, level);
+            nodes.extend((0..count).map(|position| {
+                self.intermediates
+                    .get((1 << (HEIGHT - level)) + position)
+                    .or_else(|| self.levels().padding_node(level, position))
+                    .unwrap_or_default()
+                    .0
+            }));
+        }
+        level_offsets.push(nodes.len() as u64);
+        crate::TreeArchive {
+            height: HEIGHT as u64,
+            hash_width: self.hash_width as u64,
+            bagged_peaks: self.root_mode == RootMode::BaggedPeaks,
+            size: self.size as u64,
+            first_stored_leaf: self.first_stored_leaf as u64,
+            unstored_subtrees: self
+                .unstored_subtrees
+                .iter()
+                .map(|subtree| [subtree.start as u64, subtree.end as u64])
+                .collect(),
+            active_branch: self.active_branch.iter().map(|node| node.0).collect(),
+            level_offsets,
+            nodes,
+        }
+    }
+
+    /// Returns the `rkyv` bytes of [Self::to_archive], which [Self::from_archive_bytes] reopens.
+    pub fn to_archive_bytes(&mut self) -> rkyv::util::AlignedVec {
+        rkyv::to_bytes::<rkyv::rancor::Error>(&self.to_archive())
+            .expect("archiving a tree to memory cannot fail")
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<'a, const HEIGHT: usize, H: MerkleHasher>
+    IncrementalMerkleTree<HEIGHT, H, crate::archive::ArchiveStore<'a>>
+{
+    /// Reopen a tree from the bytes written by [Self::to_archive_bytes], typically a memory-mapped
+    /// file. The bytes are validated, which only checks the layout, and are then read in place: no
+    /// node is copied or rehashed, and later appends write to an overlay on the heap. The tree must
+    /// be reopened with the hasher it was built with.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` reading its cache from `bytes`.
+    /// - `Err(IncrementalMerkleTreeError::InvalidEncoding)` if `bytes` is not a valid archive of a
+    ///   tree of height `HEIGHT`.
+    pub fn from_archive_bytes(bytes: &'a [u8]) -> Result<Self, IncrementalMerkleTreeError> {
+        let archive =
+            rkyv::access::<crate::archive::ArchivedTreeArchive, rkyv::rancor::Error>(bytes)
+                .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?;
+        let size = archive.size.to_native() as usize;
+        let first_stored_leaf = archive.first_stored_leaf.to_native() as usize;
+        let hash_width = archive.hash_width.to_native() as usize;
+        let unstored_subtrees: Vec<Range<usize>> = archive
+            .unstored_subtrees
+            .iter()
+            .map(|[start, end]| start.to_native() as usize..end.to_native() as usize)
+            .collect();
+        let offsets = &archive.level_offsets;
+        let layout_valid = archive.height.to_native() == HEIGHT as u64
+            && (1..=32).contains(&hash_width)
+            && size <= capacity(HEIGHT)
+            && first_stored_leaf <= size
+            && valid_unstored_subtrees(&unstored_subtrees, first_stored_leaf, size)
+            && archive.active_branch.len() == HEIGHT
+            && offsets.len() == HEIGHT + 2
+            && offsets[HEIGHT + 1].to_native() as usize == archive.nodes.len()
+            && (0..=HEIGHT).all(|level| {
+                offsets[level + 1]
+                    .to_native()
+                    .checked_sub(offsets[level].to_native())
+                    == Some(archived_level_len(size, level) as u64)
+            });
+        if !layout_valid {
+            return Err(IncrementalMerkleTreeError::InvalidEncoding);
+        }
+
+        let mut tree = Self::from_store(
+            crate::archive::ArchiveStore::new(archive, HEIGHT),
+            hash_width,
+        );
+        tree.size = size;
+        tree.first_stored_leaf = first_stored_leaf;
+        tree.unstored_subtrees = unstored_subtrees;
+        tree.hashed_leaves = size;
+        for (node, archived) in tree
+            .active_branch
+            .iter_mut()
+            .zip(archive.active_branch.iter())
+        {
+            *node = B256::new(*archived);
+        }
+        if archive.bagged_peaks {
+            tree.root_mode = RootMode::BaggedPeaks;
+        }
+        Ok(tree)
+    }
+}
+
+/// Returns the number of nodes at `level` above the first `size` leaves.
+#[cfg(feature = "rkyv")]
+fn archived_level_len(size: usize, level: usize) -> usize {
+    (size + (1 << level) - 1) >> level
+}
+
+#[cfg(feature = "mmap")]
+impl<const HEIGHT: usize, H: MerkleHasher>
+    IncrementalMerkleTree<HEIGHT, H, crate::mmap::MmapStore>
+{
+    /// Create a new, empty tree persisted to a memory-mapped file at `path`, replacing any existing
+    /// file. Call [Self::commit] to make the appended leaves durable.
+    pub fn create_mmap(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        Ok(Self::with_store(crate::mmap::MmapStore::create(
+            path, HEIGHT,
+        )?))
+    }
+
+    /// Reopen a tree persisted with [Self::create_mmap], at the size of its last [Self::commit]. The
+    /// leaves that were not stored at that commit, because they were pruned, appended with
+    /// [Self::append_root_only] or [Self::append_subtree], or before a restore from a [Frontier], stay
+    /// unavailable.
+    ///
+    /// If the file was not committed after its last write, the leaves appended since are dropped
+    /// and every intermediate node above the committed stored leaves is rehashed, then the file is
+    /// committed again. The root history and root mode are not persisted.
+    ///
+    /// # Returns
+    /// - `Ok(tree)` with the committed leaves.
+    /// - `Err(error)` if the file cannot be mapped, or was created for a tree of another height.
+    pub fn open_mmap(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind};
+
+        let store = crate::mmap::MmapStore::open(path)?;
+        if store.height() != HEIGHT {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "tree file was created for another height",
+            ));
+        }
+        let size = store.committed_size();
+        let first_stored_leaf = store.committed_first_stored_leaf();
+        let unstored_subtrees = store.committed_unstored_subtrees();
+        if size > capacity(HEIGHT) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "committed size exceeds the tree capacity",
+            ));
+        }
+        if first_stored_leaf > size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "committed first stored leaf exceeds the committed size",
+            ));
+        }
+        if !valid_unstored_subtrees(&unstored_subtrees, first_stored_leaf, size) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "committed unstored subtrees are not aligned ranges of the committed leaves",
+            ));
+        }
+        let dirty = store.is_dirty();
+
+        let mut tree = Self::with_store(store);
+        tree.size = size;
+        tree.first_stored_leaf = first_stored_leaf;
+        tree.unstored_subtrees = unstored_subtrees;
+        tree.hashed_leaves = if dirty { first_stored_leaf } else { size };
+        tree.recompute_intermediates();
+        for height in 0..HEIGHT {
+            if (size >> height) & 1 == 1 {
+                tree.active_branch[height] = tree.node_at(height, (size >> height) - 1);
+            }
+        }
+        if dirty {
+            tree.commit()?;
+        }
+        Ok(tree)
+    }
+
+    /// Flush every node written to the tree file and record the current size and first stored leaf,
+    /// so that [Self::open_mmap] resumes from here.
+    pub fn commit(&mut self) -> std::io::Result<()> {
+        self.intermediates
+            .commit(self.size, self.first_stored_leaf, &self.unstored_subtrees)
+    }
+}
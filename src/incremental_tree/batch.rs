@@ -0,0 +1,96 @@
+use alloc::collections::VecDeque;
+use alloy_primitives::B256;
+
+use super::{IncrementalMerkleTree, IncrementalMerkleTreeError};
+use crate::{hasher::MerkleHasher, leaf::LeafEncode, store::TreeStore};
+
+/// [BatchGuard] is an all-or-nothing batch of appends to an [IncrementalMerkleTree], returned by
+/// [IncrementalMerkleTree::begin_batch]. The leaves are appended to the tree as they come, so the
+/// speculative root and proofs are read through the guard, and [Self::commit] keeps them. Aborting
+/// the batch, explicitly or by dropping the guard, rewinds the tree to its state before the batch by
+/// dropping the nodes the batch wrote, without cloning the tree.
+#[must_use = "dropping a batch aborts it"]
+pub struct BatchGuard<'a, const HEIGHT: usize, H: MerkleHasher, S: TreeStore> {
+    /// The tree the batch appends to
+    tree: &'a mut IncrementalMerkleTree<HEIGHT, H, S>,
+    /// The number of leaves in the tree before the batch
+    start: usize,
+    /// The root history before the batch, whose oldest roots the batch may evict
+    root_history: VecDeque<B256>,
+    /// Whether the batch is kept when the guard is dropped
+    committed: bool,
+}
+
+impl<'a, const HEIGHT: usize, H: MerkleHasher, S: TreeStore> BatchGuard<'a, HEIGHT, H, S> {
+    /// Begin a batch of appends to `tree`.
+    pub(super) fn new(tree: &'a mut IncrementalMerkleTree<HEIGHT, H, S>) -> Self {
+        Self {
+            start: tree.size,
+            root_history: tree.root_history.clone(),
+            tree,
+            committed: false,
+        }
+    }
+
+    /// Append a leaf to the batch, as [IncrementalMerkleTree::append] does.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves;
+    ///   the batch is left as it was.
+    pub fn append(&mut self, leaf: impl LeafEncode) -> Result<(), IncrementalMerkleTreeError> {
+        self.tree.append(leaf)
+    }
+
+    /// Append every leaf in `leaves` to the batch, as [IncrementalMerkleTree::append_batch] does.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the batch
+    ///   is left as it was.
+    pub fn append_batch(&mut self, leaves: &[B256]) -> Result<(), IncrementalMerkleTreeError> {
+        self.tree.append_batch(leaves)
+    }
+
+    /// Returns the number of leaves appended in the batch.
+    pub fn len(&self) -> usize {
+        self.tree.size - self.start
+    }
+
+    /// Returns `true` if no leaf has been appended in the batch.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the root of the tree with the leaves of the batch.
+    pub fn root(&self) -> B256 {
+        self.tree.root()
+    }
+
+    /// Returns the tree with the leaves of the batch.
+    pub fn tree(&self) -> &IncrementalMerkleTree<HEIGHT, H, S> {
+        self.tree
+    }
+
+    /// Keep the leaves of the batch.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Discard the leaves of the batch, returning the tree to its state before the batch. Dropping
+    /// the guard does the same.
+    pub fn abort(self) {}
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore> Drop for BatchGuard<'_, HEIGHT, H, S> {
+    fn drop(&mut self) {
+        if self.committed || self.tree.size == self.start {
+            return;
+        }
+        if self.tree.hashed_leaves < self.tree.size {
+            self.tree.recompute_intermediates();
+        }
+        self.tree.rewind_to(self.start);
+        self.tree.root_history = core::mem::take(&mut self.root_history);
+    }
+}
@@ -0,0 +1,352 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Range},
+};
+
+use super::{only_unstored, truncate, IncrementalMerkleTreeError};
+use crate::{hasher::MerkleHasher, store::TreeStore};
+
+/// Returns the number of leaves below a node at `height` of a tree of `ARITY`, `ARITY^height`.
+pub(crate) fn span<const ARITY: usize>(height: usize) -> usize {
+    if ARITY == 2 {
+        1 << height
+    } else {
+        ARITY.pow(height as u32)
+    }
+}
+
+/// Returns the generalized index of the node at `height` and horizontal `position` of a tree of
+/// `tree_height` levels. The root is `1`, and the children of node `i` are `ARITY * (i - 1) + 2` to
+/// `ARITY * i + 1`, which are `2i` and `2i + 1` in a binary tree.
+pub(crate) fn node_index<const ARITY: usize>(
+    tree_height: usize,
+    height: usize,
+    position: usize,
+) -> usize {
+    (span::<ARITY>(tree_height - height) - 1) / (ARITY - 1) + 1 + position
+}
+
+/// [Levels] is the height-independent core of the incremental trees: the carry of an append, the
+/// path and level-by-level rehashes of the intermediates cache, and the roots and siblings read from
+/// it. [IncrementalMerkleTree](super::IncrementalMerkleTree) and
+/// [DynIncrementalMerkleTree](crate::DynIncrementalMerkleTree) borrow their height, size, zero hashes
+/// and cache into one for every operation, through a shared reference to the cache for reads and a
+/// mutable one for writes. A runtime-height tree stores every leaf, so it passes no unstored leaves.
+pub(crate) struct Levels<'a, H, C, const ARITY: usize = 2> {
+    /// The height of the tree
+    height: usize,
+    /// The number of leaves that have been added to the tree
+    size: usize,
+    /// The number of leading bytes kept from every internal node hash
+    hash_width: usize,
+    /// The index of the first leaf whose contents are stored
+    first_stored_leaf: usize,
+    /// The leaves of the subtrees after the first stored leaf whose contents are not stored either
+    unstored_subtrees: &'a [Range<usize>],
+    /// The zero hashes of the heights below the root
+    zero_hashes: &'a [B256],
+    /// A reference to the intermediates cache, keyed by generalized index
+    intermediates: C,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<'a, H, C: Deref<Target: TreeStore>, const ARITY: usize> Levels<'a, H, C, ARITY> {
+    /// Borrow the levels of a tree of `height` holding `size` leaves.
+    pub(crate) fn new(
+        height: usize,
+        size: usize,
+        hash_width: usize,
+        first_stored_leaf: usize,
+        unstored_subtrees: &'a [Range<usize>],
+        zero_hashes: &'a [B256],
+        intermediates: C,
+    ) -> Self {
+        Self {
+            height,
+            size,
+            hash_width,
+            first_stored_leaf,
+            unstored_subtrees,
+            zero_hashes,
+            intermediates,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the generalized index of the node at `height` and horizontal `position`.
+    pub(crate) fn node_index(&self, height: usize, position: usize) -> usize {
+        node_index::<ARITY>(self.height, height, position)
+    }
+
+    /// Returns `true` if the subtree at `height` and `position` only covers leaves whose contents are
+    /// not stored.
+    pub(crate) fn is_unstored(&self, height: usize, position: usize) -> bool {
+        only_unstored(
+            self.first_stored_leaf,
+            self.unstored_subtrees,
+            position * span::<ARITY>(height)..(position + 1) * span::<ARITY>(height),
+        )
+    }
+
+    /// Returns the zero hash of `height` if the node at `height` and `position`, which is not in the
+    /// cache, lies below a subtree of zero leaves of which only the root was written, as
+    /// [IncrementalMerkleTree::pad_to](super::IncrementalMerkleTree::pad_to) does: the nearest
+    /// ancestor of the node in the cache is then the zero hash of its height. The nodes of unstored
+    /// leaves are never read as padding.
+    pub(crate) fn padding_node(&self, height: usize, position: usize) -> Option<B256> {
+        if ARITY != 2 || self.is_unstored(height, position) {
+            return None;
+        }
+        (height + 1..self.height).find_map(|ancestor| {
+            let index = self.node_index(ancestor, position >> (ancestor - height));
+            self.intermediates.get(index).map(|node| {
+                (node == self.zero_hashes[ancestor]).then_some(self.zero_hashes[height])
+            })
+        })?
+    }
+
+    /// Returns the cached node at `height` and horizontal `position`, substituting the zero hash of
+    /// that height for nodes whose subtree holds no appended leaves or only padding.
+    pub(crate) fn node_at(&self, height: usize, position: usize) -> B256 {
+        debug_assert!(height < self.height, "node height out of range");
+        debug_assert!(
+            position < span::<ARITY>(self.height - height),
+            "node position out of range"
+        );
+        if position * span::<ARITY>(height) >= self.size {
+            return self.zero_hashes[height];
+        }
+        self.intermediates
+            .get(self.node_index(height, position))
+            .or_else(|| self.padding_node(height, position))
+            .expect("the tree store lost a node the tree wrote")
+    }
+
+    /// Returns the sibling at `height` on the path from the leaf at `index` of a binary tree up to
+    /// the root, read from the intermediates cache, which must be up to date.
+    pub(crate) fn sibling(&self, index: usize, height: usize) -> B256 {
+        self.node_at(height, (index >> height) ^ 1)
+    }
+
+    /// Returns the sibling at `height` on the path from the most recently appended leaf of a binary
+    /// tree, at `index`, up to the root. Every left sibling on that path is a complete subtree, which
+    /// every append writes to the cache, and every right sibling is a zero hash, so the stale nodes of
+    /// the cache are never read.
+    pub(crate) fn latest_sibling(&self, index: usize, height: usize) -> B256 {
+        if (index >> height) & 1 == 1 {
+            self.sibling(index, height)
+        } else {
+            self.zero_hashes[height]
+        }
+    }
+
+    /// Read the active branch back from the intermediates cache, in which every node of it is a
+    /// complete subtree.
+    pub(crate) fn read_active_branch(&self, active_branch: &mut [B256]) {
+        for (height, node) in active_branch.iter_mut().enumerate() {
+            let complete = self.size / span::<ARITY>(height);
+            if !complete.is_multiple_of(ARITY) {
+                *node = self.node_at(height, complete - 1);
+            }
+        }
+    }
+}
+
+impl<H: MerkleHasher, C: Deref<Target: TreeStore>, const ARITY: usize> Levels<'_, H, C, ARITY> {
+    /// Returns the node at `height` and horizontal `position` as it was when the tree held `size`
+    /// leaves. Only the nodes whose subtree straddles `size` differ from the cache, and they are
+    /// rehashed from their children.
+    pub(crate) fn node_at_size(&self, height: usize, position: usize, size: usize) -> B256 {
+        if position * span::<ARITY>(height) >= size {
+            self.zero_hashes[height]
+        } else if (position + 1) * span::<ARITY>(height) <= size {
+            self.node_at(height, position)
+        } else if ARITY == 2 {
+            let left = self.node_at_size(height - 1, position << 1, size);
+            let right = self.node_at_size(height - 1, (position << 1) + 1, size);
+            self.hash(&left, &right)
+        } else {
+            let children: Vec<B256> = (position * ARITY..(position + 1) * ARITY)
+                .map(|child| self.node_at_size(height - 1, child, size))
+                .collect();
+            self.hash_children(&children)
+        }
+    }
+
+    /// Hash two sibling nodes into their parent at the tree's hash width.
+    pub(crate) fn hash(&self, left: &B256, right: &B256) -> B256 {
+        truncate(H::hash_pair(left, right), self.hash_width)
+    }
+
+    /// Hash the `ARITY` children of a node into the node at the tree's hash width.
+    pub(crate) fn hash_children(&self, children: &[B256]) -> B256 {
+        truncate(H::hash_children(children), self.hash_width)
+    }
+
+    /// Rehash the node at `height` and horizontal `position` from its children in the cache.
+    pub(crate) fn hash_children_of(&self, height: usize, position: usize) -> B256 {
+        let first = position * ARITY;
+        if ARITY == 2 {
+            return self.hash(
+                &self.node_at(height - 1, first),
+                &self.node_at(height - 1, first + 1),
+            );
+        }
+        let children: Vec<B256> = (first..first + ARITY)
+            .map(|child| self.node_at(height - 1, child))
+            .collect();
+        self.hash_children(&children)
+    }
+
+    /// Fold the active branch with the zero hashes into the root of the tree padded to its height.
+    /// Trees of a higher arity fold their frontier out of the intermediates cache instead.
+    pub(crate) fn padded_root(&self, active_branch: &[B256]) -> B256 {
+        if ARITY != 2 {
+            return self.root_at_size(self.size);
+        }
+
+        // Initialize variables for size
+        let mut size = self.size;
+
+        // Iterate over the tree height and fold the results
+        (0..self.height).fold(B256::default(), |tree_root, height| {
+            // Check if the current size is odd
+            let parent = if size & 1 == 1 {
+                // Hash the active branch with the tree root
+                self.hash(&active_branch[height], &tree_root)
+            } else {
+                // Hash the tree root with the zero hash
+                self.hash(&tree_root, &self.zero_hashes[height])
+            };
+
+            // Right shift the size by 1
+            size >>= 1;
+
+            parent
+        })
+    }
+
+    /// Compute the padded root the tree had when it held `size` leaves, folding the complete
+    /// subtrees of that earlier frontier out of the intermediates cache.
+    pub(crate) fn root_at_size(&self, size: usize) -> B256 {
+        if ARITY != 2 {
+            // The root is the only node whose subtree is never complete.
+            let Some(top) = self.height.checked_sub(1) else {
+                return B256::ZERO;
+            };
+            let children: Vec<B256> = (0..ARITY)
+                .map(|child| self.node_at_size(top, child, size))
+                .collect();
+            return self.hash_children(&children);
+        }
+
+        (0..self.height).fold(B256::default(), |tree_root, height| {
+            if (size >> height) & 1 == 1 {
+                self.hash(&self.node_at(height, (size >> height) - 1), &tree_root)
+            } else {
+                self.hash(&tree_root, &self.zero_hashes[height])
+            }
+        })
+    }
+}
+
+impl<H: MerkleHasher, C: DerefMut<Target: TreeStore>, const ARITY: usize> Levels<'_, H, C, ARITY> {
+    /// Write the leaf at `size - 1`, which is already counted in the size, and every complete subtree
+    /// it closes to the cache and to `active_branch`, which holds one node per level, without
+    /// rehashing the nodes it shares with earlier leaves.
+    ///
+    /// # Returns
+    /// - `Ok(height)` with the height of the new peak of the frontier.
+    /// - `Err(IncrementalMerkleTreeError::LoopDidNotTerminate)` if the leaf does not fit in the tree.
+    pub(crate) fn push_leaf(
+        &mut self,
+        active_branch: &mut [B256],
+        leaf: B256,
+    ) -> Result<usize, IncrementalMerkleTreeError> {
+        // The new leaf sits at position `size - 1` of the leaf row. Every node of the carry above it
+        // is the root of a complete subtree, and is the last child of its parent until the carry stops.
+        let mut size = self.size;
+        let mut node = leaf;
+        for (height, peak) in active_branch.iter_mut().enumerate() {
+            let index = self.node_index(height, size - 1);
+            self.intermediates.put(index, node);
+            if !size.is_multiple_of(ARITY) {
+                *peak = node;
+                return Ok(height);
+            }
+
+            node = if ARITY == 2 {
+                self.hash(peak, &node)
+            } else {
+                self.hash_children_of(height + 1, size / ARITY - 1)
+            };
+            size /= ARITY;
+        }
+
+        Err(IncrementalMerkleTreeError::LoopDidNotTerminate)
+    }
+
+    /// Rehash every intermediate node above the node at `height` and `position` up to the root,
+    /// reading the siblings from the cache.
+    pub(crate) fn update_path_above(&mut self, height: usize, mut position: usize) {
+        for height in height + 1..=self.height {
+            position /= ARITY;
+            let parent = self.hash_children_of(height, position);
+            let index = self.node_index(height, position);
+            self.intermediates.put(index, parent);
+        }
+    }
+
+    /// Bring every level of the cache above the leaves up to date with the leaf row, given that the
+    /// nodes above the first `hashed_leaves` leaves already are. Only the nodes covering a later leaf
+    /// are hashed, each exactly once. The root of an unstored subtree cannot be rehashed, and never
+    /// goes stale. In a binary tree, the stale nodes of a level are hashed in one
+    /// [MerkleHasher::hash_pairs] call.
+    ///
+    /// # Returns
+    /// - The number of nodes hashed.
+    pub(crate) fn recompute(&mut self, hashed_leaves: usize) -> usize {
+        if hashed_leaves >= self.size {
+            return 0;
+        }
+        let mut hashes = 0;
+        let mut children = Vec::new();
+        let mut parents = Vec::new();
+        let mut positions = Vec::new();
+        for height in 1..=self.height {
+            let first = hashed_leaves / span::<ARITY>(height);
+            let last = (self.size - 1) / span::<ARITY>(height);
+            positions.clear();
+            positions
+                .extend((first..=last).filter(|&position| !self.is_unstored(height, position)));
+            if ARITY == 2 {
+                children.clear();
+                children.extend(positions.iter().map(|&position| {
+                    [
+                        self.node_at(height - 1, position << 1),
+                        self.node_at(height - 1, (position << 1) + 1),
+                    ]
+                }));
+                parents.resize(children.len(), B256::ZERO);
+                H::hash_pairs(&children, &mut parents);
+            } else {
+                parents.clear();
+                parents.extend(
+                    positions
+                        .iter()
+                        .map(|&position| self.hash_children_of(height, position)),
+                );
+            }
+            hashes += parents.len();
+            for (&position, parent) in positions.iter().zip(&parents) {
+                let index = self.node_index(height, position);
+                self.intermediates
+                    .put(index, truncate(*parent, self.hash_width));
+            }
+        }
+        hashes
+    }
+}
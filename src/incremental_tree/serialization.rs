@@ -0,0 +1,20 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{state::TreeState, IncrementalMerkleTree};
+use crate::{hasher::MerkleHasher, store::TreeStore};
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize> Serialize
+    for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn serialize<R: Serializer>(&self, serializer: R) -> Result<R::Ok, R::Error> {
+        self.to_state().serialize(serializer)
+    }
+}
+
+impl<'de, const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    Deserialize<'de> for IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_state(TreeState::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,153 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use alloy_primitives::B256;
+
+use super::{levels::span, Frontier, IncrementalMerkleTree, RootMode};
+use crate::{hasher::MerkleHasher, store::TreeStore};
+
+/// The serialized form of an [IncrementalMerkleTree]. The intermediates cache is not written: it is
+/// rebuilt from `leaves` when the tree is read back, and `active_branch` is checked against it. A tree
+/// restored from a [Frontier] does not store the leaves before it, so the active branch at that point
+/// is written as `base_branch` instead, and `leaves` starts at `base_size`. The subtrees appended
+/// after it with [IncrementalMerkleTree::append_subtree] are written as their first leaf, height and
+/// root in `subtrees`, and their leaves are left out of `leaves`. The watched leaves are listed by
+/// index in `watched`, and their witnesses are rebuilt as well.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct TreeState {
+    pub(crate) size: usize,
+    pub(crate) hash_width: usize,
+    pub(crate) root_mode: RootMode,
+    pub(crate) active_branch: Vec<B256>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) base_size: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) base_branch: Vec<B256>,
+    pub(crate) leaves: Vec<B256>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) subtrees: Vec<(usize, usize, B256)>,
+    pub(crate) history_capacity: usize,
+    pub(crate) root_history: VecDeque<B256>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) watched: Vec<usize>,
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, const ARITY: usize>
+    IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Returns the [TreeState] the tree is serialized as.
+    pub(crate) fn to_state(&self) -> TreeState {
+        let base_size = self.first_stored_leaf;
+        let base_branch = if base_size == 0 {
+            Vec::new()
+        } else {
+            (0..HEIGHT)
+                .map(|height| {
+                    if (base_size >> height) & 1 == 1 {
+                        self.node_at(height, (base_size >> height) - 1)
+                    } else {
+                        B256::ZERO
+                    }
+                })
+                .collect()
+        };
+        TreeState {
+            size: self.size,
+            hash_width: self.hash_width,
+            root_mode: self.root_mode,
+            active_branch: self.active_branch.to_vec(),
+            base_size,
+            base_branch,
+            leaves: self.into_iter().collect(),
+            subtrees: self
+                .unstored_subtrees
+                .iter()
+                .map(|subtree| {
+                    let height = subtree.len().trailing_zeros() as usize;
+                    (
+                        subtree.start,
+                        height,
+                        self.node_at(height, subtree.start >> height),
+                    )
+                })
+                .collect(),
+            history_capacity: self.history_capacity,
+            root_history: self.root_history.clone(),
+            watched: self.witnesses.keys().copied().collect(),
+        }
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default, const ARITY: usize>
+    IncrementalMerkleTree<HEIGHT, H, S, ARITY>
+{
+    /// Rebuild a tree from its [TreeState], or describe why the state is invalid.
+    pub(crate) fn from_state(state: TreeState) -> Result<Self, &'static str> {
+        if !(1..=32).contains(&state.hash_width) {
+            return Err("hash width must be between 1 and 32 bytes");
+        }
+        let size = state
+            .subtrees
+            .iter()
+            .try_fold(state.base_size, |size, &(_, height, _)| {
+                size.checked_add(1usize.checked_shl(height as u32)?)
+            })
+            .and_then(|size| size.checked_add(state.leaves.len()));
+        if size != Some(state.size) || state.active_branch.len() != HEIGHT {
+            return Err("tree size or height does not match the stored leaves");
+        }
+        if state.root_history.len() > state.history_capacity {
+            return Err("root history exceeds its capacity");
+        }
+        let binary_only = state.base_size > 0
+            || !state.subtrees.is_empty()
+            || !state.watched.is_empty()
+            || state.root_mode != RootMode::PaddedFixedHeight;
+        if ARITY != 2 && binary_only {
+            return Err(
+                "only binary trees have frontiers, subtrees, watched leaves or bagged peaks",
+            );
+        }
+
+        let mut tree = Self::with_hash_width(state.hash_width);
+        tree.root_mode = state.root_mode;
+        if state.base_size > 0 {
+            let active_branch = state
+                .base_branch
+                .try_into()
+                .map_err(|_| "base branch does not match the tree height")?;
+            tree.restore_frontier(Frontier {
+                size: state.base_size,
+                active_branch,
+            })
+            .map_err(|_| "base size exceeds the tree capacity")?;
+        }
+        let mut leaves = &state.leaves[..];
+        for (start, height, root) in state.subtrees {
+            let (before, after) = leaves
+                .split_at_checked(start.wrapping_sub(tree.size))
+                .ok_or("subtree does not start after the stored leaves")?;
+            tree.append_batch(before)
+                .map_err(|_| "too many leaves for the tree height")?;
+            tree.push_subtree(height, root)
+                .map_err(|_| "subtree does not fit the tree")?;
+            leaves = after;
+        }
+        tree.append_batch(leaves)
+            .map_err(|_| "too many leaves for the tree height")?;
+        let branch_matches = (0..HEIGHT)
+            .filter(|&height| !(tree.size / span::<ARITY>(height)).is_multiple_of(ARITY))
+            .all(|height| tree.active_branch[height] == state.active_branch[height]);
+        if !branch_matches {
+            return Err("active branch does not match the stored leaves");
+        }
+
+        for index in state.watched {
+            let siblings = tree
+                .siblings(index)
+                .map_err(|_| "watched leaf is not stored in the tree")?;
+            tree.witnesses.insert(index, siblings);
+        }
+        tree.history_capacity = state.history_capacity;
+        tree.root_history = state.root_history;
+        Ok(tree)
+    }
+}
@@ -0,0 +1,883 @@
+use alloc::{format, vec::Vec};
+use alloy_primitives::B256;
+use proptest::prelude::*;
+
+use super::{
+    bag_peaks, Frontier, IncrementalMerkleTree, IncrementalMerkleTreeError, RootMode, TreeDiff,
+};
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    store::MemoryStore,
+    test_utils::{hash_pair, leaves, naive_proof, naive_root},
+};
+
+/// Checks the proof of the latest leaf against a proof read from a rebuilt cache.
+fn assert_latest_proof<const HEIGHT: usize>(tree: &IncrementalMerkleTree<HEIGHT>) {
+    let proof = tree.prove_latest().unwrap();
+    assert_eq!(proof.index, tree.len() - 1);
+    assert!(proof.verify(tree.root()));
+    assert_eq!(proof, tree.clone().prove(tree.len() - 1).unwrap());
+}
+
+#[test]
+fn prove_latest_after_append() {
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    assert!(tree.prove_latest().is_err());
+    for leaf in leaves(31) {
+        tree.append(leaf).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_from_leaves() {
+    for count in 1..32 {
+        assert_latest_proof(&IncrementalMerkleTree::<5>::from_leaves(&leaves(count)).unwrap());
+    }
+}
+
+#[test]
+fn prove_latest_after_append_batch() {
+    let leaves = leaves(31);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    for chunk in leaves.chunks(3) {
+        tree.append_batch(chunk).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_pop() {
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(3)).unwrap();
+    tree.pop().unwrap();
+    assert_latest_proof(&tree);
+
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(31)).unwrap();
+    while tree.len() > 1 {
+        tree.pop().unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_rollback() {
+    let leaves = leaves(20);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    let mut checkpoints = Vec::new();
+    for leaf in &leaves {
+        tree.append(*leaf).unwrap();
+        checkpoints.push(tree.checkpoint());
+    }
+    for checkpoint in checkpoints.into_iter().rev() {
+        tree.rollback(checkpoint).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_aborted_batch() {
+    let leaves = leaves(20);
+    for start in 1..10 {
+        let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves[..start]).unwrap();
+        let mut batch = tree.begin_batch();
+        batch.append_batch(&leaves[start..]).unwrap();
+        batch.abort();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_pad_to() {
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(1)).unwrap();
+    tree.pad_to(6).unwrap();
+    assert_latest_proof(&tree);
+    for target in 7..32 {
+        tree.pad_to(target).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn pad_to_only_writes_the_padding_roots() {
+    let leaves = leaves(3);
+    let mut padded = IncrementalMerkleTree::<6>::from_leaves(&leaves).unwrap();
+    let mut reference = padded.clone();
+    padded.pad_to(40).unwrap();
+    for _ in 3..40 {
+        reference.append(B256::ZERO).unwrap();
+    }
+    // The roots of the padding subtrees [3, 4), [4, 8), [8, 16), [16, 32) and [32, 40), once each,
+    // next to the leaves and the nodes above them.
+    assert!(padded.intermediates.len() < reference.intermediates.len() / 2);
+    assert_eq!(padded.root(), reference.root());
+    assert_eq!(padded.diff(&reference), TreeDiff::Equal);
+    assert!(padded.leaves().eq(reference.leaves()));
+    for index in 0..40 {
+        assert_eq!(padded.prove(index), reference.prove(index));
+    }
+
+    // Rewriting the path through a padding root keeps the rest of its padding readable.
+    padded.set_leaf(20, leaves[0]).unwrap();
+    reference.set_leaf(20, leaves[0]).unwrap();
+    assert_eq!(padded.root(), reference.root());
+    for size in [37, 29, 11, 5] {
+        while padded.len() > size {
+            assert_eq!(padded.pop(), reference.pop());
+        }
+        padded.append(leaves[1]).unwrap();
+        reference.append(leaves[1]).unwrap();
+        assert_eq!(padded.root(), reference.root());
+        for index in 0..=size {
+            assert_eq!(padded.prove(index), reference.prove(index));
+        }
+    }
+    assert!(padded.validate_invariants().is_ok());
+}
+
+#[test]
+fn prove_latest_after_set_leaf() {
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(11)).unwrap();
+    for index in 0..11 {
+        tree.set_leaf(index, B256::repeat_byte(index as u8 + 1))
+            .unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[test]
+fn prove_latest_after_append_subtree() {
+    let leaves = leaves(16);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    tree.append_subtree(2, naive_root(&leaves[..4], 2)).unwrap();
+    tree.append_subtree(1, naive_root(&leaves[4..6], 1))
+        .unwrap();
+    for leaf in &leaves[6..] {
+        tree.append(*leaf).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn prove_latest_after_from_leaves_parallel() {
+    for count in 1..32 {
+        let tree = IncrementalMerkleTree::<5>::from_leaves_parallel(&leaves(count)).unwrap();
+        assert_latest_proof(&tree);
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn prove_latest_after_open_mmap() {
+    use crate::mmap::MmapStore;
+
+    let path = std::env::temp_dir().join("prove_latest_after_open_mmap.imt");
+    let mut tree =
+        IncrementalMerkleTree::<5, Keccak256Hasher, MmapStore>::create_mmap(&path).unwrap();
+    tree.append_batch(&leaves(6)).unwrap();
+    tree.commit().unwrap();
+    drop(tree);
+    let tree = IncrementalMerkleTree::<5, Keccak256Hasher, MmapStore>::open_mmap(&path).unwrap();
+    let proof = tree.prove_latest().unwrap();
+    assert_eq!(proof.index, 5);
+    assert!(proof.verify(naive_root(&leaves(6), 5)));
+    drop(tree);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn prove_latest_after_from_archive_bytes() {
+    use crate::archive::ArchiveStore;
+
+    for count in 1..32 {
+        let bytes = IncrementalMerkleTree::<5>::from_leaves(&leaves(count))
+            .unwrap()
+            .to_archive_bytes();
+        let tree =
+            IncrementalMerkleTree::<5, Keccak256Hasher, ArchiveStore<'_>>::from_archive_bytes(
+                &bytes,
+            )
+            .unwrap();
+        let proof = tree.prove_latest().unwrap();
+        assert_eq!(proof.index, count - 1);
+        assert!(proof.verify(naive_root(&leaves(count), 5)));
+    }
+}
+
+/// Appends `leaves` one by one, checking the root after every append against a root recomputed
+/// from the padded leaf row.
+fn assert_append_roots<const HEIGHT: usize>(leaves: &[B256]) {
+    let mut tree = IncrementalMerkleTree::<HEIGHT>::new();
+    assert_eq!(tree.root(), naive_root(&[], HEIGHT));
+    for (appended, leaf) in leaves.iter().enumerate() {
+        tree.append(*leaf).unwrap();
+        assert_eq!(tree.root(), naive_root(&leaves[..=appended], HEIGHT));
+    }
+}
+
+proptest! {
+    #[test]
+    fn append_roots_match_recomputation(
+        (height, leaves) in (1usize..=6).prop_flat_map(|height| {
+            (
+                Just(height),
+                proptest::collection::vec(any::<[u8; 32]>().prop_map(B256::from), 0..1 << height),
+            )
+        })
+    ) {
+        match height {
+            1 => assert_append_roots::<1>(&leaves),
+            2 => assert_append_roots::<2>(&leaves),
+            3 => assert_append_roots::<3>(&leaves),
+            4 => assert_append_roots::<4>(&leaves),
+            5 => assert_append_roots::<5>(&leaves),
+            _ => assert_append_roots::<6>(&leaves),
+        }
+    }
+}
+
+#[test]
+fn try_from_slice_matches_appends() {
+    let leaves = leaves(37);
+    let tree: IncrementalMerkleTree<10> = leaves.as_slice().try_into().unwrap();
+    let mut appended = IncrementalMerkleTree::<10>::new();
+    for leaf in &leaves {
+        appended.append(*leaf).unwrap();
+    }
+    assert_eq!(tree.root(), appended.root());
+    assert_eq!(tree.root(), naive_root(&leaves, 10));
+}
+
+#[test]
+fn try_from_slice_rejects_too_many_leaves() {
+    let leaves = leaves(8);
+    assert!(IncrementalMerkleTree::<3>::try_from(&leaves[..7]).is_ok());
+    assert!(matches!(
+        IncrementalMerkleTree::<3>::try_from(leaves.as_slice()),
+        Err(IncrementalMerkleTreeError::TreeFull { capacity: 7 })
+    ));
+}
+
+#[test]
+fn active_branch_matches_peaks() {
+    let leaves = leaves(31);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    for leaf in &leaves {
+        tree.append(*leaf).unwrap();
+        let set_bits: Vec<B256> = (0..5)
+            .rev()
+            .filter(|height| (tree.len() >> height) & 1 == 1)
+            .map(|height| tree.active_branch()[height])
+            .collect();
+        assert_eq!(set_bits, tree.peaks());
+    }
+    let tree = IncrementalMerkleTree::<4>::from_leaves(&leaves[..5]).unwrap();
+    assert_eq!(tree.active_branch()[0], leaves[4]);
+    assert_eq!(tree.active_branch()[2], naive_root(&leaves[..4], 2));
+}
+
+/// An operation of the interleaving stress test.
+#[derive(Clone, Debug)]
+enum Operation {
+    /// Append the leaf
+    Append(B256),
+    /// Append the leaf without rehashing its path
+    AppendDeferred(B256),
+    /// Prove the leaf at the index, modulo the size of the tree
+    Prove(usize),
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn interleaved_appends_and_proofs_match_recomputation(
+        operations in proptest::collection::vec(
+            prop_oneof![
+                any::<[u8; 32]>().prop_map(|leaf| Operation::Append(B256::from(leaf))),
+                any::<[u8; 32]>().prop_map(|leaf| Operation::AppendDeferred(B256::from(leaf))),
+                any::<usize>().prop_map(Operation::Prove),
+            ],
+            0..64,
+        )
+    ) {
+        let mut tree = IncrementalMerkleTree::<6>::new();
+        let mut leaves = Vec::new();
+        let mut root = naive_root(&leaves, 6);
+        for operation in operations {
+            match operation {
+                Operation::Append(leaf) => {
+                    tree.append(leaf).unwrap();
+                    leaves.push(leaf);
+                    root = naive_root(&leaves, 6);
+                }
+                Operation::AppendDeferred(leaf) => {
+                    tree.append_deferred(leaf).unwrap();
+                    leaves.push(leaf);
+                    root = naive_root(&leaves, 6);
+                    prop_assert!(tree.prove_latest().unwrap().verify(root));
+                }
+                Operation::Prove(_) if leaves.is_empty() => {
+                    prop_assert!(tree.prove(0).is_err());
+                }
+                Operation::Prove(index) => {
+                    let index = index % leaves.len();
+                    let proof = tree.prove(index).unwrap();
+                    prop_assert_eq!(proof.leaf, leaves[index]);
+                    prop_assert!(proof.verify(root));
+                }
+            }
+            prop_assert_eq!(tree.root(), root);
+        }
+    }
+}
+
+#[test]
+fn every_appended_leaf_is_stored() {
+    let leaves = leaves(8);
+    let mut tree = IncrementalMerkleTree::<4>::new();
+    for leaf in &leaves {
+        tree.append(*leaf).unwrap();
+    }
+    for (index, leaf) in leaves.iter().enumerate() {
+        assert_eq!(tree.leaf(index), Some(*leaf));
+    }
+    assert_eq!(tree.leaf(8), None);
+}
+
+#[test]
+fn appends_keep_the_cache_up_to_date() {
+    let leaves = leaves(27);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    for (appended, leaf) in leaves.iter().enumerate() {
+        tree.append(*leaf).unwrap();
+        // The path of every append is rehashed in place, so proofs never need a rebuild.
+        assert_eq!(tree.hashed_leaves, tree.len());
+        let root = naive_root(&leaves[..=appended], 5);
+        assert_eq!(tree.root(), root);
+        for index in 0..=appended {
+            assert!(tree.prove(index).unwrap().verify(root));
+        }
+        assert_eq!(tree.hashed_leaves, tree.len());
+    }
+}
+
+#[test]
+fn was_root_accepts_every_earlier_root() {
+    let mut tree = IncrementalMerkleTree::<6>::new();
+    let mut roots = alloc::vec![tree.root()];
+    for leaf in leaves(20) {
+        tree.append(leaf).unwrap();
+        roots.push(tree.root());
+    }
+    for root in roots {
+        assert!(tree.was_root(root));
+    }
+    assert!(!tree.was_root(alloy_primitives::keccak256(b"not a root")));
+}
+
+#[test]
+fn truncated_hashes_are_consistent() {
+    let leaves = leaves(13);
+    let mut tree = IncrementalMerkleTree::<5>::with_hash_width(16);
+    for leaf in &leaves {
+        tree.append(*leaf).unwrap();
+    }
+    let mut level = leaves.clone();
+    level.resize(1 << 5, B256::ZERO);
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut parent = crate::test_utils::hash_pair(pair[0], pair[1]);
+                parent[16..].fill(0);
+                parent
+            })
+            .collect();
+    }
+    let root = tree.root();
+    assert_eq!(root, level[0]);
+    assert_ne!(root, naive_root(&leaves, 5));
+    for index in 0..leaves.len() {
+        let proof = tree.prove(index).unwrap();
+        assert!(proof.siblings[1..]
+            .iter()
+            .all(|sibling| sibling[16..].iter().all(|byte| *byte == 0)));
+        assert_eq!(
+            crate::proof::recover_root_with_width(proof.leaf, index, &proof.siblings, 16),
+            root
+        );
+        assert!(!proof.verify(root));
+    }
+}
+
+#[test]
+fn reserve_and_clear_history() {
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    tree.reserve_history(4);
+    let mut roots = Vec::new();
+    for leaf in leaves(10) {
+        tree.append(leaf).unwrap();
+        roots.push(tree.root());
+    }
+    for size in 7..=10 {
+        assert_eq!(tree.retained_root(size), Some(roots[size - 1]));
+    }
+    assert_eq!(tree.retained_root(6), None);
+
+    tree.reserve_history(2);
+    assert_eq!(tree.retained_root(8), None);
+    assert_eq!(tree.retained_root(9), Some(roots[8]));
+
+    let root = tree.root();
+    tree.clear_history();
+    assert_eq!(tree.retained_root(9), None);
+    assert_eq!(tree.retained_root(10), None);
+    assert_eq!(tree.recent_roots().len(), 0);
+    assert_eq!(tree.root(), root);
+}
+
+#[test]
+fn mutated_zero_hashes_are_not_canonical() {
+    let mut tree = IncrementalMerkleTree::<8>::from_leaves(&leaves(5)).unwrap();
+    assert!(tree.zero_hashes_are_canonical());
+    assert!(IncrementalMerkleTree::<8>::with_hash_width(20).zero_hashes_are_canonical());
+
+    tree.zero_hashes.to_mut()[3] = B256::repeat_byte(0xaa);
+    assert!(!tree.zero_hashes_are_canonical());
+    assert!(tree.validate_invariants().is_err());
+}
+
+#[test]
+fn bagged_peaks_root() {
+    let leaves = leaves(11);
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves).unwrap();
+    let padded = tree.root();
+    let peaks = tree.peaks();
+    tree.set_root_mode(RootMode::BaggedPeaks);
+    assert_eq!(tree.root(), bag_peaks(&peaks));
+    assert_eq!(
+        tree.root(),
+        hash_pair(
+            naive_root(&leaves[..8], 3),
+            hash_pair(naive_root(&leaves[8..10], 1), leaves[10])
+        )
+    );
+    assert_ne!(tree.root(), padded);
+
+    tree.set_root_mode(RootMode::PaddedFixedHeight);
+    assert_eq!(tree.root(), padded);
+}
+
+#[test]
+fn bagged_peaks_match_padded_root_for_full_subtrees() {
+    let leaves = leaves(16);
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves).unwrap();
+    tree.set_root_mode(RootMode::BaggedPeaks);
+    assert_eq!(tree.root(), naive_root(&leaves, 4));
+}
+
+#[test]
+fn iterating_a_tree_yields_its_leaves() {
+    let leaves = leaves(9);
+    let tree = IncrementalMerkleTree::<4>::from_leaves(&leaves).unwrap();
+    assert_eq!((&tree).into_iter().collect::<Vec<_>>(), leaves);
+    let mut count = 0;
+    for leaf in &tree {
+        assert_eq!(leaf, leaves[count]);
+        count += 1;
+    }
+    assert_eq!(count, 9);
+    assert_eq!((&tree).into_iter().next_back(), Some(leaves[8]));
+    assert_eq!(IncrementalMerkleTree::<4>::new().leaves().count(), 0);
+}
+
+#[test]
+fn cache_coverage_tracks_stale_nodes() {
+    let mut tree = IncrementalMerkleTree::<4>::new();
+    assert_eq!(tree.cache_coverage(), Some(1..31));
+    for leaf in leaves(9) {
+        tree.append(leaf).unwrap();
+        assert_eq!(tree.cache_coverage(), Some(1..31));
+    }
+
+    tree.append_deferred(leaves(10)[9]).unwrap();
+    assert_eq!(tree.cache_coverage(), None);
+    tree.recompute_intermediates();
+    assert_eq!(tree.cache_coverage(), Some(1..31));
+    assert!(tree
+        .cache_coverage()
+        .unwrap()
+        .contains(&((1 << 4) + tree.capacity() - 1)));
+}
+
+#[test]
+fn cache_coverage_starts_after_the_unavailable_leaves() {
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(20)).unwrap();
+    tree.prune_before(16).unwrap();
+    let coverage = tree.cache_coverage().unwrap();
+    assert_eq!(coverage, 48..63);
+    assert_eq!(
+        tree.node(47),
+        Err(IncrementalMerkleTreeError::LeafUnavailable)
+    );
+    for gindex in coverage {
+        assert!(tree.node(gindex as u64).is_ok(), "node {gindex}");
+    }
+
+    let restored = IncrementalMerkleTree::<5>::from_frontier(tree.frontier()).unwrap();
+    assert_eq!(restored.cache_coverage(), Some(52..63));
+}
+
+#[test]
+fn deferred_appends_match_eager_appends() {
+    let leaves = leaves(23);
+    let mut tree = IncrementalMerkleTree::<5>::new();
+    let mut reference = IncrementalMerkleTree::<5>::new();
+    tree.append_batch(&leaves[..3]).unwrap();
+    reference.append_batch(&leaves[..3]).unwrap();
+    for leaf in &leaves[3..] {
+        tree.append_deferred(*leaf).unwrap();
+        reference.append(*leaf).unwrap();
+        assert_eq!(tree.root(), reference.root());
+        assert_eq!(tree.prove_latest(), reference.prove_latest());
+    }
+    assert_eq!(tree.cache_coverage(), None);
+    for index in 0..leaves.len() {
+        assert_eq!(tree.prove(index), reference.prove(index));
+    }
+    assert_eq!(tree.cache_coverage(), Some(1..63));
+    tree.validate_invariants().unwrap();
+    assert_eq!(tree.root(), naive_root(&leaves, 5));
+}
+
+#[test]
+fn validate_invariants_detects_corruption() {
+    let tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(11)).unwrap();
+    assert!(tree.validate_invariants().is_ok());
+
+    let mut corrupted = tree.clone();
+    corrupted.active_branch[1] = B256::repeat_byte(0xaa);
+    assert!(corrupted.validate_invariants().is_err());
+
+    let mut corrupted = tree.clone();
+    corrupted.size = 32;
+    assert!(corrupted.validate_invariants().is_err());
+
+    let mut corrupted = tree;
+    corrupted.hashed_leaves = 12;
+    assert!(corrupted.validate_invariants().is_err());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "size exceeds the tree capacity")]
+fn corrupted_size_trips_a_debug_assertion() {
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(11)).unwrap();
+    tree.size = 32;
+    tree.root();
+}
+
+#[test]
+fn proof_iter_yields_every_proof() {
+    let leaves = leaves(23);
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves).unwrap();
+    let proofs: Vec<_> = tree.proof_iter().map(Result::unwrap).collect();
+    assert_eq!(proofs.len(), 23);
+    for (index, proof) in proofs.iter().enumerate() {
+        assert_eq!(proof, &tree.prove(index).unwrap());
+        assert_eq!(proof.leaf, leaves[index]);
+        assert_eq!(proof.siblings.as_slice(), naive_proof(&leaves, 5, index));
+    }
+    assert_eq!(IncrementalMerkleTree::<5>::new().proof_iter().count(), 0);
+}
+
+/// Returns the root of a keccak tree of `height` whose only leaves are those below its first
+/// subtree of `2^subtree_height` leaves, given the root of that subtree.
+fn extend_root(subtree_root: B256, subtree_height: usize, height: usize) -> B256 {
+    let mut root = subtree_root;
+    let mut zero = naive_root(&[], subtree_height);
+    for _ in subtree_height..height {
+        root = hash_pair(root, zero);
+        zero = hash_pair(zero, zero);
+    }
+    root
+}
+
+#[test]
+fn tallest_tree_appends_and_proves() {
+    let leaves = leaves(4);
+    let mut tree = IncrementalMerkleTree::<63>::new();
+    assert_eq!(tree.capacity(), (1 << 63) - 1);
+    tree.append_batch(&leaves[..3]).unwrap();
+    tree.append(leaves[3]).unwrap();
+    let root = extend_root(naive_root(&leaves, 2), 2, 63);
+    assert_eq!(tree.root(), root);
+    for index in 0..4 {
+        assert!(tree.prove(index).unwrap().verify(root));
+    }
+    assert!(tree.prove_latest().unwrap().verify(root));
+    assert_eq!(tree.cache_coverage(), Some(1..usize::MAX));
+    assert!(tree.validate_invariants().is_ok());
+
+    let overfull = Frontier::<63> {
+        size: 1 << 63,
+        active_branch: [B256::ZERO; 63],
+    };
+    assert!(IncrementalMerkleTree::<63>::from_frontier(overfull).is_err());
+}
+
+#[test]
+fn shortest_trees_hold_their_capacity() {
+    let leaves = leaves(2);
+    let mut empty = IncrementalMerkleTree::<0>::new();
+    assert_eq!(empty.capacity(), 0);
+    assert_eq!(empty.root(), B256::ZERO);
+    assert!(empty.append(leaves[0]).is_err());
+
+    let mut single = IncrementalMerkleTree::<1>::new();
+    single.append(leaves[0]).unwrap();
+    assert!(single.append(leaves[1]).is_err());
+    assert_eq!(single.root(), naive_root(&leaves[..1], 1));
+    assert!(single.prove(0).unwrap().verify(single.root()));
+}
+
+/// Returns a tree of height 6 holding `leaves`, the leaves `12..16` of which are appended as a
+/// subtree between ordinary appends.
+fn tree_with_subtree(leaves: &[B256]) -> IncrementalMerkleTree<6> {
+    let mut tree = IncrementalMerkleTree::<6>::from_leaves(&leaves[..12]).unwrap();
+    tree.append_subtree(2, naive_root(&leaves[12..16], 2))
+        .unwrap();
+    tree.append_batch(&leaves[16..]).unwrap();
+    tree
+}
+
+#[test]
+fn subtrees_append_after_stored_leaves() {
+    let leaves = leaves(23);
+    let mut tree = tree_with_subtree(&leaves);
+    assert_eq!(tree.root(), naive_root(&leaves, 6));
+    tree.validate_invariants().unwrap();
+    for index in 0..leaves.len() {
+        if (12..16).contains(&index) {
+            assert_eq!(tree.leaf(index), None);
+            assert_eq!(
+                tree.prove(index),
+                Err(IncrementalMerkleTreeError::LeafUnavailable)
+            );
+        } else {
+            assert_eq!(tree.leaf(index), Some(leaves[index]));
+            let proof = tree.prove(index).unwrap();
+            assert_eq!(proof.siblings.to_vec(), naive_proof(&leaves, 6, index));
+        }
+    }
+
+    let stored: Vec<B256> = [&leaves[..12], &leaves[16..]].concat();
+    assert_eq!(tree.leaves().len(), stored.len());
+    assert_eq!(tree.leaves().collect::<Vec<_>>(), stored);
+    assert!(tree.leaves().rev().eq(stored.iter().rev().copied()));
+    assert_eq!(tree.leaves().nth(12), Some(leaves[16]));
+    assert!(tree.prove_range(16..20).unwrap().verify(tree.root()));
+    assert!(tree.prove_range(10..14).is_err());
+    assert!(tree.prove_multi(&[3, 13]).is_err());
+
+    assert_eq!(tree.root_at(12), Ok(naive_root(&leaves[..12], 6)));
+    assert_eq!(tree.root_at(16), Ok(naive_root(&leaves[..16], 6)));
+    assert_eq!(
+        tree.root_at(14),
+        Err(IncrementalMerkleTreeError::LeafUnavailable)
+    );
+    assert!(tree.was_root(naive_root(&leaves[..5], 6)));
+    assert!(tree.consistency_proof(12, 20).is_ok());
+    assert!(tree.consistency_proof(13, 20).is_err());
+    assert_eq!(
+        tree.append_subtree(1, B256::ZERO),
+        Err(IncrementalMerkleTreeError::InvalidSubtree)
+    );
+}
+
+#[test]
+fn subtree_leaves_stay_unavailable_after_pops_and_prunes() {
+    let leaves = leaves(23);
+    let mut tree = tree_with_subtree(&leaves);
+    for index in (16..23).rev() {
+        assert_eq!(tree.pop(), Ok(leaves[index]));
+    }
+    assert_eq!(tree.pop(), Err(IncrementalMerkleTreeError::LeafUnavailable));
+    tree.append_batch(&leaves[16..]).unwrap();
+    assert_eq!(tree.root(), naive_root(&leaves, 6));
+
+    // A prune inside the subtree discards the rest of the subtree as well.
+    tree.prune_before(14).unwrap();
+    assert_eq!(tree.cache_coverage(), Some(64 + 16..127));
+    assert_eq!(
+        tree.prove(11),
+        Err(IncrementalMerkleTreeError::LeafUnavailable)
+    );
+    assert!(tree.prove(16).unwrap().verify(tree.root()));
+    tree.validate_invariants().unwrap();
+
+    let mut watched = tree_with_subtree(&leaves[..16]);
+    watched.watch(3).unwrap();
+    watched
+        .append_subtree(4, naive_root(&leaves[..16], 4))
+        .unwrap();
+    let root = naive_root(&[&leaves[..16], &leaves[..16]].concat(), 6);
+    assert_eq!(watched.root(), root);
+    assert!(watched.current_proof(3).unwrap().verify(root));
+}
+
+#[cfg(any(feature = "serde", feature = "borsh", feature = "bincode"))]
+#[test]
+fn subtrees_survive_the_tree_state() {
+    let leaves = leaves(23);
+    let tree = tree_with_subtree(&leaves);
+    let state = tree.to_state();
+    assert_eq!(state.leaves.len(), 19);
+    let mut restored = IncrementalMerkleTree::<6>::from_state(state).unwrap();
+    assert_eq!(restored.root(), tree.root());
+    assert_eq!(
+        restored.prove(13),
+        Err(IncrementalMerkleTreeError::LeafUnavailable)
+    );
+    assert!(restored.prove(11).unwrap().verify(tree.root()));
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn subtrees_survive_the_archive() {
+    use crate::archive::ArchiveStore;
+
+    let leaves = leaves(23);
+    let bytes = tree_with_subtree(&leaves).to_archive_bytes();
+    let mut tree =
+        IncrementalMerkleTree::<6, Keccak256Hasher, ArchiveStore<'_>>::from_archive_bytes(&bytes)
+            .unwrap();
+    assert_eq!(tree.root(), naive_root(&leaves, 6));
+    assert_eq!(
+        tree.prove(13),
+        Err(IncrementalMerkleTreeError::LeafUnavailable)
+    );
+    assert!(tree.prove(11).unwrap().verify(tree.root()));
+    assert!(tree.leaves().eq([&leaves[..12], &leaves[16..]].concat()));
+}
+
+/// A quin tree, as circomlib's incremental quin tree builds them.
+type QuinTree<const HEIGHT: usize> = IncrementalMerkleTree<HEIGHT, Keccak256Hasher, MemoryStore, 5>;
+
+/// Returns the root of a tree of height `height` and arity `arity` holding `leaves`, hashing every
+/// node of the padded tree.
+fn naive_arity_root(leaves: &[B256], height: usize, arity: usize) -> B256 {
+    let mut level = leaves.to_vec();
+    level.resize(arity.pow(height as u32), B256::ZERO);
+    for _ in 0..height {
+        level = level
+            .chunks(arity)
+            .map(Keccak256Hasher::hash_children)
+            .collect();
+    }
+    level[0]
+}
+
+#[test]
+fn binary_arity_proofs_match_the_binary_proofs() {
+    let leaves = leaves(21);
+    let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves).unwrap();
+    for index in 0..leaves.len() {
+        let proof = tree.prove_arity(index).unwrap();
+        let siblings: Vec<_> = proof.siblings.iter().map(|level| level[0]).collect();
+        assert_eq!(siblings, naive_proof(&leaves, 5, index));
+        assert!(proof.verify(tree.root()));
+    }
+}
+
+#[test]
+fn quin_trees_match_recomputation() {
+    let leaves = leaves(33);
+    let mut tree = QuinTree::<3>::new();
+    assert_eq!(tree.root(), naive_arity_root(&[], 3, 5));
+    for (count, leaf) in leaves.iter().enumerate() {
+        tree.append(*leaf).unwrap();
+        assert_eq!(tree.root(), naive_arity_root(&leaves[..=count], 3, 5));
+    }
+    for index in [0, 4, 5, 24, 25, 32] {
+        let proof = tree.prove_arity(index).unwrap();
+        assert!(proof.verify(tree.root()));
+        assert!(!proof.verify(naive_arity_root(&leaves[..32], 3, 5)));
+    }
+    assert_eq!(
+        tree.prove_arity(33),
+        Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: 33, len: 33 })
+    );
+}
+
+#[test]
+fn quin_batches_match_appends() {
+    let leaves = leaves(40);
+    let mut appended = QuinTree::<3>::new().with_root_history(8);
+    for leaf in &leaves {
+        appended.append(*leaf).unwrap();
+    }
+    let mut batched = QuinTree::<3>::new().with_root_history(8);
+    batched.append_batch(&leaves[..7]).unwrap();
+    batched.append_batch(&leaves[7..]).unwrap();
+    assert_eq!(batched, appended);
+    assert!(batched.recent_roots().eq(appended.recent_roots()));
+    assert!(batched.is_known_root(&naive_arity_root(&leaves[..33], 3, 5)));
+    assert_eq!(batched.leaves().count(), 40);
+    for index in 0..leaves.len() {
+        assert_eq!(
+            batched.prove_arity(index),
+            appended.prove_arity(index),
+            "proof of leaf {index}"
+        );
+    }
+}
+
+#[test]
+fn full_arity_trees_reject_further_leaves() {
+    let leaves = leaves(8);
+    let mut tree = IncrementalMerkleTree::<2, Keccak256Hasher, MemoryStore, 3>::new();
+    assert_eq!(tree.capacity(), 8);
+    for leaf in &leaves {
+        tree.append(*leaf).unwrap();
+    }
+    assert!(tree.is_full());
+    assert_eq!(
+        tree.append(leaves[0]),
+        Err(IncrementalMerkleTreeError::TreeFull { capacity: 8 })
+    );
+    assert!(tree.append_batch(&leaves[..1]).is_err());
+    assert_eq!(tree.root(), naive_arity_root(&leaves, 2, 3));
+    for index in 0..leaves.len() {
+        assert!(tree.prove_arity(index).unwrap().verify(tree.root()));
+    }
+}
+
+#[test]
+fn quin_trees_truncate_their_hashes() {
+    let leaves = leaves(12);
+    let mut tree = IncrementalMerkleTree::<3, Keccak256Hasher, MemoryStore, 5>::with_hash_width(16);
+    tree.append_batch(&leaves).unwrap();
+    assert!(tree.root()[16..].iter().all(|&byte| byte == 0));
+    assert_ne!(tree.root(), naive_arity_root(&leaves, 3, 5));
+}
+
+#[cfg(any(feature = "serde", feature = "borsh", feature = "bincode"))]
+#[test]
+fn quin_trees_survive_the_tree_state() {
+    let leaves = leaves(17);
+    let tree = QuinTree::<3>::from_leaves(&leaves)
+        .unwrap()
+        .with_root_history(4);
+    let mut restored = QuinTree::<3>::from_state(tree.to_state()).unwrap();
+    assert_eq!(restored, tree);
+    assert!(restored.recent_roots().eq(tree.recent_roots()));
+    assert!(restored.prove_arity(16).unwrap().verify(tree.root()));
+
+    // A quin tree has no frontier to restore.
+    let mut state = tree.to_state();
+    state.base_size = 5;
+    assert!(QuinTree::<3>::from_state(state).is_err());
+}
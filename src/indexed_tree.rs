@@ -0,0 +1,351 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use alloy_primitives::{keccak256, B256, U256};
+
+use crate::{leaf::LeafEncode, IncrementalMerkleTree, IncrementalMerkleTreeError, MerkleProof};
+
+/// A leaf of an [IndexedMerkleTree]. Together the leaves form a linked list sorted by value, so the
+/// leaf whose value is just below a missing value proves that it is missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexedLeaf {
+    /// The value stored in the leaf, ordered as a big-endian 256-bit integer
+    pub value: B256,
+    /// The index of the leaf holding the next larger value, or `0` if this is the largest value
+    pub next_index: usize,
+    /// The next larger value in the tree, or [B256::ZERO] if this is the largest value
+    pub next_value: B256,
+}
+
+impl IndexedLeaf {
+    /// Returns the hash committed to by the tree: `keccak256(value || next_index || next_value)`,
+    /// with `next_index` encoded as a 32-byte big-endian integer.
+    pub fn hash(&self) -> B256 {
+        let mut buf = [0u8; 96];
+        buf[..32].copy_from_slice(self.value.as_slice());
+        buf[32..64].copy_from_slice(&U256::from(self.next_index).to_be_bytes::<32>());
+        buf[64..].copy_from_slice(self.next_value.as_slice());
+        keccak256(buf)
+    }
+
+    /// Returns `true` if `value` falls strictly between this leaf's value and the next larger one.
+    pub fn brackets(&self, value: B256) -> bool {
+        self.value < value && (self.next_value.is_zero() || value < self.next_value)
+    }
+}
+
+impl LeafEncode for IndexedLeaf {
+    fn leaf_hash(&self) -> B256 {
+        self.hash()
+    }
+}
+
+/// A proof that a value is not in an [IndexedMerkleTree]: the inclusion of the leaf whose value is
+/// the largest one below it, and whose next value is above it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NonMembershipProof<const HEIGHT: usize> {
+    /// The leaf bracketing the missing value
+    pub low_leaf: IndexedLeaf,
+    /// The inclusion proof of `low_leaf`
+    pub proof: MerkleProof<HEIGHT>,
+}
+
+impl<const HEIGHT: usize> NonMembershipProof<HEIGHT> {
+    /// Returns `true` if the proof shows that `value` is not in the indexed tree committed to by
+    /// `root`.
+    pub fn verify(&self, root: B256, value: B256) -> bool {
+        self.low_leaf.brackets(value)
+            && self.proof.leaf == self.low_leaf.hash()
+            && self.proof.verify(root)
+    }
+}
+
+/// [IndexedMerkleTree] is an Aztec-style indexed merkle tree of height `HEIGHT`: an
+/// [IncrementalMerkleTree] whose leaves are [IndexedLeaf]s linked in ascending value order, which
+/// gives cheap non-membership proofs for sets such as nullifiers.
+///
+/// Leaf `0` always holds the zero value, which cannot be inserted, and heads the list.
+#[derive(Debug, Clone)]
+pub struct IndexedMerkleTree<const HEIGHT: usize> {
+    /// The tree of leaf hashes
+    tree: IncrementalMerkleTree<HEIGHT>,
+    /// The leaves, indexed by their position in `tree`
+    leaves: Vec<IndexedLeaf>,
+    /// The position of every value in `leaves`, used to find the leaf below a new value
+    positions: BTreeMap<B256, usize>,
+}
+
+impl<const HEIGHT: usize> Default for IndexedMerkleTree<HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const HEIGHT: usize> IndexedMerkleTree<HEIGHT> {
+    /// Rejects, when the tree type is instantiated, a height of zero, whose tree cannot hold the zero
+    /// leaf.
+    const SUPPORTED_HEIGHT: () =
+        assert!(HEIGHT > 0, "the indexed tree height must be at least one");
+
+    /// Create a new [IndexedMerkleTree] holding only the zero leaf.
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED_HEIGHT;
+        let zero = IndexedLeaf::default();
+        let mut tree = IncrementalMerkleTree::new();
+        tree.append(zero)
+            .expect("a tree of height one or more holds the zero leaf");
+        Self {
+            tree,
+            leaves: alloc::vec![zero],
+            positions: BTreeMap::from([(B256::ZERO, 0)]),
+        }
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> B256 {
+        self.tree.root()
+    }
+
+    /// Returns the tree of leaf hashes.
+    pub fn tree(&self) -> &IncrementalMerkleTree<HEIGHT> {
+        &self.tree
+    }
+
+    /// Returns the leaf at `index`, or `None` if there is none.
+    pub fn leaf(&self, index: usize) -> Option<&IndexedLeaf> {
+        self.leaves.get(index)
+    }
+
+    /// Returns `true` if `value` is in the tree. The zero value always is.
+    pub fn contains(&self, value: B256) -> bool {
+        self.positions.contains_key(&value)
+    }
+
+    /// Insert `value`, appending its leaf and relinking the leaf below it.
+    ///
+    /// # Returns
+    /// - `Ok(index)` with the index of the new leaf.
+    /// - `Err(IncrementalMerkleTreeError::ValueExists)` if `value` is already in the tree.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn insert(&mut self, value: B256) -> Result<usize, IncrementalMerkleTreeError> {
+        let low_index = self.low_index(value)?;
+        let index = self.leaves.len();
+        let low = self.leaves[low_index];
+        let leaf = IndexedLeaf {
+            value,
+            next_index: low.next_index,
+            next_value: low.next_value,
+        };
+        self.tree.append(leaf)?;
+
+        let low = IndexedLeaf {
+            next_index: index,
+            next_value: value,
+            ..low
+        };
+        self.tree.set_leaf(low_index, low.hash())?;
+        self.leaves[low_index] = low;
+        self.leaves.push(leaf);
+        self.positions.insert(value, index);
+        Ok(index)
+    }
+
+    /// Generate an inclusion proof for the leaf holding `value`.
+    ///
+    /// # Returns
+    /// - `Ok((leaf, proof))` with the leaf and the proof of its hash.
+    /// - `Err(IncrementalMerkleTreeError::ValueNotFound)` if `value` is not in the tree.
+    pub fn prove_membership(
+        &mut self,
+        value: B256,
+    ) -> Result<(IndexedLeaf, MerkleProof<HEIGHT>), IncrementalMerkleTreeError> {
+        let index = *self
+            .positions
+            .get(&value)
+            .ok_or(IncrementalMerkleTreeError::ValueNotFound)?;
+        Ok((self.leaves[index], self.tree.prove(index)?))
+    }
+
+    /// Generate a [NonMembershipProof] for `value`.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf bracketing `value` and its inclusion proof.
+    /// - `Err(IncrementalMerkleTreeError::ValueExists)` if `value` is in the tree.
+    pub fn prove_non_membership(
+        &mut self,
+        value: B256,
+    ) -> Result<NonMembershipProof<HEIGHT>, IncrementalMerkleTreeError> {
+        let low_index = self.low_index(value)?;
+        Ok(NonMembershipProof {
+            low_leaf: self.leaves[low_index],
+            proof: self.tree.prove(low_index)?,
+        })
+    }
+
+    /// Returns the index of the leaf holding the largest value below `value`.
+    fn low_index(&self, value: B256) -> Result<usize, IncrementalMerkleTreeError> {
+        if self.contains(value) {
+            return Err(IncrementalMerkleTreeError::ValueExists);
+        }
+        let (_, &index) = self
+            .positions
+            .range(..value)
+            .next_back()
+            .expect("the zero value is below every other value");
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the value `byte` as the last byte of a big-endian integer.
+    fn value(byte: u8) -> B256 {
+        B256::with_last_byte(byte)
+    }
+
+    /// Checks that the leaves form a list from the zero leaf through every value in ascending order,
+    /// and that the tree commits to their hashes.
+    fn assert_linked<const HEIGHT: usize>(tree: &mut IndexedMerkleTree<HEIGHT>, values: &[B256]) {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        let mut leaf = *tree.leaf(0).unwrap();
+        for &next in &sorted {
+            assert_eq!(leaf.next_value, next);
+            leaf = *tree.leaf(leaf.next_index).unwrap();
+            assert_eq!(leaf.value, next);
+        }
+        assert_eq!((leaf.next_index, leaf.next_value), (0, B256::ZERO));
+
+        for index in 0..=values.len() {
+            let leaf = *tree.leaf(index).unwrap();
+            let (proven, proof) = tree.prove_membership(leaf.value).unwrap();
+            assert_eq!(
+                (proven, proof.index, proof.leaf),
+                (leaf, index, leaf.hash())
+            );
+            assert!(proof.verify(tree.root()));
+        }
+        assert!(tree.leaf(values.len() + 1).is_none());
+    }
+
+    #[test]
+    fn inserts_keep_the_leaves_linked_in_order() {
+        let mut tree = IndexedMerkleTree::<5>::new();
+        assert_linked(&mut tree, &[]);
+        let values = [30, 10, 20, 40, 5, 25, 1, 255].map(value);
+        for (count, &inserted) in values.iter().enumerate() {
+            assert_eq!(tree.insert(inserted), Ok(count + 1));
+            assert_linked(&mut tree, &values[..=count]);
+        }
+    }
+
+    #[test]
+    fn inserts_relink_the_low_leaf() {
+        let mut tree = IndexedMerkleTree::<5>::new();
+        tree.insert(value(10)).unwrap();
+        tree.insert(value(30)).unwrap();
+
+        // 20 is inserted between 10 and 30: only the leaf of 10 changes, to point at it.
+        let before: Vec<_> = (0..3).map(|index| *tree.leaf(index).unwrap()).collect();
+        assert_eq!(tree.insert(value(20)), Ok(3));
+        assert_eq!(tree.leaf(0), Some(&before[0]));
+        let low = IndexedLeaf {
+            next_index: 3,
+            next_value: value(20),
+            ..before[1]
+        };
+        assert_eq!(tree.leaf(1), Some(&low));
+        assert_eq!(tree.leaf(2), Some(&before[2]));
+        let leaf = IndexedLeaf {
+            value: value(20),
+            next_index: 2,
+            next_value: value(30),
+        };
+        assert_eq!(tree.leaf(3), Some(&leaf));
+
+        let mut hashes = IncrementalMerkleTree::<5>::new();
+        for index in 0..4 {
+            hashes.append(tree.leaf(index).unwrap().hash()).unwrap();
+        }
+        assert_eq!(tree.root(), hashes.root());
+    }
+
+    #[test]
+    fn duplicate_values_are_rejected() {
+        let mut tree = IndexedMerkleTree::<5>::new();
+        tree.insert(value(7)).unwrap();
+        let root = tree.root();
+        for duplicate in [value(7), B256::ZERO] {
+            assert_eq!(
+                tree.insert(duplicate),
+                Err(IncrementalMerkleTreeError::ValueExists)
+            );
+        }
+        assert_eq!(tree.root(), root);
+        assert!(tree.leaf(2).is_none());
+        assert_linked(&mut tree, &[value(7)]);
+    }
+
+    #[test]
+    fn non_membership_proofs_fail_for_present_values() {
+        let mut tree = IndexedMerkleTree::<5>::new();
+        for byte in [10, 20, 30] {
+            tree.insert(value(byte)).unwrap();
+        }
+        let root = tree.root();
+
+        for byte in [1, 15, 25, 31, 255] {
+            let proof = tree.prove_non_membership(value(byte)).unwrap();
+            assert!(proof.verify(root, value(byte)));
+        }
+        for present in [B256::ZERO, value(10), value(20), value(30)] {
+            assert_eq!(
+                tree.prove_non_membership(present),
+                Err(IncrementalMerkleTreeError::ValueExists)
+            );
+        }
+
+        // The proofs of the values around a present one do not cover it.
+        for byte in [15, 25, 31] {
+            let proof = tree.prove_non_membership(value(byte)).unwrap();
+            for present in [B256::ZERO, value(10), value(20), value(30)] {
+                assert!(!proof.verify(root, present));
+            }
+        }
+
+        // Nor does a proof whose low leaf has had its link to the present value removed, since the
+        // tree commits to the link.
+        let mut proof = tree.prove_non_membership(value(15)).unwrap();
+        proof.low_leaf.next_value = value(25);
+        assert!(!proof.verify(root, value(20)));
+
+        // A proof made before a value was inserted fails against the new root.
+        let stale = tree.prove_non_membership(value(40)).unwrap();
+        tree.insert(value(40)).unwrap();
+        assert!(stale.verify(root, value(40)));
+        assert!(!stale.verify(tree.root(), value(40)));
+    }
+
+    #[test]
+    fn full_trees_reject_inserts() {
+        let mut tree = IndexedMerkleTree::<1>::new();
+        let root = tree.root();
+        assert_eq!(
+            tree.insert(value(1)),
+            Err(IncrementalMerkleTreeError::TreeFull { capacity: 1 })
+        );
+        assert_eq!(tree.root(), root);
+        assert!(!tree.contains(value(1)));
+        assert_linked(&mut tree, &[]);
+
+        let mut tree = IndexedMerkleTree::<2>::new();
+        for byte in [2, 1] {
+            tree.insert(value(byte)).unwrap();
+        }
+        assert!(tree.insert(value(3)).is_err());
+        assert_linked(&mut tree, &[value(2), value(1)]);
+    }
+}
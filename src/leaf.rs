@@ -0,0 +1,258 @@
+use alloc::vec::Vec;
+use alloy_primitives::{Keccak256, B256};
+use core::fmt;
+
+use crate::{
+    hasher::{DigestHasher, Keccak256Hasher, MerkleHasher},
+    proof::MerkleProof,
+    store::{MemoryStore, TreeStore},
+    IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// [LeafEncode] defines how a value becomes the leaf of a tree, so records such as a deposit's
+/// `DepositData` or a [Withdrawal](crate::op_stack::Withdrawal) are appended with
+/// [IncrementalMerkleTree::append] as they are, instead of being hashed at every call site. A [B256]
+/// is its own leaf.
+///
+/// Implementations for payloads of arbitrary bytes should hash them behind a [LeafDomain].
+pub trait LeafEncode {
+    /// Returns the leaf committing to the value.
+    fn leaf_hash(&self) -> B256;
+}
+
+impl LeafEncode for B256 {
+    fn leaf_hash(&self) -> B256 {
+        *self
+    }
+}
+
+impl<L: LeafEncode + ?Sized> LeafEncode for &L {
+    fn leaf_hash(&self) -> B256 {
+        (**self).leaf_hash()
+    }
+}
+
+/// [LeafDomain] turns arbitrary byte payloads into leaves by hashing them behind a domain-separation
+/// prefix. Appending raw 32-byte values, or hashing 64-byte payloads without a prefix, lets a leaf
+/// collide with the preimage of an internal node, and a proof of that node then passes as a proof of
+/// a leaf. Services appending to the same tree should share one domain.
+///
+/// The prefix is hashed as is, without a length, so no domain of a tree should be a prefix of
+/// another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeafDomain<'a> {
+    /// The bytes hashed before every payload
+    prefix: &'a [u8],
+}
+
+impl LeafDomain<'static> {
+    /// The leaf domain of RFC 6962, the single byte `0x00`, to use with an
+    /// [Rfc6962Hasher](crate::hasher::Rfc6962Hasher) tree.
+    pub const RFC6962: Self = Self::new(&[0x00]);
+}
+
+impl<'a> LeafDomain<'a> {
+    /// Create a domain hashing payloads behind `prefix`, such as a protocol and version tag like
+    /// `b"deposits/v1"`, or `&[0x00]` as RFC 6962 does.
+    pub const fn new(prefix: &'a [u8]) -> Self {
+        Self { prefix }
+    }
+
+    /// Returns the prefix hashed before every payload.
+    pub const fn prefix(&self) -> &'a [u8] {
+        self.prefix
+    }
+
+    /// Returns the keccak leaf of `data`, `keccak256(prefix || data)`.
+    pub fn hash(&self, data: &[u8]) -> B256 {
+        self.hash_with::<Keccak256Hasher>(data)
+    }
+
+    /// Like [Self::hash], with the hash function of `H`.
+    pub fn hash_with<H: DigestHasher>(&self, data: &[u8]) -> B256 {
+        H::hash_bytes(&[self.prefix, data])
+    }
+
+    /// Returns the keccak leaf of the concatenation of `parts`, e.g. the encoded fields of a record,
+    /// without copying them into one buffer first.
+    pub fn hash_parts(&self, parts: &[&[u8]]) -> B256 {
+        self.hash_parts_with::<Keccak256Hasher>(parts)
+    }
+
+    /// Like [Self::hash_parts], with the hash function of `H`.
+    pub fn hash_parts_with<H: DigestHasher>(&self, parts: &[&[u8]]) -> B256 {
+        let mut prefixed = alloc::vec::Vec::with_capacity(parts.len() + 1);
+        prefixed.push(self.prefix);
+        prefixed.extend_from_slice(parts);
+        H::hash_bytes(&prefixed)
+    }
+}
+
+/// [LeafHasher] computes the keccak leaf of a payload too large to hold in memory, such as a file or
+/// a blob, from its chunks as they arrive. Its leaf is the one [LeafDomain::hash] returns for the
+/// whole payload, `keccak256(prefix || data)`.
+///
+/// With the `std` feature it is also an [std::io::Write], so a reader can be hashed with
+/// [std::io::copy].
+#[derive(Debug, Clone)]
+pub struct LeafHasher {
+    /// The keccak state absorbing the prefix and the chunks
+    hasher: Keccak256,
+    /// The number of payload bytes absorbed so far
+    len: u64,
+}
+
+impl LeafHasher {
+    /// Create a hasher for a payload hashed behind `domain`.
+    pub fn new(domain: LeafDomain<'_>) -> Self {
+        let mut hasher = Keccak256::new();
+        hasher.update(domain.prefix());
+        Self { hasher, len: 0 }
+    }
+
+    /// Absorb the next `chunk` of the payload.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+        self.len += chunk.len() as u64;
+    }
+
+    /// Returns the number of payload bytes absorbed so far, without the prefix.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if no payload byte has been absorbed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the leaf of the absorbed payload.
+    pub fn finalize(self) -> B256 {
+        self.hasher.finalize()
+    }
+
+    /// Append the leaf of the absorbed payload to `tree`.
+    ///
+    /// # Returns
+    /// - `Ok(leaf)` with the appended leaf.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append_to<const HEIGHT: usize, H: MerkleHasher, S: TreeStore>(
+        self,
+        tree: &mut IncrementalMerkleTree<HEIGHT, H, S>,
+    ) -> Result<B256, IncrementalMerkleTreeError> {
+        let leaf = self.finalize();
+        tree.append(leaf)?;
+        Ok(leaf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for LeafHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// [TypedMerkleTree] is an [IncrementalMerkleTree] of [LeafEncode] records that also keeps the
+/// records themselves, so they can be read back by index next to their proofs.
+#[derive(Clone)]
+pub struct TypedMerkleTree<const HEIGHT: usize, L, H = Keccak256Hasher, S = MemoryStore> {
+    /// The tree of record leaves
+    tree: IncrementalMerkleTree<HEIGHT, H, S>,
+    /// The appended records, in order
+    records: Vec<L>,
+}
+
+impl<const HEIGHT: usize, L, H: MerkleHasher, S: TreeStore> fmt::Debug
+    for TypedMerkleTree<HEIGHT, L, H, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedMerkleTree")
+            .field("tree", &self.tree)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<const HEIGHT: usize, L: LeafEncode, H: MerkleHasher, S: TreeStore + Default> Default
+    for TypedMerkleTree<HEIGHT, L, H, S>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const HEIGHT: usize, L: LeafEncode, H: MerkleHasher, S: TreeStore + Default>
+    TypedMerkleTree<HEIGHT, L, H, S>
+{
+    /// Create a new, empty [TypedMerkleTree].
+    pub fn new() -> Self {
+        Self {
+            tree: IncrementalMerkleTree::new(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl<const HEIGHT: usize, L: LeafEncode, H: MerkleHasher, S: TreeStore>
+    TypedMerkleTree<HEIGHT, L, H, S>
+{
+    /// Append `record` to the tree, and keep it.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the record was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&mut self, record: L) -> Result<(), IncrementalMerkleTreeError> {
+        self.tree.append(&record)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Returns the record at `index`, or `None` if no record has been appended there.
+    pub fn get(&self, index: usize) -> Option<&L> {
+        self.records.get(index)
+    }
+
+    /// Returns the appended records, in order.
+    pub fn records(&self) -> &[L] {
+        &self.records
+    }
+
+    /// Returns the number of appended records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if no record has been appended.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> B256 {
+        self.tree.root()
+    }
+
+    /// Returns the underlying tree of record leaves.
+    pub fn tree(&self) -> &IncrementalMerkleTree<HEIGHT, H, S> {
+        &self.tree
+    }
+
+    /// Generate an inclusion proof for the record at `index`, returned with the record.
+    ///
+    /// # Returns
+    /// - `Ok((record, proof))` with the record and the proof of its leaf.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no record has been appended
+    ///   at `index`.
+    pub fn prove(
+        &mut self,
+        index: usize,
+    ) -> Result<(&L, MerkleProof<HEIGHT>), IncrementalMerkleTreeError> {
+        let proof = self.tree.prove(index)?;
+        Ok((&self.records[index], proof))
+    }
+}
@@ -0,0 +1,268 @@
+use alloc::{vec, vec::Vec};
+use alloy_primitives::B256;
+use core::marker::PhantomData;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    IncrementalMerkleTreeError,
+};
+
+/// An inclusion proof for a leaf of a [LeanIncrementalMerkleTree], in the LeanIMT format.
+///
+/// Levels where the node on the path has no sibling contribute nothing, so bit `i` of `index` is the
+/// direction at the `i`-th sibling rather than at height `i`. It is only equal to the leaf index
+/// when every level has a sibling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeanMerkleProof {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The compressed path: bit `i` is set when the node is the right child at the `i`-th sibling
+    pub index: usize,
+    /// The siblings on the path from the leaf up to the root, skipping levels without one
+    pub siblings: Vec<B256>,
+}
+
+impl LeanMerkleProof {
+    /// Returns `true` if the proof shows that `self.leaf` is in the tree committed to by `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        if self.siblings.len() < usize::BITS as usize && self.index >> self.siblings.len() != 0 {
+            return false;
+        }
+        let computed = self
+            .siblings
+            .iter()
+            .enumerate()
+            .fold(self.leaf, |node, (i, sibling)| {
+                if (self.index >> i) & 1 == 1 {
+                    H::hash_pair(sibling, &node)
+                } else {
+                    H::hash_pair(&node, sibling)
+                }
+            });
+        computed == root
+    }
+}
+
+/// [LeanIncrementalMerkleTree] is an append-only merkle tree whose depth grows with the number of
+/// leaves, matching the LeanIMT of Semaphore v4. There are no zero hashes: a node without a right
+/// sibling is carried up to its parent unchanged, so the depth is `ceil(log2(len))` and proofs of
+/// small trees stay short.
+pub struct LeanIncrementalMerkleTree<H = Keccak256Hasher> {
+    /// The nodes of every level, from the leaves up to the root
+    levels: Vec<Vec<B256>>,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> Default for LeanIncrementalMerkleTree<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: MerkleHasher> LeanIncrementalMerkleTree<H> {
+    /// Create a new, empty [LeanIncrementalMerkleTree].
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new()],
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Returns the current depth of the tree, `ceil(log2(len))`.
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Returns the leaf at `index`, or `None` if no leaf has been appended there.
+    pub fn leaf(&self, index: usize) -> Option<B256> {
+        self.levels[0].get(index).copied()
+    }
+
+    /// Returns the root of the tree, or `None` if it is empty. The root of a single leaf is the leaf
+    /// itself.
+    pub fn root(&self) -> Option<B256> {
+        self.levels[self.depth()].first().copied()
+    }
+
+    /// Append a new leaf to the tree, growing its depth if it is full, and rehash the path above it.
+    pub fn append(&mut self, leaf: B256) {
+        let mut index = self.len();
+        self.levels[0].push(leaf);
+        if index > 0 && index.is_power_of_two() {
+            self.levels.push(Vec::new());
+        }
+
+        let mut node = leaf;
+        for level in 0..self.depth() {
+            if index & 1 == 1 {
+                node = H::hash_pair(&self.levels[level][index - 1], &node);
+            }
+            index >>= 1;
+            match self.levels[level + 1].get_mut(index) {
+                Some(parent) => *parent = node,
+                None => self.levels[level + 1].push(node),
+            }
+        }
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    pub fn prove(&self, index: usize) -> Result<LeanMerkleProof, IncrementalMerkleTreeError> {
+        let leaf = self
+            .leaf(index)
+            .ok_or(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: self.len(),
+            })?;
+
+        let mut position = index;
+        let mut path = 0;
+        let mut siblings = Vec::new();
+        for level in 0..self.depth() {
+            if let Some(sibling) = self.levels[level].get(position ^ 1) {
+                path |= (position & 1) << siblings.len();
+                siblings.push(*sibling);
+            }
+            position >>= 1;
+        }
+        Ok(LeanMerkleProof {
+            leaf,
+            index: path,
+            siblings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hash_pair, leaves};
+
+    /// Returns the levels of a LeanIMT holding `leaves`, from the leaves up to the root, computed by
+    /// hashing every pair of each level and carrying a lone last node up unchanged.
+    fn naive_levels(leaves: &[B256]) -> Vec<Vec<B256>> {
+        let mut levels = vec![leaves.to_vec()];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match *pair {
+                    [left, right] => hash_pair(left, right),
+                    [node] => node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+        levels
+    }
+
+    #[test]
+    fn every_leaf_proves_against_the_naive_root() {
+        let mut tree = LeanIncrementalMerkleTree::<Keccak256Hasher>::new();
+        assert!(tree.is_empty());
+        assert_eq!((tree.depth(), tree.root()), (0, None));
+
+        let leaves = leaves(33);
+        for len in 1..=33 {
+            tree.append(leaves[len - 1]);
+            assert_eq!(tree.len(), len);
+            assert_eq!(tree.leaf(len - 1), Some(leaves[len - 1]));
+            assert_eq!(tree.leaf(len), None);
+
+            let levels = naive_levels(&leaves[..len]);
+            let root = levels[levels.len() - 1][0];
+            assert_eq!(tree.root(), Some(root));
+            assert_eq!(tree.depth(), levels.len() - 1);
+
+            for (index, &leaf) in leaves[..len].iter().enumerate() {
+                let mut siblings = Vec::new();
+                let mut path = 0;
+                for (height, level) in levels.iter().enumerate() {
+                    if let Some(&sibling) = level.get((index >> height) ^ 1) {
+                        path |= ((index >> height) & 1) << siblings.len();
+                        siblings.push(sibling);
+                    }
+                }
+                let proof = tree.prove(index).unwrap();
+                assert_eq!(
+                    proof,
+                    LeanMerkleProof {
+                        leaf,
+                        index: path,
+                        siblings,
+                    }
+                );
+                assert!(proof.verify(root), "leaf {index} of {len}");
+            }
+            assert_eq!(
+                tree.prove(len),
+                Err(IncrementalMerkleTreeError::IndexOutOfBounds { index: len, len })
+            );
+        }
+    }
+
+    #[test]
+    fn depth_grows_at_powers_of_two() {
+        let mut tree = LeanIncrementalMerkleTree::<Keccak256Hasher>::new();
+        for (len, leaf) in (1..=65usize).zip(leaves(65)) {
+            let depth = tree.depth();
+            tree.append(leaf);
+            // The depth is ceil(log2(len)), which grows by one when the tree was full before the
+            // leaf, and only then.
+            assert_eq!(
+                tree.depth(),
+                len.next_power_of_two().trailing_zeros() as usize
+            );
+            let grew = len > 1 && (len - 1).is_power_of_two();
+            assert_eq!(tree.depth(), depth + grew as usize, "{len} leaves");
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_are_rejected() {
+        let mut tree = LeanIncrementalMerkleTree::<Keccak256Hasher>::new();
+        for leaf in leaves(11) {
+            tree.append(leaf);
+        }
+        let root = tree.root().unwrap();
+        for index in 0..11 {
+            let proof = tree.prove(index).unwrap();
+            let mut tampered = proof.clone();
+            tampered.leaf = B256::repeat_byte(1);
+            assert!(!tampered.verify(root));
+            for level in 0..proof.siblings.len() {
+                let mut tampered = proof.clone();
+                tampered.siblings[level] = B256::repeat_byte(1);
+                assert!(!tampered.verify(root));
+                let mut tampered = proof.clone();
+                tampered.index ^= 1 << level;
+                assert!(!tampered.verify(root));
+            }
+            // A path bit beyond the siblings.
+            let mut tampered = proof.clone();
+            tampered.index |= 1 << proof.siblings.len();
+            assert!(!tampered.verify(root));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! An append-only incremental merkle tree of generic height, using `keccak256` as the hash function
+//! by default.
+//!
+//! Everything but proof verification sits behind the default `tree` feature. Without it, only the
+//! [verify], [hasher] and [gindex] modules are built, for verifiers with tight code-size budgets.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "tree")]
+pub mod append_log;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(feature = "rkyv")]
+pub mod archive;
+#[cfg(any(feature = "borsh", feature = "bincode"))]
+pub mod codec;
+#[cfg(all(feature = "tree", feature = "sha256"))]
+pub mod deposit;
+#[cfg(feature = "tree")]
+pub mod dyn_tree;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "ark")]
+pub mod field_tree;
+#[cfg(feature = "tree")]
+pub mod forest;
+#[cfg(feature = "tree")]
+pub mod frontier_tree;
+pub mod gindex;
+pub mod hasher;
+#[cfg(feature = "tree")]
+pub mod incremental_tree;
+#[cfg(feature = "tree")]
+pub mod indexed_tree;
+#[cfg(feature = "tree")]
+pub mod leaf;
+#[cfg(feature = "tree")]
+pub mod lean_tree;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "tree")]
+pub mod mmr;
+#[cfg(feature = "tree")]
+pub mod namespaced;
+#[cfg(feature = "tree")]
+pub mod nested;
+#[cfg(feature = "tree")]
+pub mod observer;
+#[cfg(feature = "tree")]
+pub mod op_stack;
+#[cfg(feature = "tree")]
+pub mod proof;
+#[cfg(feature = "provider")]
+pub mod provider;
+#[cfg(feature = "serde")]
+mod serde_array;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "sol")]
+pub mod sol;
+#[cfg(feature = "tree")]
+pub mod sparse_tree;
+#[cfg(all(feature = "tree", feature = "sha256"))]
+pub mod ssz;
+#[cfg(feature = "tree")]
+pub mod store;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(test)]
+mod test_utils;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "tree")]
+pub use append_log::{AppendLog, MemoryLog};
+#[cfg(feature = "tree")]
+pub use dyn_tree::{DynIncrementalMerkleTree, DynMerkleProof};
+#[cfg(feature = "tree")]
+pub use forest::Forest;
+#[cfg(feature = "tree")]
+pub use frontier_tree::{root_from_iter, root_from_iter_with, FrontierTree};
+pub use hasher::{
+    DigestHasher, Keccak256Hasher, KeccakBackend, KeccakBackendHasher, MerkleHasher, Rfc6962Hasher,
+    SortedKeccak256Hasher,
+};
+#[cfg(feature = "tree")]
+pub use incremental_tree::{
+    bag_peaks, BatchGuard, Checkpoint, Frontier, IncrementalMerkleTree, IncrementalMerkleTreeError,
+    Leaves, RootMode, TreeDiff,
+};
+#[cfg(feature = "tree")]
+pub use indexed_tree::{IndexedLeaf, IndexedMerkleTree, NonMembershipProof};
+#[cfg(feature = "tree")]
+pub use leaf::{LeafDomain, LeafEncode, LeafHasher, TypedMerkleTree};
+#[cfg(feature = "tree")]
+pub use lean_tree::{LeanIncrementalMerkleTree, LeanMerkleProof};
+#[cfg(feature = "tree")]
+pub use mmr::{MerkleMountainRange, MmrProof};
+#[cfg(feature = "tree")]
+pub use namespaced::{
+    NamespaceProof, NamespacedMerkleTree, NamespacedNode, CELESTIA_NAMESPACE_SIZE,
+};
+#[cfg(feature = "tree")]
+pub use nested::{NestedMerkleTree, NestedProof};
+#[cfg(feature = "tree")]
+pub use observer::AppendObserver;
+#[cfg(feature = "tree")]
+pub use op_stack::{OutputRootProof, Withdrawal};
+#[cfg(feature = "tree")]
+pub use proof::{
+    recover_root_with_width, verify_batch, verify_batch_with, ArityMerkleProof, CompressedProof,
+    ConsistencyProof, MerkleProof, MultiProof, RangeProof,
+};
+#[cfg(feature = "tree")]
+pub use sparse_tree::{SparseMerkleTree, SparseProof, SPARSE_DEPTH};
+#[cfg(feature = "tree")]
+pub use store::{MemoryStore, TreeStore};
+pub use verify::{recover_root, recover_root_with_directions, verify, verify_sorted, verify_with};
+
+#[cfg(feature = "rkyv")]
+pub use archive::{ArchiveStore, TreeArchive};
+#[cfg(all(feature = "tree", feature = "sha256"))]
+pub use deposit::{DepositData, DepositTree, DEPOSIT_CONTRACT_TREE_DEPTH};
+#[cfg(feature = "ark")]
+pub use field_tree::{FieldHasher, FieldMerkleProof, FieldMerkleTree};
+#[cfg(feature = "blake3")]
+pub use hasher::Blake3Hasher;
+#[cfg(feature = "poseidon")]
+pub use hasher::PoseidonHasher;
+#[cfg(feature = "sha256")]
+pub use hasher::Sha256Hasher;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapStore;
+#[cfg(feature = "provider")]
+pub use provider::{sync_from_logs, LeafLog, LogProvider, SyncCheckpoint, SyncError};
+#[cfg(feature = "shared")]
+pub use shared::SharedIncrementalMerkleTree;
+#[cfg(feature = "sol")]
+pub use sol::{SolMerkleProof, SolMultiProof};
+#[cfg(all(feature = "tree", feature = "sha256"))]
+pub use ssz::{mix_in_length, SszList};
+#[cfg(feature = "async")]
+pub use stream::{StreamBuilder, StreamError};
@@ -0,0 +1,492 @@
+use alloy_primitives::B256;
+use core::ops::Range;
+use hashbrown::HashSet;
+use memmap2::MmapMut;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind, Result},
+    path::Path,
+    vec::Vec,
+};
+
+use crate::store::TreeStore;
+
+/// The magic bytes at the start of every tree file.
+const MAGIC: &[u8; 8] = b"IMTMMAP2";
+/// The length of the header preceding the node array.
+const HEADER_LEN: usize = 64;
+/// The offset of the tree height in the header.
+const HEIGHT_OFFSET: usize = 8;
+/// The offset of the committed size in the header.
+const SIZE_OFFSET: usize = 16;
+/// The offset of the dirty flag in the header.
+const DIRTY_OFFSET: usize = 24;
+/// The offset of the committed index of the first stored leaf in the header.
+const FIRST_STORED_LEAF_OFFSET: usize = 32;
+/// The offset of the committed number of unstored subtrees in the header, which are listed after the
+/// bitmap.
+const SUBTREES_OFFSET: usize = 40;
+
+/// [MmapStore] is a [TreeStore] persisting the intermediates cache to a memory-mapped file, so a tree
+/// larger than the heap survives restarts. See
+/// [IncrementalMerkleTree::create_mmap](crate::IncrementalMerkleTree::create_mmap) and
+/// [IncrementalMerkleTree::open_mmap](crate::IncrementalMerkleTree::open_mmap).
+///
+/// The file holds a small header, followed by one 32-byte slot per generalized index of a tree of
+/// the recorded height, and a bitmap with one bit per slot recording whether it holds a node. It is
+/// created sparse, so only the pages holding written nodes take up disk space. The file grows past
+/// the bitmap to list the ranges of leaves of the unstored subtrees, as little-endian `u64` pairs. Unwritten and removed
+/// slots read back as `None`, as they do in a [MemoryStore](crate::store::MemoryStore).
+///
+/// The header records the size of the tree, the index of its first stored leaf and the number of
+/// its unstored subtrees at the last [commit](Self::commit), and a dirty flag. The flag is set and flushed before the first write after
+/// a commit, so a crash between two commits is always detected when the file is opened again.
+/// Removals are only applied to the file once the next commit is recorded, so the nodes of the
+/// committed tree are all still there when it is reopened after a crash.
+pub struct MmapStore {
+    /// The open tree file, kept alive for the mapping and grown for the unstored subtrees
+    file: File,
+    /// The mapping of the whole file
+    map: MmapMut,
+    /// The offset of the occupancy bitmap, right after the node array
+    bitmap: usize,
+    /// Whether the dirty flag is currently set in the header
+    dirty: bool,
+    /// The slots removed since the last commit, which read back as `None` but keep their node in the
+    /// file until the next commit
+    removed: HashSet<usize>,
+}
+
+impl MmapStore {
+    /// Create a new tree file at `path` for a tree of the given `height`, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>, height: usize) -> Result<Self> {
+        let len = file_len(height)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "tree height is too large"))?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+
+        // SAFETY: the file was just created by this process and is only accessed through the map.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..8].copy_from_slice(MAGIC);
+        map[HEIGHT_OFFSET..HEIGHT_OFFSET + 8].copy_from_slice(&(height as u64).to_le_bytes());
+        map.flush()?;
+        Ok(Self {
+            file,
+            map,
+            bitmap: bitmap_offset(height).expect("a valid tree height"),
+            dirty: false,
+            removed: HashSet::new(),
+        })
+    }
+
+    /// Open an existing tree file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        // SAFETY: the file must not be modified by anything else while it is mapped, which is a
+        // requirement of this store.
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not an incremental merkle tree file",
+            ));
+        }
+        let height = read_u64(&map, HEIGHT_OFFSET) as usize;
+        let subtrees = read_u64(&map, SUBTREES_OFFSET) as usize;
+        if file_len(height)
+            .and_then(|len| len.checked_add(subtrees.checked_mul(16)?))
+            .is_none_or(|len| map.len() < len)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "tree file is too short for its height",
+            ));
+        }
+        Ok(Self {
+            file,
+            dirty: map[DIRTY_OFFSET] != 0,
+            bitmap: bitmap_offset(height).expect("a valid tree height"),
+            map,
+            removed: HashSet::new(),
+        })
+    }
+
+    /// Returns the height of the tree the file was created for.
+    pub fn height(&self) -> usize {
+        self.read_u64(HEIGHT_OFFSET) as usize
+    }
+
+    /// Returns the size of the tree at the last commit.
+    pub fn committed_size(&self) -> usize {
+        self.read_u64(SIZE_OFFSET) as usize
+    }
+
+    /// Returns the index of the first stored leaf of the tree at the last commit.
+    pub fn committed_first_stored_leaf(&self) -> usize {
+        self.read_u64(FIRST_STORED_LEAF_OFFSET) as usize
+    }
+
+    /// Returns the leaves of the unstored subtrees of the tree at the last commit.
+    pub fn committed_unstored_subtrees(&self) -> Vec<Range<usize>> {
+        let start = self.subtrees_offset();
+        (0..self.read_u64(SUBTREES_OFFSET) as usize)
+            .map(|subtree| {
+                let offset = start + subtree * 16;
+                self.read_u64(offset) as usize..self.read_u64(offset + 8) as usize
+            })
+            .collect()
+    }
+
+    /// Returns `true` if nodes were written after the last commit, i.e. the file was not closed
+    /// cleanly.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Flush every written node and the ranges of `unstored_subtrees` to disk, then record `size` as
+    /// the committed size, `first_stored_leaf` as the index of the first stored leaf and the number of
+    /// unstored subtrees, and clear the dirty flag. The slots removed since the last commit are
+    /// cleared afterwards: the committed tree no longer reads them, so a crash while they are cleared
+    /// only leaves some of them in the file.
+    pub fn commit(
+        &mut self,
+        size: usize,
+        first_stored_leaf: usize,
+        unstored_subtrees: &[Range<usize>],
+    ) -> Result<()> {
+        let start = self.subtrees_offset();
+        let len = start + unstored_subtrees.len() * 16;
+        if self.map.len() < len {
+            self.map.flush()?;
+            self.file.set_len(len as u64)?;
+            // SAFETY: the file is still only accessed through this store.
+            self.map = unsafe { MmapMut::map_mut(&self.file)? };
+        }
+        for (subtree, offset) in unstored_subtrees.iter().zip((start..).step_by(16)) {
+            self.write_u64(offset, subtree.start as u64);
+            self.write_u64(offset + 8, subtree.end as u64);
+        }
+        self.map.flush()?;
+        self.write_u64(SIZE_OFFSET, size as u64);
+        self.write_u64(FIRST_STORED_LEAF_OFFSET, first_stored_leaf as u64);
+        self.write_u64(SUBTREES_OFFSET, unstored_subtrees.len() as u64);
+        self.map[DIRTY_OFFSET] = 0;
+        self.map.flush_range(0, HEADER_LEN)?;
+        self.dirty = false;
+
+        if !self.removed.is_empty() {
+            for index in core::mem::take(&mut self.removed) {
+                let slot = self.slot(index).expect("removed slots are in the file");
+                let (byte, bit) = self.occupancy(index);
+                self.map[slot].fill(0);
+                self.map[byte] &= !bit;
+            }
+            self.map.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the byte range of the slot for generalized index `index`, if it is in the file.
+    fn slot(&self, index: usize) -> Option<core::ops::Range<usize>> {
+        let start = index.checked_mul(32)?.checked_add(HEADER_LEN)?;
+        (index > 0 && start + 32 <= self.bitmap).then_some(start..start + 32)
+    }
+
+    /// Returns the byte of the occupancy bitmap holding the bit of `index`, and the mask of the bit.
+    fn occupancy(&self, index: usize) -> (usize, u8) {
+        (self.bitmap + index / 8, 1 << (index & 7))
+    }
+
+    /// Set and flush the dirty flag before the first write since the last commit.
+    fn mark_dirty(&mut self) {
+        if !self.dirty {
+            self.map[DIRTY_OFFSET] = 1;
+            self.map
+                .flush_range(0, HEADER_LEN)
+                .expect("failed to flush the tree file header");
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the offset of the list of unstored subtrees, right after the bitmap.
+    fn subtrees_offset(&self) -> usize {
+        file_len(self.height()).expect("a valid tree height")
+    }
+
+    /// Read a little-endian `u64` from the file at `offset`.
+    fn read_u64(&self, offset: usize) -> u64 {
+        read_u64(&self.map, offset)
+    }
+
+    /// Write `value` as a little-endian `u64` to the file at `offset`.
+    fn write_u64(&mut self, offset: usize, value: u64) {
+        self.map[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Read a little-endian `u64` from the mapped file `map` at `offset`.
+fn read_u64(map: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&map[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Returns the number of slots in the file of a tree of `height`, one per generalized index and the
+/// unused index `0`, or `None` if it does not fit in a `usize`.
+fn slots(height: usize) -> Option<usize> {
+    1usize.checked_shl(u32::try_from(height).ok()?.checked_add(1)?)
+}
+
+/// Returns the offset of the occupancy bitmap in the file of a tree of `height`, or `None` if it
+/// does not fit in a `usize`.
+fn bitmap_offset(height: usize) -> Option<usize> {
+    slots(height)?.checked_mul(32)?.checked_add(HEADER_LEN)
+}
+
+/// Returns the length of the file of a tree of `height`, or `None` if it does not fit in a `usize`.
+fn file_len(height: usize) -> Option<usize> {
+    bitmap_offset(height)?.checked_add(slots(height)?.div_ceil(8))
+}
+
+impl TreeStore for MmapStore {
+    fn get(&self, index: usize) -> Option<B256> {
+        let slot = self.slot(index)?;
+        let (byte, bit) = self.occupancy(index);
+        (self.map[byte] & bit != 0 && !self.removed.contains(&index))
+            .then(|| B256::from_slice(&self.map[slot]))
+    }
+
+    /// # Panics
+    /// If `index` is outside the tree the file was created for, or the dirty flag cannot be flushed.
+    fn put(&mut self, index: usize, node: B256) {
+        let slot = self.slot(index).expect("node index outside the tree file");
+        let (byte, bit) = self.occupancy(index);
+        self.mark_dirty();
+        self.map[slot].copy_from_slice(node.as_slice());
+        self.map[byte] |= bit;
+        self.removed.remove(&index);
+    }
+
+    fn remove(&mut self, index: usize) {
+        if self.slot(index).is_some() {
+            self.removed.insert(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hasher::Keccak256Hasher,
+        test_utils::{leaves, naive_root},
+        IncrementalMerkleTree, IncrementalMerkleTreeError,
+    };
+    use std::{path::PathBuf, vec::Vec};
+
+    /// Returns a path for the tree file of the test `name`.
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(std::format!("simple-merkle-tree-{name}.imt"))
+    }
+
+    #[test]
+    fn unwritten_and_removed_slots_are_empty() {
+        let path = path("unwritten_and_removed_slots_are_empty");
+        let mut store = MmapStore::create(&path, 3).unwrap();
+        assert_eq!(store.get(5), None);
+        store.put(5, B256::ZERO);
+        store.put(15, B256::repeat_byte(1));
+        assert_eq!(store.get(5), Some(B256::ZERO));
+        assert_eq!(store.get(15), Some(B256::repeat_byte(1)));
+        store.remove(5);
+        assert_eq!(store.get(5), None);
+        assert_eq!(store.get(16), None);
+        store.commit(0, 0, &[]).unwrap();
+        drop(store);
+
+        let store = MmapStore::open(&path).unwrap();
+        assert_eq!(store.get(5), None);
+        assert_eq!(store.get(15), Some(B256::repeat_byte(1)));
+        drop(store);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn trees_reopen_at_the_last_commit() {
+        let path = path("trees_reopen_at_the_last_commit");
+        let leaves = leaves(57);
+        let mut tree =
+            IncrementalMerkleTree::<8, Keccak256Hasher, MmapStore>::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..37]).unwrap();
+        tree.commit().unwrap();
+        tree.append_batch(&leaves[37..]).unwrap();
+        drop(tree);
+
+        let mut tree =
+            IncrementalMerkleTree::<8, Keccak256Hasher, MmapStore>::open_mmap(&path).unwrap();
+        assert_eq!(tree.len(), 37);
+        assert_eq!(tree.root(), naive_root(&leaves[..37], 8));
+        assert!(tree.prove(20).unwrap().verify(tree.root()));
+        drop(tree);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    type MmapTree = IncrementalMerkleTree<6, Keccak256Hasher, MmapStore>;
+
+    /// Reopen the tree file at `path` and check that it holds the first `size` of `leaves`, of which
+    /// the leaves in `unavailable` are unavailable, and that it accepts the next leaf. The file is
+    /// removed afterwards.
+    fn assert_reopens(path: &Path, leaves: &[B256], size: usize, unavailable: Range<usize>) {
+        let mut tree = MmapTree::open_mmap(path).unwrap();
+        assert_eq!(tree.len(), size);
+        assert_eq!(tree.root(), naive_root(&leaves[..size], 6));
+        tree.validate_invariants().unwrap();
+        for index in 0..size {
+            let proof = tree.prove(index);
+            if unavailable.contains(&index) {
+                assert_eq!(proof, Err(IncrementalMerkleTreeError::LeafUnavailable));
+            } else {
+                assert!(proof.unwrap().verify(tree.root()), "leaf {index}");
+            }
+        }
+        tree.append(leaves[size]).unwrap();
+        assert_eq!(tree.root(), naive_root(&leaves[..=size], 6));
+        drop(tree);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pruned_trees_reopen_with_their_unavailable_leaves() {
+        let leaves = leaves(50);
+        let path = path("pruned_trees_reopen_clean");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..40]).unwrap();
+        tree.prune_before(35).unwrap();
+        tree.commit().unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 40, 0..35);
+
+        let path = self::path("pruned_trees_reopen_dirty");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..40]).unwrap();
+        tree.prune_before(35).unwrap();
+        tree.commit().unwrap();
+        tree.append_batch(&leaves[40..45]).unwrap();
+        tree.prune_before(42).unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 40, 0..35);
+    }
+
+    #[test]
+    fn root_only_appends_reopen_with_their_unavailable_leaves() {
+        let leaves = leaves(30);
+        let path = path("root_only_appends_reopen_clean");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..10]).unwrap();
+        for leaf in &leaves[10..13] {
+            tree.append_root_only(*leaf).unwrap();
+        }
+        tree.append_batch(&leaves[13..20]).unwrap();
+        tree.commit().unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 20, 0..13);
+
+        let path = self::path("root_only_appends_reopen_dirty");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..10]).unwrap();
+        for leaf in &leaves[10..13] {
+            tree.append_root_only(*leaf).unwrap();
+        }
+        tree.append_batch(&leaves[13..20]).unwrap();
+        tree.commit().unwrap();
+        for leaf in &leaves[20..24] {
+            tree.append_root_only(*leaf).unwrap();
+        }
+        drop(tree);
+        assert_reopens(&path, &leaves, 20, 0..13);
+    }
+
+    #[test]
+    fn restored_trees_reopen_with_their_unavailable_leaves() {
+        let leaves = leaves(40);
+        let frontier = IncrementalMerkleTree::<6>::from_leaves(&leaves[..25])
+            .unwrap()
+            .frontier();
+
+        let path = path("restored_trees_reopen_clean");
+        let store = MmapStore::create(&path, 6).unwrap();
+        let mut tree = MmapTree::from_frontier_with_store(frontier, store).unwrap();
+        tree.append_batch(&leaves[25..31]).unwrap();
+        tree.commit().unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 31, 0..25);
+
+        let path = self::path("restored_trees_reopen_dirty");
+        let store = MmapStore::create(&path, 6).unwrap();
+        let mut tree = MmapTree::from_frontier_with_store(frontier, store).unwrap();
+        tree.commit().unwrap();
+        tree.append_batch(&leaves[25..31]).unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 25, 0..25);
+    }
+
+    #[test]
+    fn subtrees_reopen_with_their_unavailable_leaves() {
+        let leaves = leaves(40);
+        let path = path("subtrees_reopen_clean");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..6]).unwrap();
+        tree.append_subtree(1, naive_root(&leaves[6..8], 1))
+            .unwrap();
+        tree.append_subtree(3, naive_root(&leaves[8..16], 3))
+            .unwrap();
+        tree.append_batch(&leaves[16..21]).unwrap();
+        tree.commit().unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 21, 6..16);
+
+        let path = self::path("subtrees_reopen_dirty");
+        let mut tree = MmapTree::create_mmap(&path).unwrap();
+        tree.append_batch(&leaves[..6]).unwrap();
+        tree.append_subtree(1, naive_root(&leaves[6..8], 1))
+            .unwrap();
+        tree.append_batch(&leaves[8..14]).unwrap();
+        tree.commit().unwrap();
+        tree.append_batch(&leaves[14..16]).unwrap();
+        tree.append_subtree(4, naive_root(&leaves[16..32], 4))
+            .unwrap();
+        drop(tree);
+        assert_reopens(&path, &leaves, 14, 6..8);
+    }
+
+    #[test]
+    fn pruned_nodes_are_unavailable() {
+        let path = path("pruned_nodes_are_unavailable");
+        let leaves = leaves(40);
+        let mut tree =
+            IncrementalMerkleTree::<6, Keccak256Hasher, MmapStore>::create_mmap(&path).unwrap();
+        let mut memory = IncrementalMerkleTree::<6>::new();
+        tree.append_batch(&leaves).unwrap();
+        memory.append_batch(&leaves).unwrap();
+        tree.prune_before(35).unwrap();
+        memory.prune_before(35).unwrap();
+
+        let exported: Vec<_> = tree.export_nodes().collect();
+        assert_eq!(exported, memory.export_nodes().collect::<Vec<_>>());
+        for gindex in 1..128 {
+            assert_eq!(tree.node(gindex), memory.node(gindex), "node {gindex}");
+        }
+        assert!(matches!(
+            tree.node(64),
+            Err(IncrementalMerkleTreeError::LeafUnavailable)
+        ));
+        drop(tree);
+        std::fs::remove_file(path).unwrap();
+    }
+}
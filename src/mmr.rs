@@ -0,0 +1,290 @@
+use alloc::{vec, vec::Vec};
+use alloy_primitives::B256;
+use core::marker::PhantomData;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    incremental_tree::bag_peaks_with_width,
+    IncrementalMerkleTreeError,
+};
+
+/// An inclusion proof for a leaf of a [MerkleMountainRange]: the path from the leaf up to the peak
+/// of its mountain, and every peak of the range so the root can be bagged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MmrProof {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The index of the leaf in the range
+    pub index: usize,
+    /// The number of leaves in the range
+    pub leaf_count: usize,
+    /// The siblings on the path from the leaf up to the peak of its mountain, starting at the leaf
+    /// level
+    pub siblings: Vec<B256>,
+    /// The peaks of the range, from the tallest (leftmost) to the shortest (rightmost)
+    pub peaks: Vec<B256>,
+}
+
+impl MmrProof {
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` under `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a range built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        if self.index >= self.leaf_count
+            || self.peaks.len() != self.leaf_count.count_ones() as usize
+        {
+            return false;
+        }
+
+        // Find the mountain holding the leaf: every set bit of the leaf count is a mountain of
+        // `2^height` leaves, from the tallest to the shortest.
+        let mut start = 0;
+        let mountain = (0..usize::BITS as usize)
+            .rev()
+            .filter(|height| (self.leaf_count >> height) & 1 == 1)
+            .enumerate()
+            .find_map(|(mountain, height)| {
+                start += 1 << height;
+                (self.index < start).then_some((mountain, height, start - (1 << height)))
+            });
+        let Some((mountain, height, first)) = mountain else {
+            return false;
+        };
+        if self.siblings.len() != height {
+            return false;
+        }
+
+        let mut position = self.index - first;
+        let peak = self.siblings.iter().fold(self.leaf, |node, sibling| {
+            let parent = if position & 1 == 1 {
+                H::hash_pair(sibling, &node)
+            } else {
+                H::hash_pair(&node, sibling)
+            };
+            position >>= 1;
+            parent
+        });
+        peak == self.peaks[mountain] && bag_peaks_with_width::<H>(&self.peaks, 32) == root
+    }
+}
+
+/// [MerkleMountainRange] is an append-only list of perfect binary trees, the mountains, whose peaks
+/// are bagged into a single root (see [bag_peaks](crate::bag_peaks)). Unlike an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) it has no fixed height, and its root only
+/// depends on the appended leaves.
+pub struct MerkleMountainRange<H = Keccak256Hasher> {
+    /// The nodes of the complete subtrees, by height and then by horizontal position
+    levels: Vec<Vec<B256>>,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> Default for MerkleMountainRange<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: MerkleHasher> MerkleMountainRange<H> {
+    /// Create a new, empty [MerkleMountainRange].
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new()],
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the number of leaves that have been appended to the range.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns `true` if no leaf has been appended to the range.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Append a new leaf, merging the mountains of equal height it completes.
+    ///
+    /// # Returns
+    /// - The index of the new leaf.
+    pub fn append(&mut self, leaf: B256) -> usize {
+        self.levels[0].push(leaf);
+        let mut height = 0;
+        while self.levels[height].len() & 1 == 0 {
+            let level = &self.levels[height];
+            let parent = H::hash_pair(&level[level.len() - 2], &level[level.len() - 1]);
+            height += 1;
+            if self.levels.len() == height {
+                self.levels.push(Vec::new());
+            }
+            self.levels[height].push(parent);
+        }
+        self.len() - 1
+    }
+
+    /// Returns the peaks of the range, from the tallest (leftmost) to the shortest (rightmost).
+    pub fn peaks(&self) -> Vec<B256> {
+        let leaf_count = self.len();
+        (0..self.levels.len())
+            .rev()
+            .filter(|height| (leaf_count >> height) & 1 == 1)
+            .map(|height| self.levels[height][(leaf_count >> height) - 1])
+            .collect()
+    }
+
+    /// Compute the root of the range by bagging its peaks.
+    ///
+    /// # Returns
+    /// - The root of the range, or [B256::ZERO] if it is empty.
+    pub fn root(&self) -> B256 {
+        bag_peaks_with_width::<H>(&self.peaks(), 32)
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf, its path to the peak of its mountain and the peaks.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    pub fn prove(&self, index: usize) -> Result<MmrProof, IncrementalMerkleTreeError> {
+        let leaf_count = self.len();
+        if index >= leaf_count {
+            return Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                index,
+                len: leaf_count,
+            });
+        }
+
+        // The leaf's mountain is the tallest one whose leaves do not all come before it, which is the
+        // highest bit where the leaf index and the leaf count differ.
+        let height = (usize::BITS - 1 - (index ^ leaf_count).leading_zeros()) as usize;
+        Ok(MmrProof {
+            leaf: self.levels[0][index],
+            index,
+            leaf_count,
+            siblings: (0..height)
+                .map(|level| self.levels[level][(index >> level) ^ 1])
+                .collect(),
+            peaks: self.peaks(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{hash_pair, leaves, naive_proof, naive_root};
+
+    /// Returns the first leaf and the height of every mountain of a range of `leaf_count` leaves, from
+    /// the tallest to the shortest.
+    fn mountains(leaf_count: usize) -> Vec<(usize, usize)> {
+        let mut first = 0;
+        (0..usize::BITS as usize)
+            .rev()
+            .filter(|height| (leaf_count >> height) & 1 == 1)
+            .map(|height| {
+                first += 1 << height;
+                (first - (1 << height), height)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_leaf_proves_against_the_naive_root() {
+        let mut range = MerkleMountainRange::<Keccak256Hasher>::new();
+        assert!(range.is_empty());
+        assert_eq!(range.root(), B256::ZERO);
+        assert!(range.peaks().is_empty());
+
+        let leaves = leaves(33);
+        for leaf_count in 1..=33 {
+            assert_eq!(range.append(leaves[leaf_count - 1]), leaf_count - 1);
+            assert_eq!(range.len(), leaf_count);
+
+            let mountains = mountains(leaf_count);
+            let peaks: Vec<_> = mountains
+                .iter()
+                .map(|&(first, height)| naive_root(&leaves[first..first + (1 << height)], height))
+                .collect();
+            let (last, rest) = peaks.split_last().unwrap();
+            let root = rest
+                .iter()
+                .rev()
+                .fold(*last, |bag, &peak| hash_pair(peak, bag));
+            assert_eq!(range.peaks(), peaks);
+            assert_eq!(range.root(), root);
+
+            for (index, &leaf) in leaves[..leaf_count].iter().enumerate() {
+                let &(first, height) = mountains
+                    .iter()
+                    .find(|&&(first, height)| index < first + (1 << height))
+                    .unwrap();
+                let proof = range.prove(index).unwrap();
+                assert_eq!(
+                    (proof.leaf, proof.index, proof.leaf_count),
+                    (leaf, index, leaf_count)
+                );
+                assert_eq!(
+                    proof.siblings,
+                    naive_proof(&leaves[first..first + (1 << height)], height, index - first)
+                );
+                assert_eq!(proof.peaks, peaks);
+                assert!(proof.verify(root), "leaf {index} of {leaf_count}");
+            }
+            assert_eq!(
+                range.prove(leaf_count),
+                Err(IncrementalMerkleTreeError::IndexOutOfBounds {
+                    index: leaf_count,
+                    len: leaf_count
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_are_rejected() {
+        let mut range = MerkleMountainRange::<Keccak256Hasher>::new();
+        for leaf in leaves(13) {
+            range.append(leaf);
+        }
+        let root = range.root();
+        for index in 0..13 {
+            let proof = range.prove(index).unwrap();
+            assert!(!proof.verify(B256::repeat_byte(1)));
+
+            let mut tampered = proof.clone();
+            tampered.leaf = B256::repeat_byte(1);
+            assert!(!tampered.verify(root));
+            let mut tampered = proof.clone();
+            tampered.index = (index + 1) % 13;
+            assert!(!tampered.verify(root));
+            let mut tampered = proof.clone();
+            tampered.index = 13;
+            assert!(!tampered.verify(root));
+            for leaf_count in [index, 12, 16] {
+                let mut tampered = proof.clone();
+                tampered.leaf_count = leaf_count;
+                assert!(!tampered.verify(root));
+            }
+            let mut tampered = proof.clone();
+            tampered.peaks.swap(0, 2);
+            assert!(!tampered.verify(root));
+            let mut tampered = proof.clone();
+            tampered.peaks.pop();
+            assert!(!tampered.verify(root));
+            let mut tampered = proof.clone();
+            tampered.siblings.push(B256::ZERO);
+            assert!(!tampered.verify(root));
+            for level in 0..proof.siblings.len() {
+                let mut tampered = proof.clone();
+                tampered.siblings[level] = B256::repeat_byte(1);
+                assert!(!tampered.verify(root));
+            }
+        }
+    }
+}
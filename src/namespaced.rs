@@ -0,0 +1,502 @@
+//! A namespaced merkle tree in the style of Celestia's NMT: every leaf carries a namespace, leaves
+//! are appended in namespace order, and every node commits to the range of namespaces below it. A
+//! [NamespaceProof] then shows either every leaf of a namespace, or that a namespace holds no leaf.
+//!
+//! Nodes are hashed as `H(0x00 || ns || data)` for leaves and
+//! `H(0x01 || left.min || left.max || left.hash || right.min || right.max || right.hash)` for
+//! parents, and the tree is split like an RFC 6962 tree, so a [NamespacedMerkleTree] of
+//! [Sha256Hasher](crate::Sha256Hasher) with [CELESTIA_NAMESPACE_SIZE] byte namespaces matches the
+//! roots of Celestia's NMT.
+
+use alloc::{vec, vec::Vec};
+use alloy_primitives::{FixedBytes, B256};
+use core::marker::PhantomData;
+
+use crate::{
+    hasher::{DigestHasher, Keccak256Hasher},
+    IncrementalMerkleTreeError,
+};
+
+/// The size in bytes of a Celestia namespace: a version byte and a 28-byte id.
+pub const CELESTIA_NAMESPACE_SIZE: usize = 29;
+
+/// A node of a [NamespacedMerkleTree]: its hash, and the range of namespaces of the leaves below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NamespacedNode<const NS: usize> {
+    /// The smallest namespace below the node
+    pub min: FixedBytes<NS>,
+    /// The largest namespace below the node, ignoring the parity namespace when other namespaces
+    /// are present
+    pub max: FixedBytes<NS>,
+    /// The hash of the node
+    pub hash: B256,
+}
+
+impl<const NS: usize> NamespacedNode<NS> {
+    /// The parity namespace, `0xff..ff`, which erasure-coded shares are appended under. The parent
+    /// of a node and a parity node keeps the largest namespace of the node, so namespace ranges stay
+    /// tight.
+    pub const PARITY_NAMESPACE: FixedBytes<NS> = FixedBytes([0xff; NS]);
+
+    /// Returns the root of an empty tree: the hash of the empty string, under the zero namespace.
+    pub fn empty<H: DigestHasher>() -> Self {
+        Self {
+            min: FixedBytes::ZERO,
+            max: FixedBytes::ZERO,
+            hash: H::hash_bytes(&[]),
+        }
+    }
+
+    /// Returns the leaf node of `data` under `namespace`.
+    pub fn leaf<H: DigestHasher>(namespace: FixedBytes<NS>, data: &[u8]) -> Self {
+        Self {
+            min: namespace,
+            max: namespace,
+            hash: H::hash_bytes(&[&[0x00], namespace.as_slice(), data]),
+        }
+    }
+
+    /// Returns the parent of `left` and `right`, whose namespaces must not be below those of `left`.
+    pub fn parent<H: DigestHasher>(left: &Self, right: &Self) -> Self {
+        let max = if right.min == Self::PARITY_NAMESPACE {
+            left.max
+        } else {
+            right.max
+        };
+        Self {
+            min: left.min,
+            max,
+            hash: H::hash_bytes(&[
+                &[0x01],
+                left.min.as_slice(),
+                left.max.as_slice(),
+                left.hash.as_slice(),
+                right.min.as_slice(),
+                right.max.as_slice(),
+                right.hash.as_slice(),
+            ]),
+        }
+    }
+}
+
+/// A proof about the leaves of a namespace in a [NamespacedMerkleTree]: the nodes covering every
+/// leaf outside of the range `start..end`, in order from left to right.
+///
+/// For an inclusion proof the range holds exactly the leaves of the namespace. For an absence proof
+/// it holds the first leaf of a larger namespace, given as [Self::absence_leaf], or is empty if the
+/// namespace lies outside the namespace range of the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceProof<const NS: usize> {
+    /// The index of the first leaf in the range
+    pub start: usize,
+    /// The index after the last leaf in the range
+    pub end: usize,
+    /// The number of leaves in the tree
+    pub leaf_count: usize,
+    /// The nodes covering the leaves before and after the range, from left to right
+    pub nodes: Vec<NamespacedNode<NS>>,
+    /// For an absence proof, the leaf at `start`, whose namespace follows the absent one
+    pub absence_leaf: Option<NamespacedNode<NS>>,
+}
+
+impl<const NS: usize> NamespaceProof<NS> {
+    /// Returns `true` if the proof shows that the leaves of `namespace` under `root` are exactly
+    /// `data`, in order, for a tree built with the hasher `H`.
+    pub fn verify_inclusion<H: DigestHasher>(
+        &self,
+        root: &NamespacedNode<NS>,
+        namespace: FixedBytes<NS>,
+        data: &[impl AsRef<[u8]>],
+    ) -> bool {
+        if self.absence_leaf.is_some()
+            || self.start >= self.end
+            || data.len() != self.end - self.start
+        {
+            return false;
+        }
+        let leaves: Vec<_> = data
+            .iter()
+            .map(|data| NamespacedNode::leaf::<H>(namespace, data.as_ref()))
+            .collect();
+        self.verify_range::<H>(root, namespace, &leaves)
+    }
+
+    /// Returns `true` if the proof shows that `namespace` holds no leaf under `root`, for a tree
+    /// built with the hasher `H`.
+    pub fn verify_absence<H: DigestHasher>(
+        &self,
+        root: &NamespacedNode<NS>,
+        namespace: FixedBytes<NS>,
+    ) -> bool {
+        match self.absence_leaf {
+            None => {
+                self.start == self.end
+                    && self.nodes.is_empty()
+                    && (*root == NamespacedNode::empty::<H>()
+                        || namespace < root.min
+                        || namespace > root.max)
+            }
+            Some(leaf) => {
+                self.end == self.start + 1
+                    && leaf.min > namespace
+                    && self.verify_range::<H>(root, namespace, &[leaf])
+            }
+        }
+    }
+
+    /// Rebuild the root from `leaves`, the nodes of the range, and the proof nodes, checking that
+    /// every node before the range lies below `namespace` and every node after it above.
+    fn verify_range<H: DigestHasher>(
+        &self,
+        root: &NamespacedNode<NS>,
+        namespace: FixedBytes<NS>,
+        leaves: &[NamespacedNode<NS>],
+    ) -> bool {
+        if self.end > self.leaf_count {
+            return false;
+        }
+        let mut nodes = self.nodes.iter();
+        let rebuilt = self.rebuild::<H>(0, self.leaf_count, namespace, leaves, &mut nodes);
+        nodes.next().is_none() && rebuilt.as_ref() == Some(root)
+    }
+
+    /// Rebuild the node covering the leaves `lo..hi`.
+    fn rebuild<'a, H: DigestHasher>(
+        &self,
+        lo: usize,
+        hi: usize,
+        namespace: FixedBytes<NS>,
+        leaves: &[NamespacedNode<NS>],
+        nodes: &mut impl Iterator<Item = &'a NamespacedNode<NS>>,
+    ) -> Option<NamespacedNode<NS>> {
+        if hi <= self.start || lo >= self.end {
+            let node = nodes.next()?;
+            let complete = if hi <= self.start {
+                node.max < namespace
+            } else {
+                node.min > namespace
+            };
+            return complete.then_some(*node);
+        }
+        if hi - lo == 1 {
+            return Some(leaves[lo - self.start]);
+        }
+        let split = lo + split_point(hi - lo);
+        let left = self.rebuild::<H>(lo, split, namespace, leaves, nodes)?;
+        let right = self.rebuild::<H>(split, hi, namespace, leaves, nodes)?;
+        Some(NamespacedNode::parent::<H>(&left, &right))
+    }
+}
+
+/// [NamespacedMerkleTree] is an append-only namespaced merkle tree. Like a
+/// [MerkleMountainRange](crate::MerkleMountainRange) it keeps every complete subtree, so its root
+/// and proofs only rehash the nodes of the incomplete subtrees on the right edge.
+pub struct NamespacedMerkleTree<const NS: usize, H = Keccak256Hasher> {
+    /// The nodes of the complete subtrees, by height and then by horizontal position
+    levels: Vec<Vec<NamespacedNode<NS>>>,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<const NS: usize, H: DigestHasher> Default for NamespacedMerkleTree<NS, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const NS: usize, H: DigestHasher> NamespacedMerkleTree<NS, H> {
+    /// Create a new, empty [NamespacedMerkleTree].
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new()],
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns `true` if no leaf has been appended to the tree.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Append `data` under `namespace`, which must not be below the namespace of the last leaf.
+    ///
+    /// # Returns
+    /// - `Ok(index)` with the index of the new leaf.
+    /// - `Err(IncrementalMerkleTreeError::NamespaceOutOfOrder)` if `namespace` is below the
+    ///   namespace of the last leaf; the tree is left unchanged.
+    pub fn append(
+        &mut self,
+        namespace: FixedBytes<NS>,
+        data: &[u8],
+    ) -> Result<usize, IncrementalMerkleTreeError> {
+        if self.levels[0]
+            .last()
+            .is_some_and(|last| namespace < last.min)
+        {
+            return Err(IncrementalMerkleTreeError::NamespaceOutOfOrder);
+        }
+        self.levels[0].push(NamespacedNode::leaf::<H>(namespace, data));
+        let mut height = 0;
+        while self.levels[height].len() & 1 == 0 {
+            let level = &self.levels[height];
+            let parent =
+                NamespacedNode::parent::<H>(&level[level.len() - 2], &level[level.len() - 1]);
+            height += 1;
+            if self.levels.len() == height {
+                self.levels.push(Vec::new());
+            }
+            self.levels[height].push(parent);
+        }
+        Ok(self.len() - 1)
+    }
+
+    /// Returns the root of the tree, or [NamespacedNode::empty] if no leaf has been appended.
+    pub fn root(&self) -> NamespacedNode<NS> {
+        if self.is_empty() {
+            return NamespacedNode::empty::<H>();
+        }
+        self.subtree(0, self.len())
+    }
+
+    /// Generate a proof of the leaves of `namespace`: an inclusion proof of all of them if there are
+    /// any, and an absence proof otherwise.
+    pub fn prove_namespace(&self, namespace: FixedBytes<NS>) -> NamespaceProof<NS> {
+        let leaves = &self.levels[0];
+        let start = leaves.partition_point(|leaf| leaf.min < namespace);
+        let end = leaves.partition_point(|leaf| leaf.min <= namespace);
+        let root = self.root();
+        if start == end
+            && (self.is_empty()
+                || namespace < root.min
+                || namespace > root.max
+                || start == leaves.len())
+        {
+            return NamespaceProof {
+                start: 0,
+                end: 0,
+                leaf_count: self.len(),
+                nodes: Vec::new(),
+                absence_leaf: None,
+            };
+        }
+        let (end, absence_leaf) = if start == end {
+            (start + 1, Some(leaves[start]))
+        } else {
+            (end, None)
+        };
+        let mut nodes = Vec::new();
+        self.range_nodes(0, self.len(), start, end, &mut nodes);
+        NamespaceProof {
+            start,
+            end,
+            leaf_count: self.len(),
+            nodes,
+            absence_leaf,
+        }
+    }
+
+    /// Push the nodes covering the leaves of `lo..hi` outside of `start..end` onto `nodes`, from left
+    /// to right.
+    fn range_nodes(
+        &self,
+        lo: usize,
+        hi: usize,
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<NamespacedNode<NS>>,
+    ) {
+        if hi <= start || lo >= end {
+            nodes.push(self.subtree(lo, hi));
+        } else if hi - lo > 1 && (lo < start || hi > end) {
+            let split = lo + split_point(hi - lo);
+            self.range_nodes(lo, split, start, end, nodes);
+            self.range_nodes(split, hi, start, end, nodes);
+        }
+    }
+
+    /// Returns the node covering the leaves `lo..hi`, a subtree of the tree as split by
+    /// [split_point]. Complete subtrees are read from the levels.
+    fn subtree(&self, lo: usize, hi: usize) -> NamespacedNode<NS> {
+        let width = hi - lo;
+        if width.is_power_of_two() {
+            let height = width.trailing_zeros() as usize;
+            return self.levels[height][lo >> height];
+        }
+        let split = lo + split_point(width);
+        NamespacedNode::parent::<H>(&self.subtree(lo, split), &self.subtree(split, hi))
+    }
+}
+
+/// Returns the number of leaves in the left subtree of a tree of `width > 1` leaves, the largest
+/// power of two below `width`.
+fn split_point(width: usize) -> usize {
+    1 << (usize::BITS - 1 - (width - 1).leading_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    type Node = NamespacedNode<2>;
+    type Tree = NamespacedMerkleTree<2, Keccak256Hasher>;
+    /// The namespaces of a tree in order, each with the data of its leaves
+    type Namespaces = Vec<(u8, Vec<Vec<u8>>)>;
+
+    /// Returns the namespace `[0, id]`.
+    fn ns(id: u8) -> FixedBytes<2> {
+        FixedBytes([0, id])
+    }
+
+    /// Returns the data of the leaf at `index`.
+    fn data(index: usize) -> Vec<u8> {
+        format!("share {index}").into_bytes()
+    }
+
+    /// Returns the root of `leaves`, computed by splitting them recursively at the largest power of
+    /// two below their number.
+    fn naive_root(leaves: &[Node]) -> Node {
+        match leaves.len() {
+            0 => Node::empty::<Keccak256Hasher>(),
+            1 => leaves[0],
+            len => {
+                let mut split = 1;
+                while split * 2 < len {
+                    split *= 2;
+                }
+                Node::parent::<Keccak256Hasher>(
+                    &naive_root(&leaves[..split]),
+                    &naive_root(&leaves[split..]),
+                )
+            }
+        }
+    }
+
+    /// Returns a tree of leaves under the namespaces `ids`, and the leaf data of each namespace.
+    fn tree(ids: &[u8]) -> (Tree, Namespaces) {
+        let mut tree = Tree::new();
+        let mut namespaces = Namespaces::new();
+        for (index, &id) in ids.iter().enumerate() {
+            assert_eq!(tree.append(ns(id), &data(index)), Ok(index));
+            match namespaces.last_mut() {
+                Some((last, data_of)) if *last == id => data_of.push(data(index)),
+                _ => namespaces.push((id, vec![data(index)])),
+            }
+        }
+        (tree, namespaces)
+    }
+
+    #[test]
+    fn roots_match_the_naive_split() {
+        let ids = [1, 1, 2, 4, 4, 4, 7, 9, 9, 12, 12, 12, 13];
+        let mut leaves = Vec::new();
+        let mut tree = Tree::new();
+        assert_eq!(tree.root(), naive_root(&[]));
+        for (index, &id) in ids.iter().enumerate() {
+            tree.append(ns(id), &data(index)).unwrap();
+            leaves.push(Node::leaf::<Keccak256Hasher>(ns(id), &data(index)));
+            let root = tree.root();
+            assert_eq!(root, naive_root(&leaves));
+            assert_eq!((root.min, root.max), (ns(1), ns(id)));
+        }
+    }
+
+    #[test]
+    fn every_namespace_proves_its_leaves_or_their_absence() {
+        let ids = [1, 1, 2, 4, 4, 4, 7, 9, 9, 12, 12, 12, 13];
+        for len in 1..=ids.len() {
+            let (tree, namespaces) = tree(&ids[..len]);
+            let root = tree.root();
+            for id in 0..=14 {
+                let proof = tree.prove_namespace(ns(id));
+                match namespaces.iter().find(|(present, _)| *present == id) {
+                    Some((_, data_of)) => {
+                        assert!(proof.verify_inclusion::<Keccak256Hasher>(&root, ns(id), data_of));
+                        assert!(!proof.verify_absence::<Keccak256Hasher>(&root, ns(id)));
+
+                        // Dropping or changing a leaf of the namespace breaks the proof.
+                        let mut missing = data_of.clone();
+                        missing.pop();
+                        assert!(!proof.verify_inclusion::<Keccak256Hasher>(
+                            &root,
+                            ns(id),
+                            &missing
+                        ));
+                        let mut changed = data_of.clone();
+                        changed[0].push(0);
+                        assert!(!proof.verify_inclusion::<Keccak256Hasher>(
+                            &root,
+                            ns(id),
+                            &changed
+                        ));
+                    }
+                    None => {
+                        assert!(proof.verify_absence::<Keccak256Hasher>(&root, ns(id)));
+                        assert!(!proof.verify_inclusion::<Keccak256Hasher>(
+                            &root,
+                            ns(id),
+                            &[data(0)]
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn absence_proofs_fail_for_present_namespaces() {
+        let (tree, namespaces) = tree(&[1, 1, 2, 4, 4, 4, 7, 9, 9]);
+        let root = tree.root();
+        // The absence proof of every namespace, reused for each present one: the leaf following the
+        // absent namespace is either in the present namespace, or the range before it is not below
+        // the present namespace.
+        for absent in [0, 3, 5, 6, 8, 10] {
+            let proof = tree.prove_namespace(ns(absent));
+            assert!(proof.verify_absence::<Keccak256Hasher>(&root, ns(absent)));
+            for (present, _) in &namespaces {
+                assert!(!proof.verify_absence::<Keccak256Hasher>(&root, ns(*present)));
+            }
+        }
+
+        // An absence proof claiming the first leaf of the present namespace follows it.
+        let inclusion = tree.prove_namespace(ns(4));
+        let forged = NamespaceProof {
+            end: inclusion.start + 1,
+            absence_leaf: Some(tree.levels[0][inclusion.start]),
+            ..tree.prove_namespace(ns(3))
+        };
+        assert!(!forged.verify_absence::<Keccak256Hasher>(&root, ns(4)));
+        // An empty proof only shows the absence of namespaces outside of the root's range.
+        let empty = tree.prove_namespace(ns(10));
+        assert_eq!(empty.absence_leaf, None);
+        assert!(empty.verify_absence::<Keccak256Hasher>(&root, ns(0)));
+        for (present, _) in &namespaces {
+            assert!(!empty.verify_absence::<Keccak256Hasher>(&root, ns(*present)));
+        }
+    }
+
+    #[test]
+    fn out_of_order_namespaces_are_rejected() {
+        let (mut tree, _) = tree(&[1, 4, 4]);
+        let root = tree.root();
+        for id in [0, 1, 3] {
+            assert_eq!(
+                tree.append(ns(id), b"late"),
+                Err(IncrementalMerkleTreeError::NamespaceOutOfOrder)
+            );
+            assert_eq!((tree.len(), tree.root()), (3, root));
+        }
+        assert_eq!(tree.append(ns(4), b"again"), Ok(3));
+        assert_eq!(tree.append(Node::PARITY_NAMESPACE, b"parity"), Ok(4));
+        assert_eq!(tree.root().max, ns(4));
+        assert_eq!(
+            tree.append(ns(5), b"after parity"),
+            Err(IncrementalMerkleTreeError::NamespaceOutOfOrder)
+        );
+    }
+}
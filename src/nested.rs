@@ -0,0 +1,181 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+
+use crate::{recover_root, IncrementalMerkleTree, IncrementalMerkleTreeError};
+
+/// [NestedMerkleTree] is a two-level commitment: an outer [IncrementalMerkleTree] of height `OUTER`
+/// whose leaves are the roots of inner trees of height `INNER`.
+///
+/// Inner trees are frozen once they are appended, so the outer leaf always matches the inner root.
+#[derive(Debug, Clone)]
+pub struct NestedMerkleTree<const OUTER: usize, const INNER: usize> {
+    /// The tree of subtree roots
+    outer: IncrementalMerkleTree<OUTER>,
+    /// The subtrees, indexed by their leaf index in `outer`
+    subtrees: Vec<IncrementalMerkleTree<INNER>>,
+}
+
+/// An inclusion proof for a leaf of an inner tree, against the root of the outer tree of a
+/// [NestedMerkleTree].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NestedProof<const OUTER: usize, const INNER: usize> {
+    /// The proven leaf of the inner tree
+    pub leaf: B256,
+    /// The index of the leaf within its inner tree
+    pub inner_index: usize,
+    /// The siblings on the path from the leaf to the inner root
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub inner_siblings: [B256; INNER],
+    /// The index of the inner root within the outer tree
+    pub outer_index: usize,
+    /// The siblings on the path from the inner root to the outer root
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub outer_siblings: [B256; OUTER],
+}
+
+impl<const OUTER: usize, const INNER: usize> Default for NestedMerkleTree<OUTER, INNER> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const OUTER: usize, const INNER: usize> NestedMerkleTree<OUTER, INNER> {
+    /// Create a new, empty [NestedMerkleTree].
+    pub fn new() -> Self {
+        Self {
+            outer: IncrementalMerkleTree::new(),
+            subtrees: Vec::new(),
+        }
+    }
+
+    /// Returns the root of the outer tree, which commits to every inner tree.
+    pub fn root(&self) -> B256 {
+        self.outer.root()
+    }
+
+    /// Returns the outer tree of subtree roots.
+    pub fn outer(&self) -> &IncrementalMerkleTree<OUTER> {
+        &self.outer
+    }
+
+    /// Returns the inner tree stored at `outer_index`, if any.
+    pub fn subtree(&self, outer_index: usize) -> Option<&IncrementalMerkleTree<INNER>> {
+        self.subtrees.get(outer_index)
+    }
+
+    /// Append `subtree` as the next leaf of the outer tree.
+    ///
+    /// # Returns
+    /// - `Ok(outer_index)` with the index the subtree was stored at.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the outer tree cannot hold any more
+    ///   leaves.
+    pub fn append_subtree(
+        &mut self,
+        subtree: IncrementalMerkleTree<INNER>,
+    ) -> Result<usize, IncrementalMerkleTreeError> {
+        self.outer.append(subtree.root())?;
+        self.subtrees.push(subtree);
+        Ok(self.subtrees.len() - 1)
+    }
+
+    /// Generate a proof for the leaf at `inner_index` of the subtree at `outer_index`, chaining the
+    /// inner proof with the proof of the subtree root in the outer tree.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with both legs of the proof.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if either index is out of range.
+    pub fn nested_proof(
+        &self,
+        outer_index: usize,
+        inner_index: usize,
+    ) -> Result<NestedProof<OUTER, INNER>, IncrementalMerkleTreeError> {
+        let subtree =
+            self.subtrees
+                .get(outer_index)
+                .ok_or(IncrementalMerkleTreeError::IndexOutOfBounds {
+                    index: outer_index,
+                    len: self.subtrees.len(),
+                })?;
+        let inner_siblings = subtree.siblings(inner_index)?;
+        let outer_siblings = self.outer.siblings(outer_index)?;
+        let leaf =
+            subtree
+                .leaf(inner_index)
+                .ok_or(IncrementalMerkleTreeError::IndexOutOfBounds {
+                    index: inner_index,
+                    len: subtree.len(),
+                })?;
+
+        Ok(NestedProof {
+            leaf,
+            inner_index,
+            inner_siblings,
+            outer_index,
+            outer_siblings,
+        })
+    }
+}
+
+impl<const OUTER: usize, const INNER: usize> NestedProof<OUTER, INNER> {
+    /// Returns the inner tree root implied by the inner leg of the proof.
+    pub fn inner_root(&self) -> B256 {
+        recover_root(self.leaf, self.inner_index, &self.inner_siblings)
+    }
+
+    /// Verify both legs of the proof: the leaf against its inner root, and that inner root against
+    /// the outer `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        recover_root(self.inner_root(), self.outer_index, &self.outer_siblings) == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{leaves, naive_root};
+
+    #[test]
+    fn nested_proofs_verify_against_the_top_root() {
+        let leaves = leaves(20);
+        let mut tree = NestedMerkleTree::<3, 4>::new();
+        let mut subtrees = Vec::new();
+        let mut start = 0;
+        for count in 2..7 {
+            subtrees.push(&leaves[start..start + count]);
+            tree.append_subtree(IncrementalMerkleTree::from_leaves(subtrees[count - 2]).unwrap())
+                .unwrap();
+            start += count;
+        }
+        let inner_roots: Vec<B256> = subtrees
+            .iter()
+            .map(|subtree| naive_root(subtree, 4))
+            .collect();
+        let root = tree.root();
+        assert_eq!(root, naive_root(&inner_roots, 3));
+
+        for (outer_index, subtree) in subtrees.iter().enumerate() {
+            for inner_index in 0..subtree.len() {
+                let proof = tree.nested_proof(outer_index, inner_index).unwrap();
+                assert_eq!(proof.inner_root(), inner_roots[outer_index]);
+                assert!(proof.verify(root));
+
+                let mut moved = proof.clone();
+                moved.inner_index ^= 1;
+                assert!(!moved.verify(root));
+                let mut moved = proof;
+                moved.outer_index ^= 1;
+                assert!(!moved.verify(root));
+            }
+        }
+    }
+
+    #[test]
+    fn nested_proof_rejects_missing_leaves() {
+        let mut tree = NestedMerkleTree::<3, 4>::new();
+        tree.append_subtree(IncrementalMerkleTree::from_leaves(&leaves(2)).unwrap())
+            .unwrap();
+        assert!(tree.nested_proof(1, 0).is_err());
+        assert!(tree.nested_proof(0, 2).is_err());
+    }
+}
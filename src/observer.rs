@@ -0,0 +1,20 @@
+use alloy_primitives::B256;
+
+/// [AppendObserver] is notified of every leaf accepted by an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) through
+/// [IncrementalMerkleTree::append_observed](crate::IncrementalMerkleTree::append_observed) and
+/// [IncrementalMerkleTree::append_batch_observed](crate::IncrementalMerkleTree::append_batch_observed),
+/// together with the root after it. Implement it to mirror the tree to a database or to emit events,
+/// without recomputing the root on every insert. Any `FnMut(usize, B256, B256)` closure is an
+/// observer.
+pub trait AppendObserver {
+    /// Called once `leaf` has been appended at `index`, with the `root` of the tree holding it as its
+    /// last leaf.
+    fn on_append(&mut self, index: usize, leaf: B256, root: B256);
+}
+
+impl<F: FnMut(usize, B256, B256)> AppendObserver for F {
+    fn on_append(&mut self, index: usize, leaf: B256, root: B256) {
+        self(index, leaf, root)
+    }
+}
@@ -0,0 +1,237 @@
+//! Helpers for proving OP Stack withdrawals with `OptimismPortal.proveWithdrawalTransaction`.
+//!
+//! Since Bedrock, the `L2ToL1MessagePasser` no longer maintains an incremental merkle tree: it marks
+//! every withdrawal hash in its `sentMessages` mapping, and the `withdrawalProof` expected by the
+//! portal is a Merkle-Patricia storage proof of that mapping entry, as returned by `eth_getProof`.
+//! This module computes everything else the portal checks: the withdrawal hash, the storage slot to
+//! request the proof for, and the output root committed to by the [OutputRootProof].
+
+use alloy_dyn_abi::DynSolValue;
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+
+use crate::leaf::LeafEncode;
+
+/// A withdrawal initiated through the `L2ToL1MessagePasser`, matching `Types.WithdrawalTransaction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Withdrawal {
+    /// The message nonce, with the message version in its two most significant bytes
+    pub nonce: U256,
+    /// The address that initiated the withdrawal on L2
+    pub sender: Address,
+    /// The address to call on L1
+    pub target: Address,
+    /// The ETH value to send with the call
+    pub value: U256,
+    /// The gas limit of the call
+    pub gas_limit: U256,
+    /// The calldata of the call
+    pub data: Bytes,
+}
+
+impl Withdrawal {
+    /// Returns the withdrawal hash, `keccak256(abi.encode(nonce, sender, target, value, gasLimit,
+    /// data))`, as computed by `Hashing.hashWithdrawal`.
+    pub fn hash(&self) -> B256 {
+        keccak256(
+            DynSolValue::Tuple(alloc::vec![
+                DynSolValue::Uint(self.nonce, 256),
+                DynSolValue::Address(self.sender),
+                DynSolValue::Address(self.target),
+                DynSolValue::Uint(self.value, 256),
+                DynSolValue::Uint(self.gas_limit, 256),
+                DynSolValue::Bytes(self.data.to_vec()),
+            ])
+            .abi_encode_params(),
+        )
+    }
+
+    /// Returns the storage slot of the withdrawal in the `sentMessages` mapping of the
+    /// `L2ToL1MessagePasser`, whose `eth_getProof` storage proof is the portal's `withdrawalProof`.
+    pub fn storage_slot(&self) -> B256 {
+        withdrawal_storage_slot(self.hash())
+    }
+}
+
+impl LeafEncode for Withdrawal {
+    /// Returns the withdrawal hash, the key of the withdrawal in the `sentMessages` mapping.
+    fn leaf_hash(&self) -> B256 {
+        self.hash()
+    }
+}
+
+/// Returns the storage slot of `withdrawal_hash` in the `sentMessages` mapping of the
+/// `L2ToL1MessagePasser`, which is declared at slot `0`: `keccak256(withdrawal_hash || 0)`.
+pub fn withdrawal_storage_slot(withdrawal_hash: B256) -> B256 {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(withdrawal_hash.as_slice());
+    keccak256(buf)
+}
+
+/// The preimage of an L2 output root, matching `Types.OutputRootProof`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputRootProof {
+    /// The version of the output root, currently [B256::ZERO]
+    pub version: B256,
+    /// The L2 state root
+    pub state_root: B256,
+    /// The storage root of the `L2ToL1MessagePasser`
+    pub message_passer_storage_root: B256,
+    /// The hash of the L2 block
+    pub latest_blockhash: B256,
+}
+
+impl OutputRootProof {
+    /// Returns the output root, `keccak256(version || stateRoot || messagePasserStorageRoot ||
+    /// latestBlockhash)`, as computed by `Hashing.hashOutputRootProof` and proposed to the
+    /// `L2OutputOracle`.
+    pub fn output_root(&self) -> B256 {
+        let mut buf = [0u8; 128];
+        buf[..32].copy_from_slice(self.version.as_slice());
+        buf[32..64].copy_from_slice(self.state_root.as_slice());
+        buf[64..96].copy_from_slice(self.message_passer_storage_root.as_slice());
+        buf[96..].copy_from_slice(self.latest_blockhash.as_slice());
+        keccak256(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn withdrawal() -> Withdrawal {
+        Withdrawal {
+            // Message version 1 in the two most significant bytes
+            nonce: (U256::from(1) << 240) | U256::from(7),
+            sender: Address::repeat_byte(0x11),
+            target: Address::repeat_byte(0x22),
+            value: U256::from(10).pow(U256::from(18)),
+            gas_limit: U256::from(100_000),
+            data: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+        }
+    }
+
+    /// Returns `address` as an ABI word.
+    fn address_word(address: Address) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(address.as_slice());
+        word
+    }
+
+    #[test]
+    fn withdrawal_hash_matches_the_abi_layout() {
+        let withdrawal = withdrawal();
+        let mut encoded = alloc::vec::Vec::new();
+        encoded.extend_from_slice(&withdrawal.nonce.to_be_bytes::<32>());
+        encoded.extend_from_slice(&address_word(withdrawal.sender));
+        encoded.extend_from_slice(&address_word(withdrawal.target));
+        encoded.extend_from_slice(&withdrawal.value.to_be_bytes::<32>());
+        encoded.extend_from_slice(&withdrawal.gas_limit.to_be_bytes::<32>());
+        // The offset of the calldata, after the six head words, then its length and padded bytes.
+        encoded.extend_from_slice(&U256::from(6 * 32).to_be_bytes::<32>());
+        encoded.extend_from_slice(&U256::from(4).to_be_bytes::<32>());
+        encoded.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        encoded.resize(encoded.len() + 28, 0);
+        assert_eq!(withdrawal.hash(), keccak256(&encoded));
+        assert_eq!(withdrawal.leaf_hash(), withdrawal.hash());
+    }
+
+    /// Returns withdrawals of every calldata shape: empty, shorter than a word, exactly one word and
+    /// spilling into a second word, with values at the bounds of their words.
+    fn withdrawals() -> impl Iterator<Item = Withdrawal> {
+        [0, 4, 32, 33, 100].into_iter().map(|len| Withdrawal {
+            nonce: (U256::from(1) << 240) | U256::from(len),
+            sender: Address::repeat_byte(len as u8),
+            target: Address::repeat_byte(0xff),
+            value: if len == 0 { U256::ZERO } else { U256::MAX },
+            gas_limit: U256::from(len as u64 * 21_000),
+            data: (0..len as u8).collect::<alloc::vec::Vec<_>>().into(),
+        })
+    }
+
+    #[cfg(feature = "sol")]
+    #[test]
+    fn withdrawal_hash_matches_the_sol_encoder() {
+        use alloy_sol_types::SolValue;
+
+        for withdrawal in withdrawals() {
+            // `Hashing.hashWithdrawal` hashes `abi.encode` of the six fields.
+            let encoded = (
+                withdrawal.nonce,
+                withdrawal.sender,
+                withdrawal.target,
+                withdrawal.value,
+                withdrawal.gas_limit,
+                withdrawal.data.clone(),
+            )
+                .abi_encode_params();
+            assert_eq!(withdrawal.hash(), keccak256(encoded));
+        }
+    }
+
+    #[test]
+    fn withdrawal_hash_pads_the_calldata() {
+        for withdrawal in withdrawals() {
+            let len = withdrawal.data.len();
+            let mut encoded = alloc::vec::Vec::new();
+            encoded.extend_from_slice(&withdrawal.nonce.to_be_bytes::<32>());
+            encoded.extend_from_slice(&address_word(withdrawal.sender));
+            encoded.extend_from_slice(&address_word(withdrawal.target));
+            encoded.extend_from_slice(&withdrawal.value.to_be_bytes::<32>());
+            encoded.extend_from_slice(&withdrawal.gas_limit.to_be_bytes::<32>());
+            encoded.extend_from_slice(&U256::from(6 * 32).to_be_bytes::<32>());
+            encoded.extend_from_slice(&U256::from(len).to_be_bytes::<32>());
+            encoded.extend_from_slice(&withdrawal.data);
+            encoded.resize(encoded.len() + len.next_multiple_of(32) - len, 0);
+            assert_eq!(encoded.len(), 7 * 32 + len.next_multiple_of(32));
+            assert_eq!(withdrawal.hash(), keccak256(&encoded));
+        }
+    }
+
+    #[test]
+    fn storage_slot_is_the_mapping_slot() {
+        let withdrawal = withdrawal();
+        let mut preimage = [0u8; 64];
+        preimage[..32].copy_from_slice(withdrawal.hash().as_slice());
+        assert_eq!(withdrawal.storage_slot(), keccak256(preimage));
+        assert_ne!(withdrawal.storage_slot(), withdrawal.hash());
+    }
+
+    #[test]
+    fn output_root_hashes_the_four_words() {
+        let proof = OutputRootProof {
+            version: B256::ZERO,
+            state_root: B256::repeat_byte(1),
+            message_passer_storage_root: B256::repeat_byte(2),
+            latest_blockhash: B256::repeat_byte(3),
+        };
+        let mut preimage = [0u8; 128];
+        preimage[32..64].fill(1);
+        preimage[64..96].fill(2);
+        preimage[96..].fill(3);
+        assert_eq!(proof.output_root(), keccak256(preimage));
+    }
+
+    #[cfg(feature = "sol")]
+    #[test]
+    fn output_root_matches_the_sol_encoder() {
+        use alloy_sol_types::SolValue;
+
+        let proof = OutputRootProof {
+            version: B256::ZERO,
+            state_root: B256::repeat_byte(0xaa),
+            message_passer_storage_root: B256::repeat_byte(0xbb),
+            latest_blockhash: B256::repeat_byte(0xcc),
+        };
+        // `Hashing.hashOutputRootProof` hashes `abi.encode` of the four words.
+        let encoded = (
+            proof.version,
+            proof.state_root,
+            proof.message_passer_storage_root,
+            proof.latest_blockhash,
+        )
+            .abi_encode_params();
+        assert_eq!(proof.output_root(), keccak256(encoded));
+    }
+}
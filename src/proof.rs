@@ -0,0 +1,899 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use core::ops::Range;
+use hashbrown::HashMap;
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    incremental_tree::{truncate, zero_hashes},
+};
+
+mod encoding;
+
+pub use crate::verify::{
+    recover_root, recover_root_with_directions, verify, verify_sorted, verify_with,
+};
+
+/// [MerkleProof] is an inclusion proof for a single leaf of an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) of height `HEIGHT`.
+///
+/// [Self::to_bytes] and [Self::from_bytes] convert it to and from its canonical binary encoding. With
+/// the `serde` feature, human-readable formats such as JSON write hashes as `0x`-prefixed hex:
+/// `{"leaf": "0x…", "index": 5, "siblings": ["0x…", …]}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MerkleProof<const HEIGHT: usize> {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The index of the leaf in the tree
+    pub index: usize,
+    /// The siblings on the path from the leaf up to the root, starting at the leaf level
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub siblings: [B256; HEIGHT],
+}
+
+impl<const HEIGHT: usize> MerkleProof<HEIGHT> {
+    /// Returns the root hash implied by the proof.
+    pub fn root(&self) -> B256 {
+        recover_root(self.leaf, self.index, &self.siblings)
+    }
+
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` under `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        verify(root, self.leaf, self.index, &self.siblings)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        verify_with::<H>(root, self.leaf, self.index, &self.siblings)
+    }
+
+    /// Like [Self::verify], for a tree built with the
+    /// [SortedKeccak256Hasher](crate::SortedKeccak256Hasher). The index is ignored, as it is by
+    /// OpenZeppelin's `MerkleProof.verify`, which accepts `self.siblings` as its `proof`.
+    pub fn verify_sorted(&self, root: B256) -> bool {
+        verify_sorted(root, self.leaf, &self.siblings)
+    }
+
+    /// Returns the [CompressedProof] of the same leaf, which omits the siblings that are keccak zero
+    /// hashes.
+    pub fn compress(&self) -> CompressedProof<HEIGHT> {
+        self.compress_with::<Keccak256Hasher>()
+    }
+
+    /// Like [Self::compress], for a tree built with the hasher `H`.
+    pub fn compress_with<H: MerkleHasher>(&self) -> CompressedProof<HEIGHT> {
+        self.compress_with_width::<H>(32)
+    }
+
+    /// Like [Self::compress_with], for a tree whose internal node hashes are truncated to `width`
+    /// bytes (see [crate::IncrementalMerkleTree::with_hash_width]), whose zero hashes are truncated
+    /// as well.
+    pub fn compress_with_width<H: MerkleHasher>(&self, width: usize) -> CompressedProof<HEIGHT> {
+        #[allow(clippy::let_unit_value)]
+        let () = CompressedProof::<HEIGHT>::SUPPORTED_HEIGHT;
+        let zero_hashes = zero_hashes::<H>(HEIGHT, width);
+        let mut zero_siblings = 0;
+        let mut siblings = Vec::new();
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            if *sibling == zero_hashes[height] {
+                zero_siblings |= 1 << height;
+            } else {
+                siblings.push(*sibling);
+            }
+        }
+        CompressedProof {
+            leaf: self.leaf,
+            index: self.index,
+            zero_siblings,
+            siblings,
+        }
+    }
+}
+
+/// [CompressedProof] is a [MerkleProof] without the siblings that are zero hashes, i.e. the roots of
+/// empty subtrees, which the verifier rebuilds itself. Most siblings of a leaf in a mostly empty tree
+/// are zero hashes, so the compressed proof of a recent leaf of a large tree carries only a few
+/// hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedProof<const HEIGHT: usize> {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The index of the leaf in the tree
+    pub index: usize,
+    /// Bit `i` is set when the sibling at height `i` is the zero hash of that height
+    pub zero_siblings: u64,
+    /// The siblings that are not zero hashes, starting at the leaf level
+    pub siblings: Vec<B256>,
+}
+
+impl<const HEIGHT: usize> CompressedProof<HEIGHT> {
+    /// Rejects, when a proof of this height is compressed or decompressed, heights whose siblings do
+    /// not all have a bit in `zero_siblings`.
+    const SUPPORTED_HEIGHT: () = assert!(
+        HEIGHT <= u64::BITS as usize,
+        "compressed proofs support heights up to the bit width of u64"
+    );
+
+    /// Rebuild the full [MerkleProof] of a keccak tree, or return `None` if the number of siblings
+    /// does not match the bitmap.
+    pub fn decompress(&self) -> Option<MerkleProof<HEIGHT>> {
+        self.decompress_with::<Keccak256Hasher>()
+    }
+
+    /// Like [Self::decompress], for a tree built with the hasher `H`.
+    pub fn decompress_with<H: MerkleHasher>(&self) -> Option<MerkleProof<HEIGHT>> {
+        self.decompress_with_width::<H>(32)
+    }
+
+    /// Like [Self::decompress_with], for a tree whose internal node hashes are truncated to `width`
+    /// bytes, as compressed by [MerkleProof::compress_with_width].
+    pub fn decompress_with_width<H: MerkleHasher>(
+        &self,
+        width: usize,
+    ) -> Option<MerkleProof<HEIGHT>> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED_HEIGHT;
+        if HEIGHT < u64::BITS as usize && self.zero_siblings >> HEIGHT != 0 {
+            return None;
+        }
+        let zeros = self.zero_siblings.count_ones() as usize;
+        if zeros + self.siblings.len() != HEIGHT {
+            return None;
+        }
+        let zero_hashes = zero_hashes::<H>(HEIGHT, width);
+        let mut siblings = self.siblings.iter();
+        let mut full = [B256::ZERO; HEIGHT];
+        for (height, sibling) in full.iter_mut().enumerate() {
+            *sibling = if (self.zero_siblings >> height) & 1 == 1 {
+                zero_hashes[height]
+            } else {
+                *siblings.next()?
+            };
+        }
+        Some(MerkleProof {
+            leaf: self.leaf,
+            index: self.index,
+            siblings: full,
+        })
+    }
+
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` in the keccak tree
+    /// committed to by `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        self.decompress_with::<H>()
+            .is_some_and(|proof| proof.verify_with::<H>(root))
+    }
+}
+
+/// Verify a batch of `(index, leaf, siblings)` inclusion proofs against the same `root`, as [verify]
+/// does for each of them, but hashing every node shared by their paths only once.
+///
+/// Every node rebuilt by a proof that reached `root` is remembered together with its siblings, so a
+/// later proof stops hashing as soon as its path meets one of them: it is valid if it rebuilt the same
+/// node and its remaining siblings are the remembered ones, and the batch fails otherwise. Verifying
+/// the proofs of a contiguous range of leaves thus costs about one hash per node of the subtree they
+/// span, instead of `HEIGHT` hashes per proof.
+///
+/// # Returns
+/// - `true` if every proof is valid, including for an empty batch.
+/// - `false` as soon as one proof is invalid, or the proofs are not all of the same height.
+pub fn verify_batch<P: AsRef<[B256]>>(root: B256, proofs: &[(usize, B256, P)]) -> bool {
+    verify_batch_with::<Keccak256Hasher, P>(root, proofs)
+}
+
+/// Like [verify_batch], for a tree built with the hasher `H`.
+pub fn verify_batch_with<H: MerkleHasher, P: AsRef<[B256]>>(
+    root: B256,
+    proofs: &[(usize, B256, P)],
+) -> bool {
+    let Some(height) = proofs
+        .first()
+        .map(|(_, _, siblings)| siblings.as_ref().len())
+    else {
+        return true;
+    };
+
+    // The nodes known to lie under `root`, keyed by height and position.
+    let mut verified: HashMap<(usize, usize), B256> = HashMap::new();
+    let mut path = Vec::with_capacity(2 * height);
+    for (index, leaf, siblings) in proofs {
+        let siblings = siblings.as_ref();
+        if siblings.len() != height || (height < usize::BITS as usize && index >> height != 0) {
+            return false;
+        }
+
+        path.clear();
+        let mut node = *leaf;
+        let mut joined = None;
+        for (level, sibling) in siblings.iter().enumerate() {
+            let position = index >> level;
+            if let Some(known) = verified.get(&(level, position)) {
+                if *known != node {
+                    return false;
+                }
+                joined = Some(level);
+                break;
+            }
+            path.push(((level, position), node));
+            path.push(((level, position ^ 1), *sibling));
+            node = if position & 1 == 1 {
+                H::hash_pair(sibling, &node)
+            } else {
+                H::hash_pair(&node, sibling)
+            };
+        }
+        match joined {
+            // Every ancestor of a verified node is verified together with its sibling, so the rest of
+            // the path is checked without hashing.
+            Some(level) => {
+                let siblings_match = (level..height).all(|level| {
+                    verified.get(&(level, (index >> level) ^ 1)) == Some(&siblings[level])
+                });
+                if !siblings_match {
+                    return false;
+                }
+            }
+            None if node != root => return false,
+            None => {}
+        }
+        verified.extend(path.drain(..));
+    }
+    true
+}
+
+/// Like [recover_root], for trees whose internal node hashes are truncated to `width` bytes (see
+/// [crate::IncrementalMerkleTree::with_hash_width]).
+pub fn recover_root_with_width<const HEIGHT: usize>(
+    leaf: B256,
+    index: usize,
+    siblings: &[B256; HEIGHT],
+    width: usize,
+) -> B256 {
+    let mut index = index;
+    siblings.iter().fold(leaf, |node, sibling| {
+        let parent = if index & 1 == 1 {
+            truncate(Keccak256Hasher::hash_pair(sibling, &node), width)
+        } else {
+            truncate(Keccak256Hasher::hash_pair(&node, sibling), width)
+        };
+        index >>= 1;
+        parent
+    })
+}
+
+/// [ArityMerkleProof] is an inclusion proof for a single leaf of an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) of height `HEIGHT` and branching factor
+/// `ARITY`, as returned by its `prove_arity`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArityMerkleProof<const HEIGHT: usize, const ARITY: usize> {
+    /// The proven leaf
+    pub leaf: B256,
+    /// The index of the leaf in the tree
+    pub index: usize,
+    /// For every level from the leaves upwards, the `ARITY - 1` siblings of the node on the path, in
+    /// order and without the node itself
+    pub siblings: Vec<Vec<B256>>,
+}
+
+impl<const HEIGHT: usize, const ARITY: usize> ArityMerkleProof<HEIGHT, ARITY> {
+    /// Returns `true` if the proof shows that `self.leaf` sits at `self.index` under `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        if self.siblings.len() != HEIGHT
+            || self.siblings.iter().any(|level| level.len() + 1 != ARITY)
+        {
+            return false;
+        }
+
+        let mut position = self.index;
+        let mut node = self.leaf;
+        for level in &self.siblings {
+            let slot = position % ARITY;
+            let mut children = level.clone();
+            children.insert(slot, node);
+            node = H::hash_children(&children);
+            position /= ARITY;
+        }
+        position == 0 && node == root
+    }
+}
+
+/// [ConsistencyProof] shows that an [IncrementalMerkleTree](crate::IncrementalMerkleTree) of height
+/// `HEIGHT` holding `new_size` leaves is an append-only extension of the same tree when it held
+/// `old_size` leaves, in the style of certificate transparency.
+///
+/// Starting from the shortest complete subtree of the old tree, `peak`, both roots are rebuilt along
+/// the same path. The old tree's complete subtrees to the left of the path are shared by both trees,
+/// while the nodes to its right are zero hashes in the old tree and taken from the new tree. The
+/// proof commits to the padded roots of the trees ([RootMode::PaddedFixedHeight](crate::RootMode)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConsistencyProof<const HEIGHT: usize> {
+    /// The number of leaves in the old tree
+    pub old_size: usize,
+    /// The number of leaves in the new tree
+    pub new_size: usize,
+    /// The root of the shortest complete subtree of the old tree, or [B256::ZERO] if it is empty
+    pub peak: B256,
+    /// The siblings on the path from `peak` up to the root, starting at the height of `peak`
+    pub siblings: Vec<B256>,
+}
+
+impl<const HEIGHT: usize> ConsistencyProof<HEIGHT> {
+    /// Returns `true` if the proof shows that the tree committed to by `new_root` extends the tree
+    /// committed to by `old_root`.
+    pub fn verify(&self, old_root: B256, new_root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(old_root, new_root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, old_root: B256, new_root: B256) -> bool {
+        if self.old_size > self.new_size
+            || (HEIGHT < usize::BITS as usize && self.new_size >> HEIGHT != 0)
+        {
+            return false;
+        }
+        if self.old_size == 0 {
+            // Every tree extends the empty tree, whose root is the zero hash above `HEIGHT` levels.
+            let empty = (0..HEIGHT).fold(B256::ZERO, |zero, _| H::hash_pair(&zero, &zero));
+            return self.siblings.is_empty() && old_root == empty;
+        }
+
+        let base = self.old_size.trailing_zeros() as usize;
+        if self.siblings.len() != HEIGHT - base {
+            return false;
+        }
+        let mut zero = (0..base).fold(B256::ZERO, |zero, _| H::hash_pair(&zero, &zero));
+        let mut position = (self.old_size >> base) - 1;
+        let (mut old, mut new) = (self.peak, self.peak);
+        for sibling in &self.siblings {
+            if position & 1 == 1 {
+                old = H::hash_pair(sibling, &old);
+                new = H::hash_pair(sibling, &new);
+            } else {
+                old = H::hash_pair(&old, &zero);
+                new = H::hash_pair(&new, sibling);
+            }
+            zero = H::hash_pair(&zero, &zero);
+            position >>= 1;
+        }
+        old == old_root && new == new_root
+    }
+}
+
+/// [MultiProof] proves several leaves of an [IncrementalMerkleTree](crate::IncrementalMerkleTree) of
+/// height `HEIGHT` at once. Internal nodes shared by the paths of the proven leaves are recomputed by
+/// the verifier instead of being included, so the proof carries each needed sibling only once.
+///
+/// The verifier walks the tree level by level over the proven positions, in ascending order. At every
+/// step one entry of `flags` is consumed: `true` when the next two proven positions are siblings and
+/// are hashed together, `false` when the sibling is taken from the front of `siblings`.
+///
+/// Like [MerkleProof], it has a canonical binary encoding and writes hashes as hex in JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiProof<const HEIGHT: usize> {
+    /// The proven leaf indices, strictly ascending
+    pub indices: Vec<usize>,
+    /// The proven leaves, matching `indices`
+    pub leaves: Vec<B256>,
+    /// The siblings that cannot be derived from the proven leaves, in the order they are consumed
+    pub siblings: Vec<B256>,
+    /// One descriptor bit per merge step, `true` when both children are derived
+    pub flags: Vec<bool>,
+}
+
+impl<const HEIGHT: usize> MultiProof<HEIGHT> {
+    /// Returns `true` if the proof shows that every leaf sits at its index under `root`. Proofs with
+    /// no leaves, unsorted or out-of-range indices, or leftover siblings or flags are rejected.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        let indices_valid = !self.indices.is_empty()
+            && self.indices.len() == self.leaves.len()
+            && self.indices.windows(2).all(|pair| pair[0] < pair[1])
+            && (HEIGHT >= usize::BITS as usize
+                || self.indices.iter().all(|index| index >> HEIGHT == 0));
+        if !indices_valid {
+            return false;
+        }
+
+        let mut nodes: Vec<(usize, B256)> = self
+            .indices
+            .iter()
+            .copied()
+            .zip(self.leaves.iter().copied())
+            .collect();
+        let mut siblings = self.siblings.iter();
+        let mut flags = self.flags.iter();
+        for _ in 0..HEIGHT {
+            let mut parents = Vec::with_capacity(nodes.len());
+            let mut i = 0;
+            while i < nodes.len() {
+                let (position, node) = nodes[i];
+                let paired = position & 1 == 0
+                    && nodes.get(i + 1).is_some_and(|next| next.0 == position + 1);
+                if flags.next() != Some(&paired) {
+                    return false;
+                }
+                let parent = if paired {
+                    i += 1;
+                    H::hash_pair(&node, &nodes[i].1)
+                } else {
+                    let Some(sibling) = siblings.next() else {
+                        return false;
+                    };
+                    if position & 1 == 1 {
+                        H::hash_pair(sibling, &node)
+                    } else {
+                        H::hash_pair(&node, sibling)
+                    }
+                };
+                parents.push((position >> 1, parent));
+                i += 1;
+            }
+            nodes = parents;
+        }
+
+        siblings.next().is_none() && flags.next().is_none() && nodes[0].1 == root
+    }
+}
+
+/// [RangeProof] proves a contiguous run of leaves of an
+/// [IncrementalMerkleTree](crate::IncrementalMerkleTree) of height `HEIGHT`, starting at `start`.
+/// Every node above the run is recomputed by the verifier, so the proof only carries the nodes
+/// bordering the run on each level: at most two siblings per level, however many leaves it covers.
+///
+/// The verifier walks the run up the tree. On every level, the left neighbour of the run is taken
+/// from the front of `siblings` if the run starts at a right child, and then its right neighbour if
+/// the run ends at a left child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeProof<const HEIGHT: usize> {
+    /// The index of the first proven leaf
+    pub start: usize,
+    /// The proven leaves, in order
+    pub leaves: Vec<B256>,
+    /// The nodes bordering the run, level by level from the leaves up, in the order they are consumed
+    pub siblings: Vec<B256>,
+}
+
+impl<const HEIGHT: usize> RangeProof<HEIGHT> {
+    /// Returns the indices of the proven leaves.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.leaves.len()
+    }
+
+    /// Returns `true` if the proof shows that the leaves sit at their indices under `root`. Proofs
+    /// with no leaves, leaves beyond the capacity of the tree, or leftover siblings are rejected.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        let Some(end) = self.start.checked_add(self.leaves.len()) else {
+            return false;
+        };
+        if self.leaves.is_empty() || (HEIGHT < usize::BITS as usize && end > 1 << HEIGHT) {
+            return false;
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut nodes = self.leaves.clone();
+        let mut start = self.start;
+        for _ in 0..HEIGHT {
+            if start & 1 == 1 {
+                let Some(sibling) = siblings.next() else {
+                    return false;
+                };
+                nodes.insert(0, *sibling);
+                start -= 1;
+            }
+            if nodes.len() & 1 == 1 {
+                let Some(sibling) = siblings.next() else {
+                    return false;
+                };
+                nodes.push(*sibling);
+            }
+            nodes = nodes
+                .chunks_exact(2)
+                .map(|pair| H::hash_pair(&pair[0], &pair[1]))
+                .collect();
+            start >>= 1;
+        }
+
+        siblings.next().is_none() && nodes.len() == 1 && nodes[0] == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{leaves, naive_root},
+        IncrementalMerkleTree, IncrementalMerkleTreeError,
+    };
+
+    /// Returns a tree of height 5 holding `count` leaves, the leaves and the root of the tree.
+    fn tree(count: usize) -> (IncrementalMerkleTree<5>, Vec<B256>, B256) {
+        let leaves = leaves(count);
+        let tree = IncrementalMerkleTree::<5>::from_leaves(&leaves).unwrap();
+        let root = naive_root(&leaves, 5);
+        (tree, leaves, root)
+    }
+
+    /// Returns the root of the complete subtree holding `leaves`, whose number is a power of two.
+    fn naive_subtree_root(leaves: &[B256]) -> B256 {
+        naive_root(leaves, leaves.len().trailing_zeros() as usize)
+    }
+
+    #[test]
+    fn compressed_proofs_round_trip() {
+        let mut tree = IncrementalMerkleTree::<16>::new();
+        for leaf in leaves(5) {
+            tree.append(leaf).unwrap();
+        }
+        let proof = tree.prove(4).unwrap();
+        let compressed = proof.compress();
+        assert_eq!(compressed.siblings.len(), 1);
+        assert_eq!(compressed.decompress(), Some(proof));
+        assert!(compressed.verify(tree.root()));
+    }
+
+    #[test]
+    fn truncated_trees_compress_against_their_own_zero_hashes() {
+        let mut tree = IncrementalMerkleTree::<16>::with_hash_width(16);
+        for leaf in leaves(5) {
+            tree.append(leaf).unwrap();
+        }
+        let proof = tree.prove(4).unwrap();
+        assert_eq!(proof.compress().siblings.len(), 15);
+
+        let compressed = proof.compress_with_width::<Keccak256Hasher>(tree.hash_width());
+        assert_eq!(compressed.siblings.len(), 1);
+        let decompressed = compressed
+            .decompress_with_width::<Keccak256Hasher>(tree.hash_width())
+            .unwrap();
+        assert_eq!(
+            recover_root_with_width(decompressed.leaf, 4, &decompressed.siblings, 16),
+            tree.root()
+        );
+        assert_eq!(decompressed, proof);
+    }
+
+    #[test]
+    fn tallest_compressed_proof_round_trips() {
+        let proof = MerkleProof::<64> {
+            leaf: leaves(1)[0],
+            index: 0,
+            siblings: zero_hashes::<Keccak256Hasher>(64, 32)
+                .as_ref()
+                .try_into()
+                .unwrap(),
+        };
+        let compressed = proof.compress();
+        assert_eq!(compressed.zero_siblings, u64::MAX);
+        assert_eq!(compressed.decompress(), Some(proof));
+    }
+
+    #[test]
+    fn multiproofs_verify_every_index_set() {
+        let (mut tree, leaves, root) = tree(21);
+        let mut sets: Vec<Vec<usize>> = alloc::vec![
+            alloc::vec![0, 1],
+            alloc::vec![4, 5, 6, 7],
+            alloc::vec![2, 3, 4],
+            alloc::vec![0, 20],
+            alloc::vec![20, 0, 7, 7, 3],
+            alloc::vec![9, 8, 8, 9],
+            (0..21).rev().collect(),
+        ];
+        // Every pair of leaves, overlapping, adjacent or apart, in descending order.
+        sets.extend(
+            (0..21).flat_map(|first| (first..21).map(move |last| alloc::vec![last, first])),
+        );
+        for indices in sets {
+            let proof = tree.prove_multi(&indices).unwrap();
+            let mut expected = indices.clone();
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(proof.indices, expected);
+            assert!(proof
+                .leaves
+                .iter()
+                .zip(&expected)
+                .all(|(leaf, &index)| *leaf == leaves[index]));
+            assert!(proof.verify(root), "indices {indices:?}");
+        }
+    }
+
+    #[test]
+    fn tampered_multiproofs_are_rejected() {
+        let (mut tree, _, root) = tree(21);
+        let proof = tree.prove_multi(&[2, 3, 9, 16]).unwrap();
+        assert!(proof.verify(root));
+        assert!(!proof.verify(B256::repeat_byte(1)));
+
+        let tampered = |tamper: &dyn Fn(&mut MultiProof<5>)| {
+            let mut tampered = proof.clone();
+            tamper(&mut tampered);
+            tampered.verify(root)
+        };
+        for i in 0..proof.leaves.len() {
+            assert!(!tampered(&|proof| proof.leaves[i] = B256::repeat_byte(1)));
+        }
+        for i in 0..proof.siblings.len() {
+            assert!(!tampered(&|proof| proof.siblings[i] = B256::repeat_byte(1)));
+        }
+        for i in 0..proof.flags.len() {
+            assert!(!tampered(&|proof| proof.flags[i] = !proof.flags[i]));
+        }
+        assert!(!tampered(&|proof| {
+            proof.siblings.pop();
+        }));
+        assert!(!tampered(&|proof| proof.siblings.push(B256::ZERO)));
+        assert!(!tampered(&|proof| {
+            proof.flags.pop();
+        }));
+        assert!(!tampered(&|proof| proof.flags.push(false)));
+        assert!(!tampered(&|proof| proof.indices.swap(0, 1)));
+        assert!(!tampered(&|proof| proof.indices[1] = 2));
+        assert!(!tampered(&|proof| proof.indices[3] = 1 << 5));
+        assert!(!tampered(&|proof| {
+            proof.leaves.pop();
+        }));
+        assert!(!tampered(&|proof| {
+            proof.indices.clear();
+            proof.leaves.clear();
+        }));
+    }
+
+    #[test]
+    fn consistency_proofs_verify_every_pair_of_sizes() {
+        let (mut tree, leaves, _) = tree(21);
+        for new_size in 0..=21 {
+            let new_root = naive_root(&leaves[..new_size], 5);
+            for old_size in 0..=new_size {
+                let old_root = naive_root(&leaves[..old_size], 5);
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                assert!(
+                    proof.verify(old_root, new_root),
+                    "sizes {old_size} and {new_size}"
+                );
+                let base = if old_size == 0 {
+                    5
+                } else {
+                    old_size.trailing_zeros() as usize
+                };
+                assert_eq!(proof.siblings.len(), 5 - base);
+            }
+        }
+        assert!(tree.consistency_proof(5, 4).is_err());
+        assert!(tree.consistency_proof(4, 22).is_err());
+    }
+
+    #[test]
+    fn consistency_proof_edge_cases() {
+        let (mut tree, leaves, root) = tree(21);
+        let empty = naive_root(&[], 5);
+
+        // Every tree extends the empty tree, and no other root stands for it.
+        let proof = tree.consistency_proof(0, 21).unwrap();
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify(empty, root));
+        assert!(!proof.verify(naive_root(&leaves[..1], 5), root));
+
+        // A tree extends itself.
+        let proof = tree.consistency_proof(13, 13).unwrap();
+        let old_root = naive_root(&leaves[..13], 5);
+        assert!(proof.verify(old_root, old_root));
+        assert!(!proof.verify(old_root, root));
+
+        // An old tree of a power of two leaves is a single complete subtree.
+        for old_size in [1, 2, 4, 8, 16] {
+            let proof = tree.consistency_proof(old_size, 21).unwrap();
+            assert_eq!(proof.peak, naive_subtree_root(&leaves[..old_size]));
+            assert!(proof.verify(naive_root(&leaves[..old_size], 5), root));
+        }
+
+        // Sizes the tree cannot hold are rejected however the proof is built.
+        let mut proof = tree.consistency_proof(8, 21).unwrap();
+        proof.old_size = 22;
+        assert!(!proof.verify(naive_root(&leaves[..8], 5), root));
+        proof.old_size = 8;
+        proof.new_size = 1 << 5;
+        assert!(!proof.verify(naive_root(&leaves[..8], 5), root));
+    }
+
+    #[test]
+    fn consistency_proofs_reject_rewritten_history() {
+        let (mut tree, leaves, root) = tree(21);
+        for old_size in 1..=21 {
+            let old_root = naive_root(&leaves[..old_size], 5);
+            for rewritten in 0..old_size {
+                // The new tree holds the same number of leaves, one of which changed before `old_size`.
+                let mut forked_leaves = leaves.clone();
+                forked_leaves[rewritten] = B256::repeat_byte(1);
+                let mut forked = IncrementalMerkleTree::<5>::from_leaves(&forked_leaves).unwrap();
+                let forked_root = forked.root();
+                assert!(!tree
+                    .consistency_proof(old_size, 21)
+                    .unwrap()
+                    .verify(old_root, forked_root));
+                assert!(!forked
+                    .consistency_proof(old_size, 21)
+                    .unwrap()
+                    .verify(old_root, forked_root));
+                assert!(!forked
+                    .consistency_proof(old_size, 21)
+                    .unwrap()
+                    .verify(old_root, root));
+            }
+        }
+    }
+
+    /// Returns the `(index, leaf, siblings)` proofs of the leaves at `indices`.
+    fn batch(
+        tree: &mut IncrementalMerkleTree<5>,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Vec<(usize, B256, [B256; 5])> {
+        indices
+            .into_iter()
+            .map(|index| {
+                let proof = tree.prove(index).unwrap();
+                (proof.index, proof.leaf, proof.siblings)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batches_with_shared_paths_verify() {
+        let (mut tree, _, root) = tree(21);
+        // Overlapping paths, in any order and with duplicates, agree on every node they share.
+        for indices in [
+            alloc::vec![0, 1, 2, 3],
+            alloc::vec![20, 3, 17, 4],
+            alloc::vec![7, 7, 8, 7],
+            (0..21).collect(),
+            (0..21).rev().collect(),
+        ] {
+            assert!(
+                verify_batch(root, &batch(&mut tree, indices.clone())),
+                "{indices:?}"
+            );
+        }
+        assert!(verify_batch::<[B256; 5]>(root, &[]));
+        assert!(!verify_batch(
+            B256::repeat_byte(1),
+            &batch(&mut tree, [4, 5])
+        ));
+    }
+
+    #[test]
+    fn one_corrupted_proof_fails_the_batch() {
+        let (mut tree, _, root) = tree(21);
+        let proofs = batch(&mut tree, 0..21);
+        for corrupted in 0..proofs.len() {
+            for level in 0..5 {
+                let mut tampered = proofs.clone();
+                tampered[corrupted].2[level] = B256::repeat_byte(1);
+                assert!(!verify_batch(root, &tampered));
+            }
+            let mut tampered = proofs.clone();
+            tampered[corrupted].1 = B256::repeat_byte(1);
+            assert!(!verify_batch(root, &tampered));
+
+            // The proof of a leaf claimed at another index, even one whose path the batch already
+            // verified.
+            let mut tampered = proofs.clone();
+            tampered[corrupted].0 = (corrupted + 1) % proofs.len();
+            assert!(!verify_batch(root, &tampered));
+        }
+
+        // A duplicate that disagrees with the first proof of the same leaf.
+        let mut duplicated = batch(&mut tree, [6, 9, 6]);
+        duplicated[2].2[4] = B256::repeat_byte(1);
+        assert!(!verify_batch(root, &duplicated));
+
+        // Proofs of another height, or of an index beyond the tree.
+        let mut proofs: Vec<(usize, B256, Vec<B256>)> = batch(&mut tree, [2, 3])
+            .into_iter()
+            .map(|(index, leaf, siblings)| (index, leaf, siblings.to_vec()))
+            .collect();
+        proofs[1].2.pop();
+        assert!(!verify_batch(root, &proofs));
+        let mut proofs = batch(&mut tree, [2, 3]);
+        proofs[1].0 += 1 << 5;
+        assert!(!verify_batch(root, &proofs));
+    }
+
+    #[test]
+    fn range_proofs_verify_every_range() {
+        let (mut tree, leaves, root) = tree(21);
+        // Every range of the tree, which covers ranges at the start, the end and in the middle, single
+        // leaves, and ranges crossing the power-of-two boundaries at 8 and 16, such as 7..9 and 15..17.
+        for start in 0..21 {
+            for end in start + 1..=21 {
+                let proof = tree.prove_range(start..end).unwrap();
+                assert_eq!(proof.range(), start..end);
+                assert_eq!(proof.leaves, leaves[start..end]);
+                assert!(proof.siblings.len() <= 2 * 5);
+                assert!(proof.verify(root), "{start}..{end}");
+            }
+        }
+
+        // Only the empty last leaf borders the range of a full tree.
+        let (mut full, _, root) = self::tree(31);
+        let proof = full.prove_range(0..31).unwrap();
+        assert_eq!(proof.siblings, [B256::ZERO]);
+        assert!(proof.verify(root));
+    }
+
+    #[test]
+    fn shifted_and_oversized_range_proofs_are_rejected() {
+        let (mut tree, leaves, root) = tree(21);
+        for range in [0..1, 0..5, 7..9, 15..17, 12..13, 16..21, 20..21] {
+            let proof = tree.prove_range(range.clone()).unwrap();
+            for start in [
+                range.start.wrapping_sub(1),
+                range.start + 1,
+                range.start + 2,
+            ] {
+                let shifted = RangeProof::<5> {
+                    start,
+                    ..proof.clone()
+                };
+                assert!(!shifted.verify(root), "{range:?} shifted to {start}");
+            }
+
+            let mut tampered = proof.clone();
+            tampered.leaves[0] = B256::repeat_byte(1);
+            assert!(!tampered.verify(root));
+            let mut extended = proof.clone();
+            extended
+                .leaves
+                .push(leaves.get(range.end).copied().unwrap_or_default());
+            assert!(!extended.verify(root));
+            let mut extra = proof.clone();
+            extra.siblings.push(B256::ZERO);
+            assert!(!extra.verify(root));
+        }
+
+        // Claims beyond the appended leaves cannot be proven, and claims beyond the capacity of the
+        // tree never verify.
+        for range in [21..22, 19..22, 0..22, 5..5] {
+            assert!(matches!(
+                tree.prove_range(range),
+                Err(IncrementalMerkleTreeError::IndexOutOfBounds { len: 21, .. })
+            ));
+        }
+        let beyond = RangeProof::<5> {
+            start: 31,
+            leaves: alloc::vec![B256::ZERO; 2],
+            siblings: Vec::new(),
+        };
+        assert!(!beyond.verify(root));
+        let empty = RangeProof::<5> {
+            start: 3,
+            leaves: Vec::new(),
+            siblings: Vec::new(),
+        };
+        assert!(!empty.verify(root));
+    }
+}
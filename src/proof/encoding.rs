@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+
+use super::{MerkleProof, MultiProof};
+use crate::IncrementalMerkleTreeError;
+
+// The canonical binary encodings of the proofs. Integers are big-endian, and every list is prefixed
+// with its length as a `u32`:
+//
+// - `MerkleProof`: `index: u64 || leaf: bytes32 || len: u32 || siblings: bytes32[len]`
+// - `MultiProof`: `len: u32 || indices: u64[len] || leaves: bytes32[len] || len: u32 ||
+//   siblings: bytes32[len] || len: u32 || flags`, where the flags are packed eight per byte, the
+//   first flag in the least significant bit, and the unused bits of the last byte are zero.
+
+impl<const HEIGHT: usize> MerkleProof<HEIGHT> {
+    /// Returns the canonical binary encoding of the proof:
+    /// `index: u64 || leaf: bytes32 || len: u32 || siblings: bytes32[len]`, big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 32 + 4 + 32 * HEIGHT);
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+        bytes.extend_from_slice(self.leaf.as_slice());
+        put_hashes(&mut bytes, &self.siblings);
+        bytes
+    }
+
+    /// Decode a proof from its canonical binary encoding, see [Self::to_bytes].
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the decoded proof.
+    /// - `Err(IncrementalMerkleTreeError::InvalidEncoding)` if `bytes` is truncated, has trailing
+    ///   bytes, or does not hold exactly `HEIGHT` siblings.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IncrementalMerkleTreeError> {
+        let mut reader = Reader(bytes);
+        let index = reader.index()?;
+        let leaf = reader.hash()?;
+        let siblings = reader
+            .hashes()?
+            .try_into()
+            .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?;
+        reader.finish()?;
+        Ok(Self {
+            leaf,
+            index,
+            siblings,
+        })
+    }
+}
+
+impl<const HEIGHT: usize> MultiProof<HEIGHT> {
+    /// Returns the canonical binary encoding of the proof: the length-prefixed indices as `u64`s, the
+    /// leaves, the length-prefixed siblings, and the number of flags followed by the flags packed
+    /// eight per byte, least significant bit first. Integers are big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            12 + 40 * self.indices.len() + 32 * self.siblings.len() + self.flags.len().div_ceil(8),
+        );
+        put_len(&mut bytes, self.indices.len());
+        for index in &self.indices {
+            bytes.extend_from_slice(&(*index as u64).to_be_bytes());
+        }
+        for leaf in &self.leaves {
+            bytes.extend_from_slice(leaf.as_slice());
+        }
+        put_hashes(&mut bytes, &self.siblings);
+        put_len(&mut bytes, self.flags.len());
+        for chunk in self.flags.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, &flag)| byte | (u8::from(flag) << bit));
+            bytes.push(byte);
+        }
+        bytes
+    }
+
+    /// Decode a proof from its canonical binary encoding, see [Self::to_bytes].
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the decoded proof.
+    /// - `Err(IncrementalMerkleTreeError::InvalidEncoding)` if `bytes` is truncated, has trailing
+    ///   bytes, or sets an unused flag bit.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IncrementalMerkleTreeError> {
+        let mut reader = Reader(bytes);
+        let len = reader.len()?;
+        let indices = (0..len)
+            .map(|_| reader.index())
+            .collect::<Result<Vec<_>, _>>()?;
+        let leaves = (0..len)
+            .map(|_| reader.hash())
+            .collect::<Result<Vec<_>, _>>()?;
+        let siblings = reader.hashes()?;
+        let flag_count = reader.len()?;
+        let packed = reader.take(flag_count.div_ceil(8))?;
+        let flags: Vec<bool> = (0..flag_count)
+            .map(|flag| (packed[flag / 8] >> (flag % 8)) & 1 == 1)
+            .collect();
+        if flag_count % 8 != 0 && packed[flag_count / 8] >> (flag_count % 8) != 0 {
+            return Err(IncrementalMerkleTreeError::InvalidEncoding);
+        }
+        reader.finish()?;
+        Ok(Self {
+            indices,
+            leaves,
+            siblings,
+            flags,
+        })
+    }
+}
+
+/// Append `len` as a big-endian `u32` length prefix.
+fn put_len(bytes: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).expect("list too long for a u32 length prefix");
+    bytes.extend_from_slice(&len.to_be_bytes());
+}
+
+/// Append the length-prefixed list of `hashes`.
+fn put_hashes(bytes: &mut Vec<u8>, hashes: &[B256]) {
+    put_len(bytes, hashes.len());
+    for hash in hashes {
+        bytes.extend_from_slice(hash.as_slice());
+    }
+}
+
+/// Reads the fields of an encoded proof from the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Consume the next `len` bytes.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IncrementalMerkleTreeError> {
+        if self.0.len() < len {
+            return Err(IncrementalMerkleTreeError::InvalidEncoding);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    /// Consume a `u32` length prefix.
+    fn len(&mut self) -> Result<usize, IncrementalMerkleTreeError> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().expect("took 4 bytes"));
+        Ok(len as usize)
+    }
+
+    /// Consume a `u64` leaf index.
+    fn index(&mut self) -> Result<usize, IncrementalMerkleTreeError> {
+        let index = u64::from_be_bytes(self.take(8)?.try_into().expect("took 8 bytes"));
+        usize::try_from(index).map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)
+    }
+
+    /// Consume a hash.
+    fn hash(&mut self) -> Result<B256, IncrementalMerkleTreeError> {
+        Ok(B256::from_slice(self.take(32)?))
+    }
+
+    /// Consume a length-prefixed list of hashes.
+    fn hashes(&mut self) -> Result<Vec<B256>, IncrementalMerkleTreeError> {
+        let len = self.len()?;
+        // Check the length against the remaining input before allocating for it.
+        let hashes = self.take(
+            len.checked_mul(32)
+                .ok_or(IncrementalMerkleTreeError::InvalidEncoding)?,
+        )?;
+        Ok(hashes.chunks_exact(32).map(B256::from_slice).collect())
+    }
+
+    /// Check that every byte has been consumed.
+    fn finish(&self) -> Result<(), IncrementalMerkleTreeError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(IncrementalMerkleTreeError::InvalidEncoding)
+        }
+    }
+}
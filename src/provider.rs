@@ -0,0 +1,182 @@
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use core::fmt;
+
+use crate::{
+    hasher::MerkleHasher, store::TreeStore, Checkpoint, IncrementalMerkleTree,
+    IncrementalMerkleTreeError,
+};
+
+/// A log emitted by the contract an [IncrementalMerkleTree] mirrors, such as a `DepositEvent`,
+/// reduced to what [sync_from_logs] needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeafLog {
+    /// The number of the block holding the log
+    pub block_number: u64,
+    /// The hash of the block holding the log
+    pub block_hash: B256,
+    /// The index of the log within its block
+    pub log_index: u64,
+    /// The leaf appended by the contract
+    pub leaf: B256,
+    /// The index the contract reports for the leaf, if the event carries one
+    pub leaf_index: Option<u64>,
+}
+
+/// [LogProvider] is the source of [LeafLog]s for [sync_from_logs], typically a thin adapter over an
+/// `alloy` provider's `get_logs` and `get_block_by_number` with the contract address and event
+/// signature of the mirrored contract.
+#[allow(async_fn_in_trait)]
+pub trait LogProvider {
+    /// The error returned by the provider
+    type Error;
+
+    /// Returns every leaf log emitted in the blocks `from_block..=to_block`, in any order.
+    async fn leaf_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<LeafLog>, Self::Error>;
+
+    /// Returns the hash of the canonical block `number`, or `None` if there is no such block.
+    async fn block_hash(&self, number: u64) -> Result<Option<B256>, Self::Error>;
+}
+
+/// Where [sync_from_logs] resumes from: the tree state after the last synced block, and the hash of
+/// that block to detect reorgs. Persist it next to the tree after every sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncCheckpoint<const HEIGHT: usize> {
+    /// The first block that has not been synced yet
+    pub next_block: u64,
+    /// The hash of the block `next_block - 1`, or `None` if nothing has been synced yet
+    pub last_block_hash: Option<B256>,
+    /// The state of the tree after the block `next_block - 1`
+    pub tree: Checkpoint<HEIGHT>,
+}
+
+impl<const HEIGHT: usize> SyncCheckpoint<HEIGHT> {
+    /// Create a checkpoint to sync `tree` from `from_block` on, e.g. the deployment block of the
+    /// contract for an empty tree.
+    pub fn new<H: MerkleHasher, S: TreeStore>(
+        from_block: u64,
+        tree: &IncrementalMerkleTree<HEIGHT, H, S>,
+    ) -> Self {
+        Self {
+            next_block: from_block,
+            last_block_hash: None,
+            tree: tree.checkpoint(),
+        }
+    }
+}
+
+/// The error returned by [sync_from_logs].
+#[derive(Debug)]
+pub enum SyncError<E> {
+    /// The provider failed
+    Provider(E),
+    /// The tree rejected the leaves, or the logs are inconsistent with it
+    Tree(IncrementalMerkleTreeError),
+    /// The last synced block is no longer canonical. Rewind to an older [SyncCheckpoint] with
+    /// [IncrementalMerkleTree::rollback] and sync again.
+    Reorg {
+        /// The number of the block that was reorganized away
+        block: u64,
+    },
+}
+
+impl<E: fmt::Display> fmt::Display for SyncError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Provider(error) => write!(f, "the log provider failed: {error}"),
+            Self::Tree(error) => write!(f, "the tree rejected the logs: {error}"),
+            Self::Reorg { block } => write!(f, "block {block} is no longer canonical"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for SyncError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Provider(error) => Some(error),
+            Self::Tree(error) => Some(error),
+            Self::Reorg { .. } => None,
+        }
+    }
+}
+
+impl<E> From<IncrementalMerkleTreeError> for SyncError<E> {
+    fn from(error: IncrementalMerkleTreeError) -> Self {
+        Self::Tree(error)
+    }
+}
+
+/// Stream the leaf logs of the blocks from `checkpoint.next_block` to `to_block` from `provider`,
+/// `chunk_size` blocks at a time, and append their leaves to `tree` in `(block, log index)` order.
+///
+/// The checkpoint is advanced after every chunk, so an interrupted sync resumes where it stopped. Each
+/// chunk is appended as a single batch, so a failing chunk leaves the tree at the last checkpoint.
+/// Pick a `to_block` the chain will not reorganize, such as the latest finalized block: a reorg below
+/// it is only detected, not repaired.
+///
+/// # Returns
+/// - `Ok(count)` with the number of appended leaves.
+/// - `Err(SyncError::Provider(error))` if the provider failed.
+/// - `Err(SyncError::Reorg { block })` if the last synced block is no longer canonical.
+/// - `Err(SyncError::Tree(IncrementalMerkleTreeError::CorruptedState))` if a log reports a leaf index
+///   other than the one it is appended at, or `tree` is not at the checkpoint.
+/// - `Err(SyncError::Tree(IncrementalMerkleTreeError::TreeFull { .. }))` if the leaves do not fit.
+///
+/// # Panics
+/// If `chunk_size` is zero.
+pub async fn sync_from_logs<const HEIGHT: usize, H: MerkleHasher, S: TreeStore, P: LogProvider>(
+    tree: &mut IncrementalMerkleTree<HEIGHT, H, S>,
+    provider: &P,
+    checkpoint: &mut SyncCheckpoint<HEIGHT>,
+    to_block: u64,
+    chunk_size: u64,
+) -> Result<usize, SyncError<P::Error>> {
+    assert!(chunk_size > 0, "chunk size must be positive");
+    if tree.len() != checkpoint.tree.size() {
+        return Err(IncrementalMerkleTreeError::CorruptedState.into());
+    }
+
+    let mut appended = 0;
+    while checkpoint.next_block <= to_block {
+        if let Some(expected) = checkpoint.last_block_hash {
+            let block = checkpoint.next_block - 1;
+            let canonical = provider
+                .block_hash(block)
+                .await
+                .map_err(SyncError::Provider)?;
+            if canonical != Some(expected) {
+                return Err(SyncError::Reorg { block });
+            }
+        }
+
+        let last = to_block.min(checkpoint.next_block.saturating_add(chunk_size - 1));
+        let mut logs = provider
+            .leaf_logs(checkpoint.next_block, last)
+            .await
+            .map_err(SyncError::Provider)?;
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        let mut leaves = Vec::with_capacity(logs.len());
+        for log in &logs {
+            let index = (tree.len() + leaves.len()) as u64;
+            if log.leaf_index.is_some_and(|leaf_index| leaf_index != index) {
+                return Err(IncrementalMerkleTreeError::CorruptedState.into());
+            }
+            leaves.push(log.leaf);
+        }
+        let last_block_hash = provider
+            .block_hash(last)
+            .await
+            .map_err(SyncError::Provider)?;
+        tree.append_batch(&leaves)?;
+        appended += leaves.len();
+
+        *checkpoint = SyncCheckpoint {
+            next_block: last + 1,
+            last_block_hash,
+            tree: tree.checkpoint(),
+        };
+    }
+    Ok(appended)
+}
@@ -0,0 +1,22 @@
+//! Serde helpers for `[B256; N]` arrays, which serde cannot derive for const-generic lengths. Arrays
+//! are written as sequences and must have exactly `N` entries when read back.
+
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer, const N: usize>(
+    array: &[B256; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(array)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[B256; N], D::Error> {
+    let entries = Vec::<B256>::deserialize(deserializer)?;
+    entries.try_into().map_err(|entries: Vec<B256>| {
+        D::Error::invalid_length(entries.len(), &"one hash per level")
+    })
+}
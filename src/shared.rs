@@ -0,0 +1,156 @@
+use alloc::{sync::Arc, vec::Vec};
+use alloy_primitives::B256;
+use arc_swap::ArcSwap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    proof::MerkleProof,
+    store::{MemoryStore, TreeStore},
+    IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// The writer side of a [SharedIncrementalMerkleTree].
+struct Writer<const HEIGHT: usize, H, S> {
+    /// The snapshot replaced by the last write, reused for the next one once no reader holds it
+    spare: Option<Arc<IncrementalMerkleTree<HEIGHT, H, S>>>,
+    /// The leaves appended to the current snapshot but not yet to the spare one
+    pending: Vec<B256>,
+}
+
+/// [SharedIncrementalMerkleTree] wraps an [IncrementalMerkleTree] for concurrent use: appends are
+/// serialized behind a lock, while roots, proofs and leaves are read lock-free from an immutable
+/// snapshot of the tree.
+///
+/// Every write brings a spare copy of the tree up to date, rehashes its cache and then atomically
+/// publishes it as the new snapshot, so readers never wait on a write or a cache rebuild. The spare
+/// copy is the previous snapshot, which only needs the leaves of the last write. If a reader still
+/// holds it, the current snapshot is cloned instead, so hold on to [Self::snapshot] only briefly, and
+/// prefer [Self::append_batch] when ingesting many leaves.
+pub struct SharedIncrementalMerkleTree<const HEIGHT: usize, H = Keccak256Hasher, S = MemoryStore> {
+    /// The snapshot served to readers
+    current: ArcSwap<IncrementalMerkleTree<HEIGHT, H, S>>,
+    /// The writer state, whose lock serializes appends
+    writer: Mutex<Writer<HEIGHT, H, S>>,
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Default + Clone> Default
+    for SharedIncrementalMerkleTree<HEIGHT, H, S>
+{
+    fn default() -> Self {
+        Self::new(IncrementalMerkleTree::new())
+    }
+}
+
+impl<const HEIGHT: usize, H: MerkleHasher, S: TreeStore + Clone>
+    SharedIncrementalMerkleTree<HEIGHT, H, S>
+{
+    /// Share `tree`, rehashing any stale nodes of its cache first.
+    pub fn new(mut tree: IncrementalMerkleTree<HEIGHT, H, S>) -> Self {
+        tree.recompute_intermediates();
+        Self {
+            current: ArcSwap::from_pointee(tree),
+            writer: Mutex::new(Writer {
+                spare: None,
+                pending: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns the current snapshot of the tree. Its cache is up to date, and it is not affected by
+    /// later appends.
+    pub fn snapshot(&self) -> Arc<IncrementalMerkleTree<HEIGHT, H, S>> {
+        self.current.load_full()
+    }
+
+    /// Returns the root of the current snapshot.
+    pub fn root(&self) -> B256 {
+        self.current.load().root()
+    }
+
+    /// Returns the number of leaves in the current snapshot.
+    pub fn len(&self) -> usize {
+        self.current.load().len()
+    }
+
+    /// Returns `true` if no leaf has been appended to the current snapshot.
+    pub fn is_empty(&self) -> bool {
+        self.current.load().is_empty()
+    }
+
+    /// Returns the leaf at `index` in the current snapshot, or `None` if it is not stored.
+    pub fn leaf(&self, index: usize) -> Option<B256> {
+        self.current.load().leaf(index)
+    }
+
+    /// Generate an inclusion proof for the leaf at `index` against the root of the current snapshot.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the leaf and its siblings.
+    /// - `Err(IncrementalMerkleTreeError::IndexOutOfBounds { .. })` if no leaf has been appended at
+    ///   `index`.
+    /// - `Err(IncrementalMerkleTreeError::LeafUnavailable)` if the leaf at `index` was appended before
+    ///   the tree was restored from a [Frontier](crate::Frontier).
+    pub fn prove(&self, index: usize) -> Result<MerkleProof<HEIGHT>, IncrementalMerkleTreeError> {
+        let snapshot = self.current.load();
+        let siblings = snapshot.siblings(index)?;
+        Ok(MerkleProof {
+            leaf: snapshot
+                .leaf(index)
+                .ok_or(IncrementalMerkleTreeError::LeafUnavailable)?,
+            index,
+            siblings,
+        })
+    }
+
+    /// Append a new leaf and publish the resulting snapshot.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the tree cannot hold any more leaves.
+    pub fn append(&self, leaf: B256) -> Result<(), IncrementalMerkleTreeError> {
+        self.append_batch(&[leaf])
+    }
+
+    /// Append every leaf in `leaves`, in order, and publish the resulting snapshot once.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every leaf was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the leaves do not all fit; the tree
+    ///   is left unchanged.
+    pub fn append_batch(&self, leaves: &[B256]) -> Result<(), IncrementalMerkleTreeError> {
+        let mut writer = self.lock_writer();
+        let Writer { spare, pending } = &mut *writer;
+
+        let mut next = match spare.take().map(Arc::try_unwrap) {
+            Some(Ok(mut tree)) => {
+                tree.append_batch(pending)
+                    .expect("the spare snapshot accepts the leaves of the current one");
+                tree
+            }
+            _ => IncrementalMerkleTree::clone(&self.current.load()),
+        };
+        pending.clear();
+
+        if let Err(error) = next.append_batch(leaves) {
+            *spare = Some(Arc::new(next));
+            return Err(error);
+        }
+        next.recompute_intermediates();
+        *spare = Some(self.current.swap(Arc::new(next)));
+        pending.extend_from_slice(leaves);
+        Ok(())
+    }
+
+    /// Lock the writer state. A panic during a previous write may have left the spare snapshot half
+    /// updated, so it is dropped in that case.
+    fn lock_writer(&self) -> MutexGuard<'_, Writer<HEIGHT, H, S>> {
+        self.writer.lock().unwrap_or_else(|poisoned| {
+            self.writer.clear_poison();
+            let mut writer = poisoned.into_inner();
+            writer.spare = None;
+            writer.pending.clear();
+            writer
+        })
+    }
+}
@@ -0,0 +1,212 @@
+//! Solidity ABI encoding of proofs, so they can be passed straight to an on-chain verifier.
+//!
+//! [MerkleProof::abi_encode] produces the encoding of the `MerkleProof` struct below, which the
+//! following verifier accepts for a keccak tree:
+//!
+//! ```solidity
+//! struct MerkleProof {
+//!     bytes32 leaf;
+//!     uint256 index;
+//!     bytes32[] siblings;
+//! }
+//!
+//! function verify(bytes32 root, MerkleProof memory proof) pure returns (bool) {
+//!     bytes32 node = proof.leaf;
+//!     uint256 index = proof.index;
+//!     for (uint256 i = 0; i < proof.siblings.length; i++) {
+//!         node = index & 1 == 1
+//!             ? keccak256(abi.encodePacked(proof.siblings[i], node))
+//!             : keccak256(abi.encodePacked(node, proof.siblings[i]));
+//!         index >>= 1;
+//!     }
+//!     return index == 0 && node == root;
+//! }
+//! ```
+//!
+//! A [MultiProof] is encoded as the `MultiProof` struct of [SolMultiProof].
+
+use alloc::vec::Vec;
+use alloy_primitives::U256;
+use alloy_sol_types::{sol, SolValue};
+
+use crate::{
+    proof::{MerkleProof, MultiProof},
+    IncrementalMerkleTreeError,
+};
+
+sol! {
+    /// The Solidity form of a [MerkleProof].
+    #[derive(Debug, PartialEq, Eq)]
+    struct SolMerkleProof {
+        bytes32 leaf;
+        uint256 index;
+        bytes32[] siblings;
+    }
+
+    /// The Solidity form of a [MultiProof].
+    #[derive(Debug, PartialEq, Eq)]
+    struct SolMultiProof {
+        uint256[] indices;
+        bytes32[] leaves;
+        bytes32[] siblings;
+        bool[] flags;
+    }
+}
+
+impl<const HEIGHT: usize> From<&MerkleProof<HEIGHT>> for SolMerkleProof {
+    fn from(proof: &MerkleProof<HEIGHT>) -> Self {
+        Self {
+            leaf: proof.leaf,
+            index: U256::from(proof.index),
+            siblings: proof.siblings.to_vec(),
+        }
+    }
+}
+
+impl<const HEIGHT: usize> TryFrom<SolMerkleProof> for MerkleProof<HEIGHT> {
+    type Error = IncrementalMerkleTreeError;
+
+    fn try_from(proof: SolMerkleProof) -> Result<Self, Self::Error> {
+        Ok(Self {
+            leaf: proof.leaf,
+            index: proof
+                .index
+                .try_into()
+                .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?,
+            siblings: proof
+                .siblings
+                .try_into()
+                .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?,
+        })
+    }
+}
+
+impl<const HEIGHT: usize> From<&MultiProof<HEIGHT>> for SolMultiProof {
+    fn from(proof: &MultiProof<HEIGHT>) -> Self {
+        Self {
+            indices: proof
+                .indices
+                .iter()
+                .map(|&index| U256::from(index))
+                .collect(),
+            leaves: proof.leaves.clone(),
+            siblings: proof.siblings.clone(),
+            flags: proof.flags.clone(),
+        }
+    }
+}
+
+impl<const HEIGHT: usize> TryFrom<SolMultiProof> for MultiProof<HEIGHT> {
+    type Error = IncrementalMerkleTreeError;
+
+    fn try_from(proof: SolMultiProof) -> Result<Self, Self::Error> {
+        Ok(Self {
+            indices: proof
+                .indices
+                .into_iter()
+                .map(|index| {
+                    index
+                        .try_into()
+                        .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)
+                })
+                .collect::<Result<_, _>>()?,
+            leaves: proof.leaves,
+            siblings: proof.siblings,
+            flags: proof.flags,
+        })
+    }
+}
+
+impl<const HEIGHT: usize> MerkleProof<HEIGHT> {
+    /// Returns the ABI encoding of the proof as a `MerkleProof` struct, see the [module](self)
+    /// documentation.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        SolMerkleProof::from(self).abi_encode()
+    }
+
+    /// Decode a proof from its ABI encoding as a `MerkleProof` struct.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the decoded proof.
+    /// - `Err(IncrementalMerkleTreeError::InvalidEncoding)` if `data` is not a valid encoding, or the
+    ///   proof does not have exactly `HEIGHT` siblings.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, IncrementalMerkleTreeError> {
+        SolMerkleProof::abi_decode(data, true)
+            .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?
+            .try_into()
+    }
+}
+
+impl<const HEIGHT: usize> MultiProof<HEIGHT> {
+    /// Returns the ABI encoding of the proof as a `MultiProof` struct, see [SolMultiProof].
+    pub fn abi_encode(&self) -> Vec<u8> {
+        SolMultiProof::from(self).abi_encode()
+    }
+
+    /// Decode a proof from its ABI encoding as a `MultiProof` struct.
+    ///
+    /// # Returns
+    /// - `Ok(proof)` with the decoded proof.
+    /// - `Err(IncrementalMerkleTreeError::InvalidEncoding)` if `data` is not a valid encoding.
+    pub fn abi_decode(data: &[u8]) -> Result<Self, IncrementalMerkleTreeError> {
+        SolMultiProof::abi_decode(data, true)
+            .map_err(|_| IncrementalMerkleTreeError::InvalidEncoding)?
+            .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{hash_pair, leaves},
+        IncrementalMerkleTree,
+    };
+    use alloy_primitives::B256;
+
+    /// Mirrors the Solidity verifier of the module documentation, reading the proof from the raw
+    /// ABI words as the contract would after `abi.decode(data, (MerkleProof))`.
+    fn solidity_verify(root: B256, data: &[u8]) -> bool {
+        let word = |offset: usize| B256::from_slice(&data[offset..offset + 32]);
+        let number = |offset: usize| U256::from_be_bytes(word(offset).0).to::<usize>();
+        let proof = number(0);
+        let mut node = word(proof);
+        let mut index = U256::from_be_bytes(word(proof + 32).0);
+        let siblings = proof + number(proof + 64);
+        for i in 0..number(siblings) {
+            let sibling = word(siblings + 32 * (i + 1));
+            node = if index.bit(0) {
+                hash_pair(sibling, node)
+            } else {
+                hash_pair(node, sibling)
+            };
+            index >>= 1;
+        }
+        index == U256::ZERO && node == root
+    }
+
+    #[test]
+    fn encoded_proofs_pass_the_solidity_verifier() {
+        let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(9)).unwrap();
+        let root = tree.root();
+        for index in 0..9 {
+            let proof = tree.prove(index).unwrap();
+            let encoded = proof.abi_encode();
+            assert_eq!(encoded.len(), 32 * (5 + 5));
+            assert!(solidity_verify(root, &encoded));
+            assert!(!solidity_verify(B256::ZERO, &encoded));
+            assert_eq!(MerkleProof::<5>::abi_decode(&encoded).unwrap(), proof);
+            assert!(MerkleProof::<4>::abi_decode(&encoded).is_err());
+        }
+    }
+
+    #[test]
+    fn encoded_multiproofs_round_trip() {
+        let mut tree = IncrementalMerkleTree::<5>::from_leaves(&leaves(9)).unwrap();
+        let proof = tree.prove_multi(&[1, 4, 8]).unwrap();
+        let decoded = MultiProof::<5>::abi_decode(&proof.abi_encode()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(tree.root()));
+        assert!(MultiProof::<5>::abi_decode(&[0u8; 31]).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use alloc::vec::Vec;
+use alloy_primitives::{B256, U256};
+use core::marker::PhantomData;
+use hashbrown::HashMap;
+
+use crate::hasher::{Keccak256Hasher, MerkleHasher};
+
+/// The depth of a [SparseMerkleTree]: one level per bit of a key.
+pub const SPARSE_DEPTH: usize = 256;
+
+/// An inclusion proof for the value stored under a key of a [SparseMerkleTree]. A proof of the zero
+/// value is an exclusion proof: it shows that nothing is stored under the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SparseProof {
+    /// The proven key
+    pub key: B256,
+    /// The value stored under the key, or [B256::ZERO] if there is none
+    pub value: B256,
+    /// The siblings on the path from the leaf up to the root, starting at the leaf level
+    pub siblings: Vec<B256>,
+}
+
+impl SparseProof {
+    /// Returns `true` if the proof shows that `self.value` is stored under `self.key` in the tree
+    /// committed to by `root`.
+    pub fn verify(&self, root: B256) -> bool {
+        self.verify_with::<Keccak256Hasher>(root)
+    }
+
+    /// Like [Self::verify], for a tree built with the hasher `H`.
+    pub fn verify_with<H: MerkleHasher>(&self, root: B256) -> bool {
+        if self.siblings.len() != SPARSE_DEPTH {
+            return false;
+        }
+        let key = U256::from_be_bytes(self.key.0);
+        let computed =
+            self.siblings
+                .iter()
+                .enumerate()
+                .fold(self.value, |node, (height, sibling)| {
+                    if key.bit(height) {
+                        H::hash_pair(sibling, &node)
+                    } else {
+                        H::hash_pair(&node, sibling)
+                    }
+                });
+        computed == root
+    }
+
+    /// Returns `true` if the proof shows that nothing is stored under `self.key` in the tree committed
+    /// to by `root`.
+    pub fn verify_exclusion(&self, root: B256) -> bool {
+        self.value.is_zero() && self.verify(root)
+    }
+}
+
+/// [SparseMerkleTree] is a key-value merkle tree of depth 256, with one leaf for every 256-bit key.
+/// Every leaf starts out as [B256::ZERO], so only the nodes above non-zero leaves are stored, and
+/// every other node is the zero hash of its height.
+///
+/// Bit `i` of a key, counting from the least significant bit, selects the direction at height `i`:
+/// when it is set, the node is the right child of its parent.
+pub struct SparseMerkleTree<H = Keccak256Hasher> {
+    /// The zero hashes, one per level including the root
+    zero_hashes: Vec<B256>,
+    /// The nodes that differ from the zero hash of their height, keyed by height and by the key
+    /// shifted right by the height
+    nodes: HashMap<(usize, U256), B256>,
+    /// The hash function combining sibling nodes
+    _hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> Default for SparseMerkleTree<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: MerkleHasher> SparseMerkleTree<H> {
+    /// Create a new, empty [SparseMerkleTree], precomputing its zero hashes.
+    pub fn new() -> Self {
+        let mut zero_hashes = Vec::with_capacity(SPARSE_DEPTH + 1);
+        zero_hashes.push(B256::ZERO);
+        for height in 1..=SPARSE_DEPTH {
+            let zero = zero_hashes[height - 1];
+            zero_hashes.push(H::hash_pair(&zero, &zero));
+        }
+        Self {
+            zero_hashes,
+            nodes: HashMap::new(),
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> B256 {
+        self.node(SPARSE_DEPTH, U256::ZERO)
+    }
+
+    /// Returns the value stored under `key`, or [B256::ZERO] if there is none.
+    pub fn get(&self, key: B256) -> B256 {
+        self.node(0, U256::from_be_bytes(key.0))
+    }
+
+    /// Store `value` under `key`, replacing any previous value, and rehash the path above it.
+    /// Storing [B256::ZERO] deletes the key.
+    pub fn insert(&mut self, key: B256, value: B256) {
+        let mut position = U256::from_be_bytes(key.0);
+        self.set_node(0, position, value);
+        let mut node = value;
+        for height in 0..SPARSE_DEPTH {
+            let sibling = self.node(height, position ^ U256::from(1));
+            node = if position.bit(0) {
+                H::hash_pair(&sibling, &node)
+            } else {
+                H::hash_pair(&node, &sibling)
+            };
+            position >>= 1;
+            self.set_node(height + 1, position, node);
+        }
+    }
+
+    /// Delete the value stored under `key`, if any.
+    pub fn remove(&mut self, key: B256) {
+        self.insert(key, B256::ZERO);
+    }
+
+    /// Generate a [SparseProof] of the value stored under `key`, which is an exclusion proof if there
+    /// is none.
+    pub fn prove(&self, key: B256) -> SparseProof {
+        let position = U256::from_be_bytes(key.0);
+        SparseProof {
+            key,
+            value: self.get(key),
+            siblings: (0..SPARSE_DEPTH)
+                .map(|height| self.node(height, (position >> height) ^ U256::from(1)))
+                .collect(),
+        }
+    }
+
+    /// Returns the node at `height` and horizontal `position`, or the zero hash of that height if it
+    /// is not stored.
+    fn node(&self, height: usize, position: U256) -> B256 {
+        self.nodes
+            .get(&(height, position))
+            .copied()
+            .unwrap_or(self.zero_hashes[height])
+    }
+
+    /// Store `node` at `height` and `position`, dropping it instead if it is the zero hash of that
+    /// height.
+    fn set_node(&mut self, height: usize, position: U256, node: B256) {
+        if node == self.zero_hashes[height] {
+            self.nodes.remove(&(height, position));
+        } else {
+            self.nodes.insert((height, position), node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the root of the subtree at `height` holding `entries`, which all share the bits of
+    /// their keys above `height`, by splitting them on the bit below it.
+    fn naive_root(zero_hashes: &[B256], height: usize, entries: &[(U256, B256)]) -> B256 {
+        if entries.is_empty() {
+            return zero_hashes[height];
+        }
+        if height == 0 {
+            return entries[0].1;
+        }
+        let (right, left): (Vec<_>, Vec<_>) =
+            entries.iter().partition(|(key, _)| key.bit(height - 1));
+        Keccak256Hasher::hash_pair(
+            &naive_root(zero_hashes, height - 1, &left),
+            &naive_root(zero_hashes, height - 1, &right),
+        )
+    }
+
+    /// Checks the root of `tree` against [naive_root] over `entries`, and the proof of every key.
+    fn assert_entries(tree: &SparseMerkleTree, entries: &[(B256, B256)]) {
+        let stored: Vec<_> = entries
+            .iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(key, value)| (U256::from_be_bytes(key.0), *value))
+            .collect();
+        assert_eq!(
+            tree.root(),
+            naive_root(&tree.zero_hashes, SPARSE_DEPTH, &stored)
+        );
+        for &(key, value) in entries {
+            assert_eq!(tree.get(key), value);
+            let proof = tree.prove(key);
+            assert_eq!(proof.value, value);
+            assert!(proof.verify(tree.root()));
+            assert_eq!(proof.verify_exclusion(tree.root()), value.is_zero());
+        }
+    }
+
+    /// Returns a key of all zero bytes but `first` and `last`.
+    fn key(first: u8, last: u8) -> B256 {
+        let mut key = B256::ZERO;
+        key[0] = first;
+        key[31] = last;
+        key
+    }
+
+    #[test]
+    fn inserts_overwrites_and_removals_match_the_naive_root() {
+        let mut tree = SparseMerkleTree::<Keccak256Hasher>::new();
+        let empty = tree.root();
+        assert_eq!(empty, tree.zero_hashes[SPARSE_DEPTH]);
+
+        let keys = [
+            key(0, 0),
+            key(0, 1),
+            key(0x80, 1),
+            key(0xff, 0xff),
+            key(0x12, 0x34),
+        ];
+        let mut entries: Vec<_> = keys.iter().map(|&key| (key, B256::ZERO)).collect();
+        for (index, key) in keys.into_iter().enumerate() {
+            let value = B256::repeat_byte(index as u8 + 1);
+            tree.insert(key, value);
+            entries[index].1 = value;
+            assert_entries(&tree, &entries);
+        }
+
+        // Overwriting a value rehashes its path, and leaves the other keys alone.
+        tree.insert(keys[2], B256::repeat_byte(0xaa));
+        entries[2].1 = B256::repeat_byte(0xaa);
+        assert_entries(&tree, &entries);
+
+        // Removing every key returns to the empty root, and drops every stored node.
+        for (index, key) in keys.into_iter().enumerate().rev() {
+            tree.remove(key);
+            entries[index].1 = B256::ZERO;
+            assert_entries(&tree, &entries);
+        }
+        assert_eq!(tree.root(), empty);
+        assert!(tree.nodes.is_empty());
+
+        // Storing the zero value is the same as removing the key.
+        tree.insert(keys[1], B256::repeat_byte(1));
+        tree.insert(keys[1], B256::ZERO);
+        assert_eq!(tree.root(), empty);
+        assert!(tree.nodes.is_empty());
+    }
+
+    #[test]
+    fn keys_differing_in_their_top_bit_have_their_own_proofs() {
+        let mut tree = SparseMerkleTree::<Keccak256Hasher>::new();
+        let (low, high) = (key(0, 7), key(0x80, 7));
+        tree.insert(low, B256::repeat_byte(1));
+        let root = tree.root();
+
+        // The key with the top bit set shares every sibling below the root with the stored one, and
+        // is still excluded.
+        let excluded = tree.prove(high);
+        assert!(excluded.verify_exclusion(root));
+        let included = tree.prove(low);
+        assert_eq!(
+            excluded.siblings[..SPARSE_DEPTH - 1],
+            included.siblings[..SPARSE_DEPTH - 1]
+        );
+        assert!(included.verify(root) && !included.verify_exclusion(root));
+
+        // Neither proof holds for the other key.
+        assert!(!SparseProof {
+            key: high,
+            ..included.clone()
+        }
+        .verify(root));
+        assert!(!SparseProof {
+            key: low,
+            ..excluded.clone()
+        }
+        .verify(root));
+
+        tree.insert(high, B256::repeat_byte(2));
+        assert_entries(
+            &tree,
+            &[
+                (low, B256::repeat_byte(1)),
+                (high, B256::repeat_byte(2)),
+                (key(0x80, 6), B256::ZERO),
+                (key(0x40, 7), B256::ZERO),
+            ],
+        );
+        assert!(!excluded.verify(tree.root()));
+    }
+
+    #[test]
+    fn malformed_proofs_are_rejected() {
+        let mut tree = SparseMerkleTree::<Keccak256Hasher>::new();
+        tree.insert(key(1, 2), B256::repeat_byte(3));
+        let root = tree.root();
+        let proof = tree.prove(key(1, 2));
+
+        let mut short = proof.clone();
+        short.siblings.pop();
+        assert!(!short.verify(root));
+        let mut long = proof.clone();
+        long.siblings.push(B256::ZERO);
+        assert!(!long.verify(root));
+        for height in [0, 100, SPARSE_DEPTH - 1] {
+            let mut tampered = proof.clone();
+            tampered.siblings[height] = B256::repeat_byte(9);
+            assert!(!tampered.verify(root));
+        }
+        let claimed_absent = SparseProof {
+            value: B256::ZERO,
+            ..proof
+        };
+        assert!(!claimed_absent.verify_exclusion(root));
+    }
+}
@@ -0,0 +1,125 @@
+use alloy_primitives::B256;
+
+use crate::{
+    hasher::{MerkleHasher, Sha256Hasher},
+    IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// Mix `length` into `root` as the SSZ spec's `mix_in_length` does: `sha256(root || length)`, with
+/// the length encoded as a little-endian 256-bit integer.
+pub fn mix_in_length(root: B256, length: usize) -> B256 {
+    let mut length_chunk = [0u8; 32];
+    length_chunk[..8].copy_from_slice(&(length as u64).to_le_bytes());
+    Sha256Hasher::hash_pair(&root, &B256::from(length_chunk))
+}
+
+/// [SszList] merkleizes a list of 32-byte chunks as the SSZ type `List[bytes32, 2^DEPTH]`, so that
+/// [SszList::hash_tree_root] matches consensus-layer `hash_tree_root` values.
+///
+/// Lists with a limit `N` that is not a power of two use `DEPTH = ceil(log2(N))`, since SSZ pads the
+/// limit to the next power of two. The list holds at most `2^DEPTH - 1` chunks, like every
+/// [IncrementalMerkleTree].
+#[derive(Debug, Clone)]
+pub struct SszList<const DEPTH: usize> {
+    /// The tree of chunks, padded to `DEPTH` with SHA-256 zero hashes
+    tree: IncrementalMerkleTree<DEPTH, Sha256Hasher>,
+}
+
+impl<const DEPTH: usize> Default for SszList<DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const DEPTH: usize> SszList<DEPTH> {
+    /// Create a new, empty [SszList].
+    pub fn new() -> Self {
+        Self {
+            tree: IncrementalMerkleTree::new(),
+        }
+    }
+
+    /// Append `chunk` to the end of the list.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the chunk was appended.
+    /// - `Err(IncrementalMerkleTreeError::TreeFull { .. })` if the list cannot hold any more chunks.
+    pub fn push(&mut self, chunk: B256) -> Result<(), IncrementalMerkleTreeError> {
+        self.tree.append(chunk)
+    }
+
+    /// Returns the underlying tree of chunks.
+    pub fn tree(&self) -> &IncrementalMerkleTree<DEPTH, Sha256Hasher> {
+        &self.tree
+    }
+
+    /// Compute the SSZ `hash_tree_root` of the list: the merkleized chunks with the list length mixed
+    /// in.
+    pub fn hash_tree_root(&self) -> B256 {
+        mix_in_length(self.tree.root(), self.tree.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::leaves;
+    use alloy_primitives::b256;
+    use sha2::{Digest, Sha256};
+
+    /// Returns the SSZ `merkleize` of `chunks` padded to `2^depth` chunks, hashed level by level.
+    fn merkleize(chunks: &[B256], depth: usize) -> B256 {
+        let mut level = chunks.to_vec();
+        level.resize(1 << depth, B256::ZERO);
+        for _ in 0..depth {
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    B256::from_slice(
+                        &Sha256::new()
+                            .chain_update(pair[0])
+                            .chain_update(pair[1])
+                            .finalize(),
+                    )
+                })
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn empty_lists_match_known_roots() {
+        // The root of an empty `List[Withdrawal, 16]`, as in execution payload headers of blocks
+        // without withdrawals.
+        assert_eq!(
+            SszList::<4>::new().hash_tree_root(),
+            b256!("792930bbd5baac43bcc798ee49aa8185ef76bb3b44ba62b91d86ae569e4bb535")
+        );
+        // The root of the empty `List[DepositData, 2^32]`, returned by `get_deposit_root()` of the
+        // deposit contract before the first deposit.
+        assert_eq!(
+            SszList::<32>::new().hash_tree_root(),
+            b256!("d70a234731285c6804c2a4f56711ddb8c82c99740f207854891028af34e27e5e")
+        );
+    }
+
+    #[test]
+    fn lists_match_merkleize_with_mixed_in_length() {
+        let chunks = leaves(11);
+        let mut list = SszList::<4>::new();
+        for (pushed, chunk) in chunks.iter().enumerate() {
+            list.push(*chunk).unwrap();
+            let mut length = [0u8; 32];
+            length[..8].copy_from_slice(&(pushed as u64 + 1).to_le_bytes());
+            let expected = Sha256::new()
+                .chain_update(merkleize(&chunks[..=pushed], 4))
+                .chain_update(length)
+                .finalize();
+            assert_eq!(list.hash_tree_root(), B256::from_slice(&expected));
+        }
+        for chunk in leaves(4) {
+            list.push(chunk).unwrap();
+        }
+        assert!(list.push(B256::ZERO).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use alloy_primitives::B256;
+use hashbrown::HashMap;
+
+/// [TreeStore] holds the intermediates cache of an [IncrementalMerkleTree](crate::IncrementalMerkleTree),
+/// one node per generalized index: `1` is the root, and the children of node `i` are `2i` and
+/// `2i + 1`. Implement it to back the cache with a key-value store such as sled or RocksDB instead of
+/// the heap.
+///
+/// The tree only reads nodes it has written, and never writes nodes whose subtree holds no appended
+/// leaves.
+pub trait TreeStore {
+    /// Returns the node at generalized index `index`, or `None` if it has not been written.
+    fn get(&self, index: usize) -> Option<B256>;
+
+    /// Write `node` at generalized index `index`, replacing any previous node.
+    fn put(&mut self, index: usize, node: B256);
+
+    /// Remove the node at generalized index `index`, if any.
+    fn remove(&mut self, index: usize);
+}
+
+/// [MemoryStore] is the default [TreeStore], keeping the written nodes in a hash map on the heap.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStore {
+    /// The written nodes, keyed by generalized index
+    nodes: HashMap<usize, B256>,
+}
+
+impl MemoryStore {
+    /// Returns the number of nodes held by the store.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the store holds no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl TreeStore for MemoryStore {
+    fn get(&self, index: usize) -> Option<B256> {
+        self.nodes.get(&index).copied()
+    }
+
+    fn put(&mut self, index: usize, node: B256) {
+        self.nodes.insert(index, node);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.nodes.remove(&index);
+    }
+}
@@ -0,0 +1,207 @@
+//! Build a tree from an asynchronous source of leaves, such as a network feed, without buffering the
+//! whole source or blocking the executor. See [StreamBuilder].
+
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_util::stream::{Stream, StreamExt, TryStream};
+
+use crate::{
+    hasher::{Keccak256Hasher, MerkleHasher},
+    store::{MemoryStore, TreeStore},
+    Checkpoint, IncrementalMerkleTree, IncrementalMerkleTreeError,
+};
+
+/// The number of leaves [StreamBuilder] appends between checkpoints unless told otherwise.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 1024;
+
+/// The error yielded by [StreamBuilder].
+#[derive(Debug)]
+pub enum StreamError<E> {
+    /// The stream of leaves failed
+    Stream(E),
+    /// The tree rejected the leaves
+    Tree(IncrementalMerkleTreeError),
+}
+
+impl<E: fmt::Display> fmt::Display for StreamError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stream(error) => write!(f, "the stream of leaves failed: {error}"),
+            Self::Tree(error) => write!(f, "the tree rejected the leaves: {error}"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for StreamError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Stream(error) => Some(error),
+            Self::Tree(error) => Some(error),
+        }
+    }
+}
+
+impl<E> From<IncrementalMerkleTreeError> for StreamError<E> {
+    fn from(error: IncrementalMerkleTreeError) -> Self {
+        Self::Tree(error)
+    }
+}
+
+/// [StreamBuilder] appends the leaves of a [TryStream] to an [IncrementalMerkleTree], and is itself a
+/// [Stream] of the [Checkpoint]s it reaches along the way.
+///
+/// Leaves are pulled from the source only while the builder is polled, and at most
+/// `checkpoint_interval` of them are buffered before they are appended as one batch and a checkpoint
+/// is yielded. A slow consumer, e.g. one persisting every checkpoint, therefore slows down the source
+/// instead of growing a buffer, and no single poll hashes more than one batch. A plain stream of
+/// leaves can be fed in with `stream.map(Ok::<_, Infallible>)`.
+///
+/// When the source fails, the leaves received before the failure are appended and checkpointed
+/// first, and the error is yielded after that checkpoint. The builder ends after yielding an error:
+///
+/// ```ignore
+/// let mut builder = StreamBuilder::new(&mut tree, leaves).checkpoint_every(4096);
+/// while let Some(checkpoint) = builder.next().await {
+///     persist(checkpoint?).await;
+/// }
+/// ```
+pub struct StreamBuilder<
+    'a,
+    const HEIGHT: usize,
+    St: TryStream<Ok = B256>,
+    H = Keccak256Hasher,
+    S = MemoryStore,
+> {
+    /// The tree the leaves are appended to
+    tree: &'a mut IncrementalMerkleTree<HEIGHT, H, S>,
+    /// The source of the leaves
+    leaves: St,
+    /// The number of leaves appended between checkpoints
+    checkpoint_interval: usize,
+    /// The leaves received since the last checkpoint
+    buffer: Vec<B256>,
+    /// An error of the source, yielded after the checkpoint of the leaves received before it
+    error: Option<St::Error>,
+    /// Whether the source has ended or an error has been yielded
+    done: bool,
+}
+
+impl<'a, const HEIGHT: usize, St, H, S> StreamBuilder<'a, HEIGHT, St, H, S>
+where
+    St: TryStream<Ok = B256> + Unpin,
+    H: MerkleHasher,
+    S: TreeStore,
+{
+    /// Create a builder appending the leaves of `leaves` to `tree`, yielding a checkpoint every
+    /// [DEFAULT_CHECKPOINT_INTERVAL] leaves.
+    pub fn new(tree: &'a mut IncrementalMerkleTree<HEIGHT, H, S>, leaves: St) -> Self {
+        Self {
+            tree,
+            leaves,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            buffer: Vec::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Yield a checkpoint every `leaves` leaves instead, which also bounds the number of buffered
+    /// leaves.
+    ///
+    /// # Panics
+    /// If `leaves` is zero.
+    pub fn checkpoint_every(mut self, leaves: usize) -> Self {
+        assert!(leaves > 0, "checkpoint interval must be positive");
+        self.checkpoint_interval = leaves;
+        self
+    }
+
+    /// Returns the tree the leaves are appended to.
+    pub fn tree(&self) -> &IncrementalMerkleTree<HEIGHT, H, S> {
+        self.tree
+    }
+
+    /// Append every remaining leaf of the source, ignoring the intermediate checkpoints.
+    ///
+    /// # Returns
+    /// - `Ok(checkpoint)` of the tree after the last leaf.
+    /// - `Err(StreamError::Stream(error))` if the source failed; the leaves received before the
+    ///   failure are appended.
+    /// - `Err(StreamError::Tree(IncrementalMerkleTreeError::TreeFull { .. }))` if the leaves do not
+    ///   fit; the tree is left at the last checkpoint.
+    pub async fn finish(mut self) -> Result<Checkpoint<HEIGHT>, StreamError<St::Error>> {
+        while let Some(checkpoint) = self.next().await {
+            checkpoint?;
+        }
+        Ok(self.tree.checkpoint())
+    }
+
+    /// Append the buffered leaves as one batch, and take a checkpoint of the tree.
+    fn flush(&mut self) -> Result<Checkpoint<HEIGHT>, StreamError<St::Error>> {
+        let appended = self.tree.append_batch(&self.buffer);
+        self.buffer.clear();
+        appended?;
+        Ok(self.tree.checkpoint())
+    }
+}
+
+// The builder never pins its fields: the source is polled through `Pin::new`, which requires it to be
+// `Unpin` already.
+impl<const HEIGHT: usize, St: TryStream<Ok = B256>, H, S> Unpin
+    for StreamBuilder<'_, HEIGHT, St, H, S>
+{
+}
+
+impl<const HEIGHT: usize, St, H, S> Stream for StreamBuilder<'_, HEIGHT, St, H, S>
+where
+    St: TryStream<Ok = B256> + Unpin,
+    H: MerkleHasher,
+    S: TreeStore,
+{
+    type Item = Result<Checkpoint<HEIGHT>, StreamError<St::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            this.done = true;
+            return Poll::Ready(Some(Err(StreamError::Stream(error))));
+        }
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        while this.buffer.len() < this.checkpoint_interval {
+            match Pin::new(&mut this.leaves).try_poll_next(cx) {
+                Poll::Ready(Some(Ok(leaf))) => this.buffer.push(leaf),
+                Poll::Ready(Some(Err(error))) => {
+                    if this.buffer.is_empty() {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(StreamError::Stream(error))));
+                    }
+                    this.error = Some(error);
+                    break;
+                }
+                Poll::Ready(None) => {
+                    this.done = true;
+                    if this.buffer.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    break;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let checkpoint = this.flush();
+        if checkpoint.is_err() {
+            this.error = None;
+            this.done = true;
+        }
+        Poll::Ready(Some(checkpoint))
+    }
+}
@@ -0,0 +1,49 @@
+//! Reference implementations the unit tests check the trees against.
+
+use alloc::vec::Vec;
+use alloy_primitives::{keccak256, B256};
+
+/// Returns the `keccak256` hash of `left` and `right` concatenated.
+pub(crate) fn hash_pair(left: B256, right: B256) -> B256 {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(left.as_slice());
+    buf[32..].copy_from_slice(right.as_slice());
+    keccak256(buf)
+}
+
+/// Returns the root of a keccak tree of `height` holding `leaves`, computed by padding the leaf row
+/// with zeros and folding it level by level.
+pub(crate) fn naive_root(leaves: &[B256], height: usize) -> B256 {
+    let mut level = leaves.to_vec();
+    level.resize(1 << height, B256::ZERO);
+    for _ in 0..height {
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(pair[0], pair[1]))
+            .collect();
+    }
+    level[0]
+}
+
+/// Returns `count` distinct leaves.
+pub(crate) fn leaves(count: usize) -> Vec<B256> {
+    (0..count as u64)
+        .map(|i| keccak256(i.to_be_bytes()))
+        .collect()
+}
+
+/// Returns the siblings on the path of the leaf at `index` in a keccak tree of `height` holding
+/// `leaves`, from the leaf level upwards.
+pub(crate) fn naive_proof(leaves: &[B256], height: usize, index: usize) -> Vec<B256> {
+    let mut level = leaves.to_vec();
+    level.resize(1 << height, B256::ZERO);
+    let mut siblings = Vec::with_capacity(height);
+    for position in (0..height).map(|height| index >> height) {
+        siblings.push(level[position ^ 1]);
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(pair[0], pair[1]))
+            .collect();
+    }
+    siblings
+}
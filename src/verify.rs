@@ -0,0 +1,145 @@
+//! Proof verification on its own, for on-chain programs and zkVM guests that only check proofs. It
+//! relies on nothing but [MerkleHasher] and never allocates. With `default-features = false`, it is
+//! all that is built next to the [hasher](crate::hasher) and [gindex](crate::gindex) modules, so the
+//! trees, the proof types and their dependencies are left out.
+//!
+//! The functions are re-exported from the crate root, and from the `proof` module with the `tree`
+//! feature.
+
+use alloy_primitives::B256;
+
+use crate::hasher::{Keccak256Hasher, MerkleHasher, SortedKeccak256Hasher};
+
+/// Verify that `leaf` sits at `index` in the tree committed to by `root`, given the `siblings` on its
+/// path from the leaf level upwards. The height of the tree is `siblings.len()`.
+///
+/// This needs neither a tree instance nor any allocation.
+///
+/// # Returns
+/// - `true` if the proof is valid, `false` otherwise, including when `index` does not fit in a tree
+///   of `siblings.len()` levels.
+pub fn verify(root: B256, leaf: B256, index: usize, siblings: &[B256]) -> bool {
+    verify_with::<Keccak256Hasher>(root, leaf, index, siblings)
+}
+
+/// Like [verify], for a tree built with the hasher `H`.
+pub fn verify_with<H: MerkleHasher>(
+    root: B256,
+    leaf: B256,
+    index: usize,
+    siblings: &[B256],
+) -> bool {
+    if siblings.len() < usize::BITS as usize && index >> siblings.len() != 0 {
+        return false;
+    }
+
+    let mut index = index;
+    let computed = siblings.iter().fold(leaf, |node, sibling| {
+        let parent = if index & 1 == 1 {
+            H::hash_pair(sibling, &node)
+        } else {
+            H::hash_pair(&node, sibling)
+        };
+        index >>= 1;
+        parent
+    });
+    computed == root
+}
+
+/// Verify that `leaf` is in the tree committed to by `root`, given the `siblings` on its path from the
+/// leaf level upwards, for a tree built with the [SortedKeccak256Hasher](crate::SortedKeccak256Hasher).
+/// This matches OpenZeppelin's `MerkleProof.verify`: every pair is hashed in ascending order, so no
+/// index is needed.
+///
+/// # Returns
+/// - `true` if the proof is valid, `false` otherwise.
+pub fn verify_sorted(root: B256, leaf: B256, siblings: &[B256]) -> bool {
+    let computed = siblings.iter().fold(leaf, |node, sibling| {
+        SortedKeccak256Hasher::hash_pair(&node, sibling)
+    });
+    computed == root
+}
+
+/// Recompute the root committed to by a merkle proof, given the leaf, its index and the siblings
+/// along its path from the leaf level upwards.
+///
+/// Bit `i` of `index` selects the direction at height `i`: when it is set, the current node is the
+/// right child and is hashed as `sibling || node`, otherwise as `node || sibling`.
+///
+/// # Returns
+/// - The root hash implied by the proof.
+pub fn recover_root<const HEIGHT: usize>(
+    leaf: B256,
+    index: usize,
+    siblings: &[B256; HEIGHT],
+) -> B256 {
+    let mut index = index;
+    siblings.iter().fold(leaf, |node, sibling| {
+        let parent = if index & 1 == 1 {
+            Keccak256Hasher::hash_pair(sibling, &node)
+        } else {
+            Keccak256Hasher::hash_pair(&node, sibling)
+        };
+        index >>= 1;
+        parent
+    })
+}
+
+/// Recompute the root committed to by a merkle proof, given the leaf, the siblings along its path
+/// and an explicit direction for every level.
+///
+/// `right_child[i]` states whether the node at height `i` is the right child of its parent, in which
+/// case it is hashed as `sibling || node`, otherwise as `node || sibling`. This is equivalent to
+/// [recover_root] for verifiers whose path representation is not a packed integer.
+///
+/// # Returns
+/// - The root hash implied by the proof.
+pub fn recover_root_with_directions<const HEIGHT: usize>(
+    leaf: B256,
+    siblings: &[B256; HEIGHT],
+    right_child: &[bool; HEIGHT],
+) -> B256 {
+    siblings
+        .iter()
+        .zip(right_child)
+        .fold(leaf, |node, (sibling, &is_right)| {
+            if is_right {
+                Keccak256Hasher::hash_pair(sibling, &node)
+            } else {
+                Keccak256Hasher::hash_pair(&node, sibling)
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{leaves, naive_proof, naive_root};
+
+    #[test]
+    fn directions_agree_with_index() {
+        let leaves = leaves(11);
+        let root = naive_root(&leaves, 4);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let siblings: [B256; 4] = naive_proof(&leaves, 4, index).try_into().unwrap();
+            let right_child = core::array::from_fn(|height| (index >> height) & 1 == 1);
+            assert_eq!(recover_root(*leaf, index, &siblings), root);
+            assert_eq!(
+                recover_root_with_directions(*leaf, &siblings, &right_child),
+                root
+            );
+        }
+    }
+
+    #[test]
+    fn flipped_direction_changes_root() {
+        let leaves = leaves(11);
+        let siblings: [B256; 4] = naive_proof(&leaves, 4, 5).try_into().unwrap();
+        let mut right_child = core::array::from_fn(|height| (5 >> height) & 1 == 1);
+        right_child[1] = !right_child[1];
+        assert_ne!(
+            recover_root_with_directions(leaves[5], &siblings, &right_child),
+            naive_root(&leaves, 4)
+        );
+    }
+}
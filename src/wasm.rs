@@ -0,0 +1,131 @@
+//! `wasm-bindgen` bindings for creating keccak trees, appending leaves, and generating and verifying
+//! proofs from JavaScript. Leaves, roots and siblings cross the boundary as `0x`-prefixed hex
+//! strings.
+//!
+//! Build the module with `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm
+//! --crate-type cdylib`, then run `wasm-bindgen` on the output.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use alloy_primitives::B256;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    dyn_tree::{DynIncrementalMerkleTree, DynMerkleProof},
+    IncrementalMerkleTreeError,
+};
+
+/// A keccak [DynIncrementalMerkleTree] exported to JavaScript as `MerkleTree`.
+#[wasm_bindgen(js_name = MerkleTree)]
+pub struct WasmMerkleTree {
+    /// The wrapped tree
+    tree: DynIncrementalMerkleTree,
+}
+
+#[wasm_bindgen(js_class = MerkleTree)]
+impl WasmMerkleTree {
+    /// Create a new, empty tree of the given `height`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(height: usize) -> Result<WasmMerkleTree, JsError> {
+        if height > <DynIncrementalMerkleTree>::MAX_HEIGHT {
+            return Err(JsError::new("tree height is too large"));
+        }
+        Ok(Self {
+            tree: DynIncrementalMerkleTree::new(height),
+        })
+    }
+
+    /// Returns the number of leaves that have been appended to the tree.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Append the leaf given as hex.
+    pub fn append(&mut self, leaf: &str) -> Result<(), JsError> {
+        self.tree.append(parse(leaf)?).map_err(tree_error)
+    }
+
+    /// Returns the root of the tree as hex.
+    pub fn root(&self) -> String {
+        self.tree.root().to_string()
+    }
+
+    /// Generate an inclusion proof for the leaf at `index`.
+    pub fn prove(&mut self, index: usize) -> Result<WasmMerkleProof, JsError> {
+        self.tree
+            .prove(index)
+            .map(|proof| WasmMerkleProof { proof })
+            .map_err(tree_error)
+    }
+}
+
+/// A [DynMerkleProof] exported to JavaScript as `MerkleProof`.
+#[wasm_bindgen(js_name = MerkleProof)]
+pub struct WasmMerkleProof {
+    /// The wrapped proof
+    proof: DynMerkleProof,
+}
+
+#[wasm_bindgen(js_class = MerkleProof)]
+impl WasmMerkleProof {
+    /// Create a proof from its leaf, index and siblings, all hashes given as hex.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        leaf: &str,
+        index: usize,
+        siblings: Vec<String>,
+    ) -> Result<WasmMerkleProof, JsError> {
+        Ok(Self {
+            proof: DynMerkleProof {
+                leaf: parse(leaf)?,
+                index,
+                siblings: siblings
+                    .iter()
+                    .map(|sibling| parse(sibling))
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
+
+    /// Returns the proven leaf as hex.
+    #[wasm_bindgen(getter)]
+    pub fn leaf(&self) -> String {
+        self.proof.leaf.to_string()
+    }
+
+    /// Returns the index of the proven leaf.
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.proof.index
+    }
+
+    /// Returns the siblings on the path from the leaf up to the root as hex, starting at the leaf
+    /// level.
+    #[wasm_bindgen(getter)]
+    pub fn siblings(&self) -> Vec<String> {
+        self.proof
+            .siblings
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Returns `true` if the proof shows that the leaf sits at its index under `root`, given as hex.
+    pub fn verify(&self, root: &str) -> Result<bool, JsError> {
+        Ok(self.proof.verify(parse(root)?))
+    }
+}
+
+/// Parse a 32-byte hash from hex, with or without the `0x` prefix.
+fn parse(hex: &str) -> Result<B256, JsError> {
+    hex.parse()
+        .map_err(|_| JsError::new("expected a 32-byte hex string"))
+}
+
+/// Convert a tree error into a JavaScript error.
+fn tree_error(error: IncrementalMerkleTreeError) -> JsError {
+    JsError::new(&error.to_string())
+}